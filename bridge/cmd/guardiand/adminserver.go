@@ -5,6 +5,7 @@ import (
 	"errors"
 	"fmt"
 	"math"
+	"math/big"
 	"net"
 	"os"
 	"time"
@@ -94,6 +95,168 @@ func adminContractUpgradeToVAA(req *nodev1.ContractUpgrade, guardianSetIndex uin
 	return v, nil
 }
 
+// adminTreasuryWithdrawalToVAA converts a nodev1.TreasuryWithdrawal message to its canonical VAA representation.
+// Returns an error if the data is invalid.
+func adminTreasuryWithdrawalToVAA(req *nodev1.TreasuryWithdrawal, guardianSetIndex uint32, timestamp uint32) (*vaa.VAA, error) {
+	if len(req.Recipient) != 32 {
+		return nil, errors.New("invalid recipient address")
+	}
+
+	if req.ChainId > math.MaxUint8 {
+		return nil, errors.New("invalid chain_id")
+	}
+
+	if req.Amount == 0 {
+		return nil, errors.New("invalid amount")
+	}
+
+	recipientAddress := vaa.Address{}
+	copy(recipientAddress[:], req.Recipient)
+
+	v := &vaa.VAA{
+		Version:          vaa.SupportedVAAVersion,
+		GuardianSetIndex: guardianSetIndex,
+		Timestamp:        time.Unix(int64(timestamp), 0),
+		Payload: &vaa.BodyTreasuryWithdrawal{
+			ChainID:   uint8(req.ChainId),
+			Recipient: recipientAddress,
+			Amount:    req.Amount,
+		},
+	}
+
+	return v, nil
+}
+
+// adminFreezeAccountToVAA converts a nodev1.FreezeAccount message to its canonical VAA representation.
+// Returns an error if the data is invalid.
+func adminFreezeAccountToVAA(req *nodev1.FreezeAccount, guardianSetIndex uint32, timestamp uint32) (*vaa.VAA, error) {
+	if len(req.Mint) != 32 {
+		return nil, errors.New("invalid mint address")
+	}
+
+	if len(req.TokenAccount) != 32 {
+		return nil, errors.New("invalid token_account address")
+	}
+
+	if req.ChainId > math.MaxUint8 {
+		return nil, errors.New("invalid chain_id")
+	}
+
+	mintAddress := vaa.Address{}
+	copy(mintAddress[:], req.Mint)
+	tokenAccountAddress := vaa.Address{}
+	copy(tokenAccountAddress[:], req.TokenAccount)
+
+	v := &vaa.VAA{
+		Version:          vaa.SupportedVAAVersion,
+		GuardianSetIndex: guardianSetIndex,
+		Timestamp:        time.Unix(int64(timestamp), 0),
+		Payload: &vaa.BodyFreezeAccount{
+			ChainID:      uint8(req.ChainId),
+			Mint:         mintAddress,
+			TokenAccount: tokenAccountAddress,
+			Freeze:       req.Freeze,
+		},
+	}
+
+	return v, nil
+}
+
+// adminHaltMintingToVAA converts a nodev1.HaltMinting message to its canonical VAA representation.
+// Returns an error if the data is invalid.
+func adminHaltMintingToVAA(req *nodev1.HaltMinting, guardianSetIndex uint32, timestamp uint32) (*vaa.VAA, error) {
+	if len(req.Mint) != 32 {
+		return nil, errors.New("invalid mint address")
+	}
+
+	if req.ChainId > math.MaxUint8 {
+		return nil, errors.New("invalid chain_id")
+	}
+
+	mintAddress := vaa.Address{}
+	copy(mintAddress[:], req.Mint)
+
+	v := &vaa.VAA{
+		Version:          vaa.SupportedVAAVersion,
+		GuardianSetIndex: guardianSetIndex,
+		Timestamp:        time.Unix(int64(timestamp), 0),
+		Payload: &vaa.BodyHaltMinting{
+			ChainID: uint8(req.ChainId),
+			Mint:    mintAddress,
+			Halt:    req.Halt,
+		},
+	}
+
+	return v, nil
+}
+
+// adminRevertTransferToVAA converts a nodev1.RevertTransfer message to its canonical VAA representation.
+// Returns an error if the data is invalid.
+func adminRevertTransferToVAA(req *nodev1.RevertTransfer, guardianSetIndex uint32, timestamp uint32) (*vaa.VAA, error) {
+	if len(req.SourceAddress) != 32 {
+		return nil, errors.New("invalid source_address")
+	}
+
+	if len(req.TargetAddress) != 32 {
+		return nil, errors.New("invalid target_address")
+	}
+
+	if len(req.TokenAddress) != 32 {
+		return nil, errors.New("invalid token_address")
+	}
+
+	if req.SourceChain > math.MaxUint8 {
+		return nil, errors.New("invalid source_chain")
+	}
+
+	if req.TargetChain > math.MaxUint8 {
+		return nil, errors.New("invalid target_chain")
+	}
+
+	if req.TokenChain > math.MaxUint8 {
+		return nil, errors.New("invalid token_chain")
+	}
+
+	if req.TokenDecimals > math.MaxUint8 {
+		return nil, errors.New("invalid token_decimals")
+	}
+
+	amount, ok := new(big.Int).SetString(req.Amount, 10)
+	if !ok || amount.Sign() <= 0 {
+		return nil, errors.New("invalid amount")
+	}
+
+	if req.TargetCheckTime == 0 {
+		return nil, errors.New("invalid target_check_time")
+	}
+
+	t := vaa.BodyTransfer{
+		Nonce:       req.Nonce,
+		SourceChain: vaa.ChainID(req.SourceChain),
+		TargetChain: vaa.ChainID(req.TargetChain),
+		Asset: &vaa.AssetMeta{
+			Chain:    vaa.ChainID(req.TokenChain),
+			Decimals: uint8(req.TokenDecimals),
+		},
+		Amount: amount,
+	}
+	copy(t.SourceAddress[:], req.SourceAddress)
+	copy(t.TargetAddress[:], req.TargetAddress)
+	copy(t.Asset.Address[:], req.TokenAddress)
+
+	v := &vaa.VAA{
+		Version:          vaa.SupportedVAAVersion,
+		GuardianSetIndex: guardianSetIndex,
+		Timestamp:        time.Unix(int64(timestamp), 0),
+		Payload: &vaa.BodyRevertTransfer{
+			BodyTransfer:    t,
+			TargetCheckTime: time.Unix(int64(req.TargetCheckTime), 0),
+		},
+	}
+
+	return v, nil
+}
+
 func (s *nodePrivilegedService) InjectGovernanceVAA(ctx context.Context, req *nodev1.InjectGovernanceVAARequest) (*nodev1.InjectGovernanceVAAResponse, error) {
 	s.logger.Info("governance VAA injected via admin socket", zap.String("request", req.String()))
 
@@ -106,6 +269,14 @@ func (s *nodePrivilegedService) InjectGovernanceVAA(ctx context.Context, req *no
 		v, err = adminGuardianSetUpdateToVAA(payload.GuardianSet, req.CurrentSetIndex, req.Timestamp)
 	case *nodev1.InjectGovernanceVAARequest_ContractUpgrade:
 		v, err = adminContractUpgradeToVAA(payload.ContractUpgrade, req.CurrentSetIndex, req.Timestamp)
+	case *nodev1.InjectGovernanceVAARequest_TreasuryWithdrawal:
+		v, err = adminTreasuryWithdrawalToVAA(payload.TreasuryWithdrawal, req.CurrentSetIndex, req.Timestamp)
+	case *nodev1.InjectGovernanceVAARequest_FreezeAccount:
+		v, err = adminFreezeAccountToVAA(payload.FreezeAccount, req.CurrentSetIndex, req.Timestamp)
+	case *nodev1.InjectGovernanceVAARequest_HaltMinting:
+		v, err = adminHaltMintingToVAA(payload.HaltMinting, req.CurrentSetIndex, req.Timestamp)
+	case *nodev1.InjectGovernanceVAARequest_RevertTransfer:
+		v, err = adminRevertTransferToVAA(payload.RevertTransfer, req.CurrentSetIndex, req.Timestamp)
 	default:
 		panic(fmt.Sprintf("unsupported VAA type: %T", payload))
 	}
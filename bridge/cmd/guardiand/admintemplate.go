@@ -22,6 +22,10 @@ func init() {
 
 	TemplateCmd.AddCommand(AdminClientGuardianSetTemplateCmd)
 	TemplateCmd.AddCommand(AdminClientContractUpgradeTemplateCmd)
+	TemplateCmd.AddCommand(AdminClientTreasuryWithdrawalTemplateCmd)
+	TemplateCmd.AddCommand(AdminClientFreezeAccountTemplateCmd)
+	TemplateCmd.AddCommand(AdminClientHaltMintingTemplateCmd)
+	TemplateCmd.AddCommand(AdminClientRevertTransferTemplateCmd)
 }
 
 var TemplateCmd = &cobra.Command{
@@ -43,6 +47,34 @@ var AdminClientContractUpgradeTemplateCmd = &cobra.Command{
 	Args:  cobra.ExactArgs(1),
 }
 
+var AdminClientTreasuryWithdrawalTemplateCmd = &cobra.Command{
+	Use:   "treasury-withdrawal [FILENAME]",
+	Short: "Generate an empty treasury withdrawal template at specified path (offline)",
+	Run:   runTreasuryWithdrawalTemplate,
+	Args:  cobra.ExactArgs(1),
+}
+
+var AdminClientFreezeAccountTemplateCmd = &cobra.Command{
+	Use:   "freeze-account [FILENAME]",
+	Short: "Generate an empty freeze account template at specified path (offline)",
+	Run:   runFreezeAccountTemplate,
+	Args:  cobra.ExactArgs(1),
+}
+
+var AdminClientHaltMintingTemplateCmd = &cobra.Command{
+	Use:   "halt-minting [FILENAME]",
+	Short: "Generate an empty halt minting template at specified path (offline)",
+	Run:   runHaltMintingTemplate,
+	Args:  cobra.ExactArgs(1),
+}
+
+var AdminClientRevertTransferTemplateCmd = &cobra.Command{
+	Use:   "revert-transfer [FILENAME]",
+	Short: "Generate an empty revert transfer template at specified path (offline)",
+	Run:   runRevertTransferTemplate,
+	Args:  cobra.ExactArgs(1),
+}
+
 func runGuardianSetTemplate(cmd *cobra.Command, args []string) {
 	path := args[0]
 
@@ -103,3 +135,123 @@ func runContractUpgradeTemplate(cmd *cobra.Command, args []string) {
 		log.Fatal(err)
 	}
 }
+
+func runTreasuryWithdrawalTemplate(cmd *cobra.Command, args []string) {
+	path := args[0]
+
+	m := &nodev1.InjectGovernanceVAARequest{
+		CurrentSetIndex: uint32(*templateGuardianIndex),
+		// Timestamp is hardcoded to make it reproducible on different devnet nodes.
+		// In production, a real UNIX timestamp should be used (see node.proto).
+		Timestamp: 1605744545,
+		Payload: &nodev1.InjectGovernanceVAARequest_TreasuryWithdrawal{
+			TreasuryWithdrawal: &nodev1.TreasuryWithdrawal{
+				ChainId:   1,
+				Recipient: make([]byte, 32),
+				Amount:    1,
+			},
+		},
+	}
+
+	b, err := prototext.MarshalOptions{Multiline: true}.Marshal(m)
+	if err != nil {
+		panic(err)
+	}
+
+	err = ioutil.WriteFile(path, b, 0640)
+	if err != nil {
+		log.Fatal(err)
+	}
+}
+
+func runFreezeAccountTemplate(cmd *cobra.Command, args []string) {
+	path := args[0]
+
+	m := &nodev1.InjectGovernanceVAARequest{
+		CurrentSetIndex: uint32(*templateGuardianIndex),
+		// Timestamp is hardcoded to make it reproducible on different devnet nodes.
+		// In production, a real UNIX timestamp should be used (see node.proto).
+		Timestamp: 1605744545,
+		Payload: &nodev1.InjectGovernanceVAARequest_FreezeAccount{
+			FreezeAccount: &nodev1.FreezeAccount{
+				ChainId:      1,
+				Mint:         make([]byte, 32),
+				TokenAccount: make([]byte, 32),
+				Freeze:       true,
+			},
+		},
+	}
+
+	b, err := prototext.MarshalOptions{Multiline: true}.Marshal(m)
+	if err != nil {
+		panic(err)
+	}
+
+	err = ioutil.WriteFile(path, b, 0640)
+	if err != nil {
+		log.Fatal(err)
+	}
+}
+
+func runHaltMintingTemplate(cmd *cobra.Command, args []string) {
+	path := args[0]
+
+	m := &nodev1.InjectGovernanceVAARequest{
+		CurrentSetIndex: uint32(*templateGuardianIndex),
+		// Timestamp is hardcoded to make it reproducible on different devnet nodes.
+		// In production, a real UNIX timestamp should be used (see node.proto).
+		Timestamp: 1605744545,
+		Payload: &nodev1.InjectGovernanceVAARequest_HaltMinting{
+			HaltMinting: &nodev1.HaltMinting{
+				ChainId: 1,
+				Mint:    make([]byte, 32),
+				Halt:    true,
+			},
+		},
+	}
+
+	b, err := prototext.MarshalOptions{Multiline: true}.Marshal(m)
+	if err != nil {
+		panic(err)
+	}
+
+	err = ioutil.WriteFile(path, b, 0640)
+	if err != nil {
+		log.Fatal(err)
+	}
+}
+
+func runRevertTransferTemplate(cmd *cobra.Command, args []string) {
+	path := args[0]
+
+	m := &nodev1.InjectGovernanceVAARequest{
+		CurrentSetIndex: uint32(*templateGuardianIndex),
+		// Timestamp is hardcoded to make it reproducible on different devnet nodes.
+		// In production, a real UNIX timestamp should be used (see node.proto).
+		Timestamp: 1605744545,
+		Payload: &nodev1.InjectGovernanceVAARequest_RevertTransfer{
+			RevertTransfer: &nodev1.RevertTransfer{
+				Nonce:           0,
+				SourceChain:     1,
+				TargetChain:     2,
+				SourceAddress:   make([]byte, 32),
+				TargetAddress:   make([]byte, 32),
+				TokenChain:      1,
+				TokenAddress:    make([]byte, 32),
+				TokenDecimals:   9,
+				Amount:          "1",
+				TargetCheckTime: 1605744545,
+			},
+		},
+	}
+
+	b, err := prototext.MarshalOptions{Multiline: true}.Marshal(m)
+	if err != nil {
+		panic(err)
+	}
+
+	err = ioutil.WriteFile(path, b, 0640)
+	if err != nil {
+		log.Fatal(err)
+	}
+}
@@ -41,6 +41,14 @@ func runGovernanceVAAVerify(cmd *cobra.Command, args []string) {
 		v, err = adminGuardianSetUpdateToVAA(payload.GuardianSet, msg.CurrentSetIndex, msg.Timestamp)
 	case *nodev1.InjectGovernanceVAARequest_ContractUpgrade:
 		v, err = adminContractUpgradeToVAA(payload.ContractUpgrade, msg.CurrentSetIndex, msg.Timestamp)
+	case *nodev1.InjectGovernanceVAARequest_TreasuryWithdrawal:
+		v, err = adminTreasuryWithdrawalToVAA(payload.TreasuryWithdrawal, msg.CurrentSetIndex, msg.Timestamp)
+	case *nodev1.InjectGovernanceVAARequest_FreezeAccount:
+		v, err = adminFreezeAccountToVAA(payload.FreezeAccount, msg.CurrentSetIndex, msg.Timestamp)
+	case *nodev1.InjectGovernanceVAARequest_HaltMinting:
+		v, err = adminHaltMintingToVAA(payload.HaltMinting, msg.CurrentSetIndex, msg.Timestamp)
+	case *nodev1.InjectGovernanceVAARequest_RevertTransfer:
+		v, err = adminRevertTransferToVAA(payload.RevertTransfer, msg.CurrentSetIndex, msg.Timestamp)
 	}
 	if err != nil {
 		log.Fatalf("invalid update: %v", err)
@@ -183,6 +183,50 @@ func (p *Processor) handleObservation(ctx context.Context, m *gossipv1.SignedObs
 						zap.String("bytes", hex.EncodeToString(vaaBytes)),
 						zap.Uint8("target_chain", t.ChainID))
 				}
+			case *vaa.BodyTreasuryWithdrawal:
+				switch t.ChainID {
+				case vaa.ChainIDSolana:
+				// Already submitted to Solana.
+				default:
+					p.logger.Error("unsupported target chain for treasury withdrawal",
+						zap.String("digest", hash),
+						zap.Any("vaa", signed),
+						zap.String("bytes", hex.EncodeToString(vaaBytes)),
+						zap.Uint8("target_chain", t.ChainID))
+				}
+			case *vaa.BodyFreezeAccount:
+				switch t.ChainID {
+				case vaa.ChainIDSolana:
+				// Already submitted to Solana.
+				default:
+					p.logger.Error("unsupported target chain for freezing an account",
+						zap.String("digest", hash),
+						zap.Any("vaa", signed),
+						zap.String("bytes", hex.EncodeToString(vaaBytes)),
+						zap.Uint8("target_chain", t.ChainID))
+				}
+			case *vaa.BodyHaltMinting:
+				switch t.ChainID {
+				case vaa.ChainIDSolana:
+				// Already submitted to Solana.
+				default:
+					p.logger.Error("unsupported target chain for halting minting",
+						zap.String("digest", hash),
+						zap.Any("vaa", signed),
+						zap.String("bytes", hex.EncodeToString(vaaBytes)),
+						zap.Uint8("target_chain", t.ChainID))
+				}
+			case *vaa.BodyRevertTransfer:
+				switch t.SourceChain {
+				case vaa.ChainIDSolana:
+				// Already submitted to Solana.
+				default:
+					p.logger.Error("unsupported source chain for reverting a transfer",
+						zap.String("digest", hash),
+						zap.Any("vaa", signed),
+						zap.String("bytes", hex.EncodeToString(vaaBytes)),
+						zap.Stringer("source_chain", t.SourceChain))
+				}
 			default:
 				panic(fmt.Sprintf("unknown VAA payload type: %+v", v))
 			}
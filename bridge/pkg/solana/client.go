@@ -52,14 +52,20 @@ func (s *SolanaWatcher) Run(ctx context.Context) error {
 						Commitment: rpc.CommitmentMax,
 						Filters: []rpc.RPCFilter{
 							{
-								DataSize: 1184, // Search for TransferOutProposal accounts
+								DataSize: 216, // Search for TransferOutProposal accounts
 							},
 							{
 								Memcmp: &rpc.RPCFilterMemcmp{
-									Offset: 1140,                      // Offset of VaaTime
+									Offset: 168,                       // Offset of VaaTime
 									Bytes:  solana.Base58{0, 0, 0, 0}, // VAA time is 0 when no VAA is present
 								},
 							},
+							{
+								Memcmp: &rpc.RPCFilterMemcmp{
+									Offset: 209,              // Offset of Reverted
+									Bytes:  solana.Base58{0}, // Reverted transfers must never be signed
+								},
+							},
 						},
 					})
 					if err != nil {
@@ -94,7 +100,7 @@ func (s *SolanaWatcher) Run(ctx context.Context) error {
 						lock := &common.ChainLock{
 							TxHash:        txHash,
 							Timestamp:     proposal.LockupTime,
-							Nonce:         proposal.Nonce,
+							Nonce:         proposal.Sequence,
 							SourceAddress: proposal.SourceAddress,
 							TargetAddress: proposal.ForeignAddress,
 							SourceChain:   vaa.ChainIDSolana,
@@ -127,8 +133,8 @@ type (
 		SourceAddress    vaa.Address
 		ForeignAddress   vaa.Address
 		Asset            vaa.AssetMeta
-		Nonce            uint32
-		VAA              [1001]byte
+		Sequence         uint32
+		VaaAccount       solana.PublicKey
 		VaaTime          time.Time
 		LockupTime       time.Time
 		PokeCounter      uint8
@@ -179,17 +185,14 @@ func ParseTransferOutProposal(data []byte) (*TransferOutProposal, error) {
 	// Skip alignment byte
 	r.Next(1)
 
-	if err := binary.Read(r, binary.LittleEndian, &prop.Nonce); err != nil {
-		return nil, fmt.Errorf("failed to read nonce: %w", err)
+	if err := binary.Read(r, binary.LittleEndian, &prop.Sequence); err != nil {
+		return nil, fmt.Errorf("failed to read sequence: %w", err)
 	}
 
-	if n, err := r.Read(prop.VAA[:]); err != nil || n != 1001 {
-		return nil, fmt.Errorf("failed to read vaa: %w", err)
+	if n, err := r.Read(prop.VaaAccount[:]); err != nil || n != 32 {
+		return nil, fmt.Errorf("failed to read vaa account: %w", err)
 	}
 
-	// Skip alignment bytes
-	r.Next(3)
-
 	var vaaTime uint32
 	if err := binary.Read(r, binary.LittleEndian, &vaaTime); err != nil {
 		return nil, fmt.Errorf("failed to read vaa time: %w", err)
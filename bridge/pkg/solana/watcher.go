@@ -4,7 +4,6 @@ import (
 	"context"
 	"encoding/hex"
 	"fmt"
-	"strings"
 	"time"
 
 	"google.golang.org/grpc"
@@ -58,7 +57,14 @@ func (e *SolanaVAASubmitter) Run(ctx context.Context) error {
 		return fmt.Errorf("failed to get balance: %v", err)
 	}
 	readiness.SetReady(common.ReadinessSolanaSyncing)
-	logger.Info("account balance", zap.Uint64("lamports", balance.Balance))
+	logger.Info("account balance", zap.Uint64("lamports", balance.Balance),
+		zap.Uint64("active_lamports", balance.ActiveBalance))
+	for _, p := range balance.Payers {
+		if !p.Active {
+			logger.Warn("fee payer below minimum balance, out of rotation",
+				zap.String("address", p.Address), zap.Uint64("lamports", p.Balance))
+		}
+	}
 
 	go func() {
 		for {
@@ -105,10 +111,10 @@ func (e *SolanaVAASubmitter) Run(ctx context.Context) error {
 							e.vaaChan <- v
 						}(v)
 
-					case codes.Internal:
-						// This VAA has already been executed on chain, successfully or not.
-						// TODO: dissect InstructionError in agent and convert this to the proper gRPC code
-						if strings.Contains(st.Message(), "custom program error: 0xb") { // AlreadyExists
+					case codes.FailedPrecondition:
+						// The VAA was rejected by the bridge. It might have already been executed on chain,
+						// successfully or not.
+						if pe := programError(st); pe != nil && pe.Name == "AlreadyExists" {
 							logger.Info("VAA already submitted on-chain, ignoring", zap.Error(err), zap.String("digest", h))
 							break
 						}
@@ -121,6 +127,16 @@ func (e *SolanaVAASubmitter) Run(ctx context.Context) error {
 					break
 				}
 
+				if res.AlreadySubmitted {
+					logger.Info("VAA already submitted on-chain, ignoring", zap.String("digest", h))
+					break
+				}
+
+				if res.Queued {
+					logger.Info("VAA queued for retry by the agent", zap.String("digest", h))
+					break
+				}
+
 				logger.Info("submitted VAA",
 					zap.String("tx_sig", res.Signature), zap.String("digest", h))
 			}
@@ -134,3 +150,13 @@ func (e *SolanaVAASubmitter) Run(ctx context.Context) error {
 		return err
 	}
 }
+
+// programError returns the bridge error attached to a status returned by the agent, if any.
+func programError(st *status.Status) *agentv1.ProgramError {
+	for _, d := range st.Details() {
+		if pe, ok := d.(*agentv1.ProgramError); ok {
+			return pe
+		}
+	}
+	return nil
+}
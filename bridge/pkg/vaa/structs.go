@@ -92,6 +92,42 @@ type (
 		// NewContract is the address of the account containing the new contract.
 		NewContract Address
 	}
+
+	BodyTreasuryWithdrawal struct {
+		// ChainID is the chain whose bridge pays out the withdrawal
+		ChainID uint8
+		// Recipient is the address receiving the withdrawn funds
+		Recipient Address
+		// Amount is the amount of the chain's native currency to be withdrawn
+		Amount uint64
+	}
+
+	BodyFreezeAccount struct {
+		// ChainID is the chain on which the token account should be frozen
+		ChainID uint8
+		// Mint is the address of the wrapped asset
+		Mint Address
+		// TokenAccount is the address of the token account to be frozen or thawed
+		TokenAccount Address
+		// Freeze specifies whether the account should be frozen or thawed
+		Freeze bool
+	}
+
+	BodyHaltMinting struct {
+		// ChainID is the chain on which minting should be halted
+		ChainID uint8
+		// Mint is the address of the wrapped asset
+		Mint Address
+		// Halt specifies whether minting should be halted or resumed
+		Halt bool
+	}
+
+	// BodyRevertTransfer reverts the lockup of the embedded transfer on its source chain
+	BodyRevertTransfer struct {
+		BodyTransfer
+		// TargetCheckTime is when the guardians verified that the transfer was not redeemed on its target chain
+		TargetCheckTime time.Time
+	}
 )
 
 func (a Address) String() string {
@@ -112,9 +148,13 @@ func (c ChainID) String() string {
 }
 
 const (
-	ActionGuardianSetUpdate Action = 0x01
-	ActionContractUpgrade   Action = 0x02
-	ActionTransfer          Action = 0x10
+	ActionGuardianSetUpdate  Action = 0x01
+	ActionContractUpgrade    Action = 0x02
+	ActionTreasuryWithdrawal Action = 0x03
+	ActionFreezeAccount      Action = 0x04
+	ActionHaltMinting        Action = 0x05
+	ActionRevertTransfer     Action = 0x06
+	ActionTransfer           Action = 0x10
 
 	// ChainIDSolana is the ChainID of Solana
 	ChainIDSolana = 1
@@ -192,6 +232,14 @@ func Unmarshal(data []byte) (*VAA, error) {
 		v.Payload, err = parseBodyTransfer(payloadReader)
 	case ActionContractUpgrade:
 		v.Payload, err = parseBodyContractUpgrade(payloadReader)
+	case ActionTreasuryWithdrawal:
+		v.Payload, err = parseBodyTreasuryWithdrawal(payloadReader)
+	case ActionFreezeAccount:
+		v.Payload, err = parseBodyFreezeAccount(payloadReader)
+	case ActionHaltMinting:
+		v.Payload, err = parseBodyHaltMinting(payloadReader)
+	case ActionRevertTransfer:
+		v.Payload, err = parseBodyRevertTransfer(payloadReader)
 	default:
 		return nil, fmt.Errorf("unknown action: %d", action)
 	}
@@ -440,6 +488,137 @@ func (v *BodyContractUpgrade) serialize() ([]byte, error) {
 	return buf.Bytes(), nil
 }
 
+func parseBodyTreasuryWithdrawal(r io.Reader) (*BodyTreasuryWithdrawal, error) {
+	b := &BodyTreasuryWithdrawal{}
+
+	if err := binary.Read(r, binary.BigEndian, &b.ChainID); err != nil {
+		return nil, fmt.Errorf("failed to read chain id: %w", err)
+	}
+
+	if n, err := r.Read(b.Recipient[:]); err != nil || n != 32 {
+		return nil, fmt.Errorf("failed to read recipient address: %w", err)
+	}
+
+	if err := binary.Read(r, binary.BigEndian, &b.Amount); err != nil {
+		return nil, fmt.Errorf("failed to read amount: %w", err)
+	}
+
+	return b, nil
+}
+
+func (v *BodyTreasuryWithdrawal) getActionID() Action {
+	return ActionTreasuryWithdrawal
+}
+
+func (v *BodyTreasuryWithdrawal) serialize() ([]byte, error) {
+	buf := new(bytes.Buffer)
+
+	MustWrite(buf, binary.BigEndian, v.ChainID)
+	buf.Write(v.Recipient[:])
+	MustWrite(buf, binary.BigEndian, v.Amount)
+
+	return buf.Bytes(), nil
+}
+
+func parseBodyFreezeAccount(r io.Reader) (*BodyFreezeAccount, error) {
+	b := &BodyFreezeAccount{}
+
+	if err := binary.Read(r, binary.BigEndian, &b.ChainID); err != nil {
+		return nil, fmt.Errorf("failed to read chain id: %w", err)
+	}
+
+	if n, err := r.Read(b.Mint[:]); err != nil || n != 32 {
+		return nil, fmt.Errorf("failed to read mint address: %w", err)
+	}
+
+	if n, err := r.Read(b.TokenAccount[:]); err != nil || n != 32 {
+		return nil, fmt.Errorf("failed to read token account address: %w", err)
+	}
+
+	if err := binary.Read(r, binary.BigEndian, &b.Freeze); err != nil {
+		return nil, fmt.Errorf("failed to read freeze flag: %w", err)
+	}
+
+	return b, nil
+}
+
+func (v *BodyFreezeAccount) getActionID() Action {
+	return ActionFreezeAccount
+}
+
+func (v *BodyFreezeAccount) serialize() ([]byte, error) {
+	buf := new(bytes.Buffer)
+
+	MustWrite(buf, binary.BigEndian, v.ChainID)
+	buf.Write(v.Mint[:])
+	buf.Write(v.TokenAccount[:])
+	MustWrite(buf, binary.BigEndian, v.Freeze)
+
+	return buf.Bytes(), nil
+}
+
+func parseBodyHaltMinting(r io.Reader) (*BodyHaltMinting, error) {
+	b := &BodyHaltMinting{}
+
+	if err := binary.Read(r, binary.BigEndian, &b.ChainID); err != nil {
+		return nil, fmt.Errorf("failed to read chain id: %w", err)
+	}
+
+	if n, err := r.Read(b.Mint[:]); err != nil || n != 32 {
+		return nil, fmt.Errorf("failed to read mint address: %w", err)
+	}
+
+	if err := binary.Read(r, binary.BigEndian, &b.Halt); err != nil {
+		return nil, fmt.Errorf("failed to read halt flag: %w", err)
+	}
+
+	return b, nil
+}
+
+func (v *BodyHaltMinting) getActionID() Action {
+	return ActionHaltMinting
+}
+
+func (v *BodyHaltMinting) serialize() ([]byte, error) {
+	buf := new(bytes.Buffer)
+
+	MustWrite(buf, binary.BigEndian, v.ChainID)
+	buf.Write(v.Mint[:])
+	MustWrite(buf, binary.BigEndian, v.Halt)
+
+	return buf.Bytes(), nil
+}
+
+func parseBodyRevertTransfer(r io.Reader) (*BodyRevertTransfer, error) {
+	t, err := parseBodyTransfer(r)
+	if err != nil {
+		return nil, err
+	}
+
+	checkTime := uint32(0)
+	if err := binary.Read(r, binary.BigEndian, &checkTime); err != nil {
+		return nil, fmt.Errorf("failed to read target check time: %w", err)
+	}
+
+	return &BodyRevertTransfer{BodyTransfer: *t, TargetCheckTime: time.Unix(int64(checkTime), 0)}, nil
+}
+
+func (v *BodyRevertTransfer) getActionID() Action {
+	return ActionRevertTransfer
+}
+
+func (v *BodyRevertTransfer) serialize() ([]byte, error) {
+	b, err := v.BodyTransfer.serialize()
+	if err != nil {
+		return nil, err
+	}
+
+	buf := bytes.NewBuffer(b)
+	MustWrite(buf, binary.BigEndian, uint32(v.TargetCheckTime.Unix()))
+
+	return buf.Bytes(), nil
+}
+
 // MustWrite calls binary.Write and panics on errors
 func MustWrite(w io.Writer, order binary.ByteOrder, data interface{}) {
 	if err := binary.Write(w, order, data); err != nil {
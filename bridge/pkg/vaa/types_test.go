@@ -79,6 +79,93 @@ func TestSerializeDeserialize(t *testing.T) {
 				},
 			},
 		},
+		{
+			name: "TreasuryWithdrawal",
+			vaa: &VAA{
+				Version:          1,
+				GuardianSetIndex: 9,
+				Signatures: []*Signature{
+					{
+						Index:     1,
+						Signature: [65]byte{},
+					},
+				},
+				Timestamp: time.Unix(2837, 0),
+				Payload: &BodyTreasuryWithdrawal{
+					ChainID:   ChainIDSolana,
+					Recipient: Address{4, 2, 9},
+					Amount:    1000000,
+				},
+			},
+		},
+		{
+			name: "FreezeAccount",
+			vaa: &VAA{
+				Version:          1,
+				GuardianSetIndex: 9,
+				Signatures: []*Signature{
+					{
+						Index:     1,
+						Signature: [65]byte{},
+					},
+				},
+				Timestamp: time.Unix(2837, 0),
+				Payload: &BodyFreezeAccount{
+					ChainID:      ChainIDSolana,
+					Mint:         Address{3, 7, 1},
+					TokenAccount: Address{4, 2, 9},
+					Freeze:       true,
+				},
+			},
+		},
+		{
+			name: "HaltMinting",
+			vaa: &VAA{
+				Version:          1,
+				GuardianSetIndex: 9,
+				Signatures: []*Signature{
+					{
+						Index:     1,
+						Signature: [65]byte{},
+					},
+				},
+				Timestamp: time.Unix(2837, 0),
+				Payload: &BodyHaltMinting{
+					ChainID: ChainIDSolana,
+					Mint:    Address{3, 7, 1},
+					Halt:    true,
+				},
+			},
+		},
+		{
+			name: "RevertTransfer",
+			vaa: &VAA{
+				Version:          1,
+				GuardianSetIndex: 9,
+				Signatures: []*Signature{
+					{
+						Index:     1,
+						Signature: [65]byte{},
+					},
+				},
+				Timestamp: time.Unix(2837, 0),
+				Payload: &BodyRevertTransfer{
+					BodyTransfer: BodyTransfer{
+						Nonce:         38,
+						SourceChain:   1,
+						TargetChain:   2,
+						SourceAddress: Address{2, 1, 4},
+						TargetAddress: Address{2, 1, 3},
+						Asset: &AssetMeta{
+							Chain:   9,
+							Address: Address{9, 2, 4},
+						},
+						Amount: big.NewInt(29),
+					},
+					TargetCheckTime: time.Unix(3000, 0),
+				},
+			},
+		},
 	}
 	for _, test := range tests {
 		t.Run(test.name, func(t *testing.T) {
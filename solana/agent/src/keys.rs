@@ -0,0 +1,193 @@
+//! Pool of fee payer keys
+//!
+//! Submissions are paid by the keys of the pool in turn. Keys whose balance drops below the
+//! minimum are taken out of rotation until they are topped up, so a single drained key does not
+//! halt submissions. The key files are read again on SIGHUP to add, remove or replace keys
+//! without a restart.
+
+use std::{
+    error::Error,
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use log::info;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signer},
+};
+use tonic::{Code, Status};
+
+use crate::{metrics, rpc::AsyncRpc};
+
+/// How the fee payer of a submission is chosen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Selection {
+    /// the keys in rotation take turns
+    RoundRobin,
+    /// the key in rotation with the highest balance
+    Balance,
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "round-robin" => Ok(Selection::RoundRobin),
+            "balance" => Ok(Selection::Balance),
+            _ => Err(format!("unknown key selection {}", s)),
+        }
+    }
+}
+
+pub struct FeePayer {
+    pub keypair: Arc<Keypair>,
+    /// balance at the last check
+    balance: AtomicU64,
+    /// the balance was above the minimum at the last check
+    active: AtomicBool,
+}
+
+impl FeePayer {
+    fn new(keypair: Keypair) -> Self {
+        // Keys are in rotation until their first balance check
+        FeePayer {
+            keypair: Arc::new(keypair),
+            balance: AtomicU64::new(0),
+            active: AtomicBool::new(true),
+        }
+    }
+
+    pub fn balance(&self) -> u64 {
+        self.balance.load(Ordering::Relaxed)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+}
+
+pub struct KeyPool {
+    paths: Vec<PathBuf>,
+    selection: Selection,
+    min_balance: u64,
+    payers: RwLock<Vec<Arc<FeePayer>>>,
+    next: AtomicUsize,
+}
+
+impl KeyPool {
+    pub fn load(
+        paths: Vec<PathBuf>,
+        selection: Selection,
+        min_balance: u64,
+    ) -> Result<Self, Box<dyn Error>> {
+        let payers = read_keys(&paths)?
+            .into_iter()
+            .map(|k| Arc::new(FeePayer::new(k)))
+            .collect();
+
+        Ok(KeyPool {
+            paths,
+            selection,
+            min_balance,
+            payers: RwLock::new(payers),
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Reads the key files again. Keys that remain in the pool keep their state.
+    pub fn reload(&self) -> Result<(), Box<dyn Error>> {
+        let keys = read_keys(&self.paths)?;
+
+        let mut payers = self.payers.write().unwrap();
+        let reloaded = keys
+            .into_iter()
+            .map(|k| {
+                payers
+                    .iter()
+                    .find(|p| p.keypair.pubkey() == k.pubkey())
+                    .cloned()
+                    .unwrap_or_else(|| Arc::new(FeePayer::new(k)))
+            })
+            .collect();
+        *payers = reloaded;
+        Ok(())
+    }
+
+    /// Returns the fee payers of the pool
+    pub fn payers(&self) -> Vec<Arc<FeePayer>> {
+        self.payers.read().unwrap().clone()
+    }
+
+    /// Chooses the fee payer of the next submission among the keys in rotation
+    pub fn select(&self) -> Result<Arc<Keypair>, Status> {
+        let payers = self.payers.read().unwrap();
+        let active: Vec<&Arc<FeePayer>> = payers.iter().filter(|p| p.is_active()).collect();
+        if active.is_empty() {
+            return Err(Status::new(
+                Code::ResourceExhausted,
+                "all fee payers are below the minimum balance",
+            ));
+        }
+
+        let payer = match self.selection {
+            Selection::RoundRobin => {
+                active[self.next.fetch_add(1, Ordering::Relaxed) % active.len()]
+            }
+            Selection::Balance => active.iter().copied().max_by_key(|p| p.balance()).unwrap(),
+        };
+        Ok(payer.keypair.clone())
+    }
+
+    /// Fetches the balances of the fee payers, taking keys below the minimum balance out of
+    /// rotation and putting topped up keys back in
+    pub async fn refresh(&self, rpc: &AsyncRpc) -> Result<(), Status> {
+        let payers = self.payers();
+        let keys: Vec<Pubkey> = payers.iter().map(|p| p.keypair.pubkey()).collect();
+        let balances = rpc
+            .run(move |rpc| {
+                keys.iter()
+                    .map(|k| rpc.get_balance(k))
+                    .collect::<Result<Vec<u64>, _>>()
+                    .map_err(|e| {
+                        Status::new(Code::Unavailable, format!("failed to fetch balance: {}", e))
+                    })
+            })
+            .await?;
+
+        for (payer, balance) in payers.iter().zip(balances) {
+            let address = payer.keypair.pubkey();
+            metrics::FEE_PAYER_BALANCE
+                .with_label_values(&[&address.to_string()])
+                .set(balance as i64);
+
+            payer.balance.store(balance, Ordering::Relaxed);
+            let active = balance >= self.min_balance;
+            let was_active = payer.active.swap(active, Ordering::Relaxed);
+            if was_active && !active {
+                info!(
+                    "fee payer {} balance {} is below {}, taking it out of rotation",
+                    address, balance, self.min_balance
+                );
+            } else if !was_active && active {
+                info!("fee payer {} was topped up, putting it back in rotation", address);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_keys(paths: &[PathBuf]) -> Result<Vec<Keypair>, Box<dyn Error>> {
+    paths
+        .iter()
+        .map(|path| {
+            read_keypair_file(path)
+                .map_err(|e| format!("could not read key {}: {}", path.display(), e).into())
+        })
+        .collect()
+}
@@ -1,310 +1,423 @@
-use std::{env, io::Write, mem::size_of, str::FromStr, fs};
-use std::path::Path;
+use std::{env, io::Write, mem::size_of, net::SocketAddr, str::FromStr, fs, sync::Arc, time::Duration};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use libc;
 
 use clap::{Arg, App, SubCommand};
 
-use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
-use futures::stream::TryStreamExt;
-use solana_client::{
-    client_error::ClientError, rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig,
-};
+use futures::{future::join_all, stream::TryStreamExt};
+use log::{error, info, warn};
 use solana_sdk::{
-    commitment_config::{CommitmentConfig, CommitmentLevel},
-    instruction::Instruction,
     pubkey::Pubkey,
-    signature::{read_keypair_file, write_keypair_file, Keypair, Signature, Signer},
-    transaction::Transaction,
+    signature::{write_keypair_file, Signer},
 };
 use tokio::net::UnixListener;
-use tokio::sync::mpsc;
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::{broadcast::RecvError, mpsc};
 use tonic::{transport::Server, Code, Request, Response, Status};
+use tonic_health::server::HealthReporter;
 
 use service::{
     agent_server::{Agent, AgentServer},
     Empty,SubmitVaaRequest, SubmitVaaResponse,
-    GetBalanceResponse, GetBalanceRequest,
+    SubmitVaaBatchRequest, SubmitVaaBatchResponse, SubmitVaaResult,
+    GetBalanceResponse, GetBalanceRequest, FeePayer,
+    Lockup, WatchLockupsRequest,
+    get_vaa_status_request::Query, GetVaaStatusRequest, GetVaaStatusResponse,
+    DeadLetter, GetQueueStatusRequest, GetQueueStatusResponse,
 };
-use spl_bridge::{
-    instruction::{post_vaa, verify_signatures, VerifySigPayload, CHAIN_ID_SOLANA},
-    state::{Bridge, GuardianSet, TransferOutProposal},
-    vaa::VAA,
+use wormhole_solana_client::{
+    ops::fetch_guardian_set,
+    spl_bridge::{state::GuardianSet, vaa::VAA},
+    Pda,
 };
 
+mod keys;
+mod metrics;
+mod queue;
+mod rpc;
 mod socket;
+mod status;
+mod submit;
+mod watcher;
+
+use keys::{KeyPool, Selection};
+use queue::Queue;
+use rpc::AsyncRpc;
+use submit::Submitter;
+use watcher::{LockupWatcher, LOCKUP_BUFFER};
 
 pub mod service {
     include!(concat!(env!("OUT_DIR"), concat!("/", "agent.v1", ".rs")));
 }
 
+/// Interval of the health checks of the RPC node and the fee payer
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
 pub struct AgentImpl {
-    url: String,
+    watcher: LockupWatcher,
     bridge: Pubkey,
 
-    rpc_url: String,
-    key: Keypair,
+    rpc: AsyncRpc,
+    keys: Arc<KeyPool>,
+    queue: Arc<Queue>,
 }
 
-pub struct SignatureItem {
-    signature: [u8; 64 + 1],
-    key: [u8; 20],
-    index: u8,
+impl AgentImpl {
+    fn submitter(&self) -> Submitter {
+        Submitter {
+            bridge: self.bridge,
+            rpc: self.rpc.clone(),
+            keys: self.keys.clone(),
+        }
+    }
+
+    /// Parses `data` and journals the VAA before the first attempt, so that it is retried if the
+    /// attempt fails, the agent restarts or the request is cancelled
+    fn journal(&self, data: &[u8]) -> Result<([u8; 32], Arc<VAA>), Status> {
+        let vaa = VAA::deserialize(data).map_err(|e| {
+            Status::new(Code::InvalidArgument, format!("could not parse VAA: {}", e))
+        })?;
+        let hash = vaa.body_hash().map_err(|e| {
+            Status::new(Code::InvalidArgument, format!("could not hash VAA: {}", e))
+        })?;
+        self.queue.push(&hash, data)?;
+
+        Ok((hash, Arc::new(vaa)))
+    }
 }
 
 #[tonic::async_trait]
 impl Agent for AgentImpl {
+    type WatchLockupsStream = mpsc::Receiver<Result<Lockup, Status>>;
+
     async fn submit_vaa(
         &self,
         request: Request<SubmitVaaRequest>,
     ) -> Result<Response<SubmitVaaResponse>, Status> {
-        // Hack to clone keypair
-        let b = self.key.to_bytes();
-        let key = Keypair::from_bytes(&b).unwrap();
-        let bridge = self.bridge.clone();
-
-        let rpc_url = self.rpc_url.clone();
-
-        // we need to spawn an extra thread because tokio does not allow nested runtimes
-        std::thread::spawn(move || {
-            let rpc = RpcClient::new(rpc_url);
-
-            let mut vaa = match VAA::deserialize(&request.get_ref().vaa) {
-                Ok(v) => v,
-                Err(e) => {
-                    return Err(Status::new(
-                        Code::InvalidArgument,
-                        format!("could not parse VAA: {}", e),
-                    ));
-                }
-            };
-            let verify_txs = pack_sig_verification_txs(&rpc, &bridge, &vaa, &key)?;
-
-            // Strip signatures
-            vaa.signatures = Vec::new();
-            let ix = match post_vaa(&bridge, &key.pubkey(), vaa.serialize().unwrap()) {
-                Ok(v) => v,
-                Err(e) => {
-                    return Err(Status::new(
-                        Code::InvalidArgument,
-                        format!("could not create post_vaa instruction: {}", e),
-                    ));
-                }
-            };
+        let (hash, vaa) = self.journal(&request.get_ref().vaa)?;
+        queue::attempt(&self.queue, &self.submitter(), hash, vaa, None).await
+    }
 
-            for mut tx in verify_txs {
-                match sign_and_send(&rpc, &mut tx, vec![&key]) {
-                    Ok(_) => (),
+    async fn submit_vaa_batch(
+        &self,
+        request: Request<SubmitVaaBatchRequest>,
+    ) -> Result<Response<SubmitVaaBatchResponse>, Status> {
+        let pda = Pda::new(&self.bridge).map_err(|e| {
+            Status::new(Code::Internal, format!("could not derive bridge: {}", e))
+        })?;
+        let vaas: Vec<Result<([u8; 32], Arc<VAA>), Status>> = request
+            .get_ref()
+            .vaas
+            .iter()
+            .map(|data| self.journal(data))
+            .collect();
+
+        // Each guardian set is only fetched once for the batch. VAAs of sets that could not be
+        // fetched try again on their own and report the error of that attempt.
+        let indices: HashSet<u32> = vaas
+            .iter()
+            .filter_map(|v| v.as_ref().ok())
+            .map(|(_, vaa)| vaa.guardian_set_index)
+            .collect();
+        let fetched = self
+            .rpc
+            .run(move |rpc| {
+                Ok(indices
+                    .into_iter()
+                    .map(|i| (i, fetch_guardian_set(rpc, &pda, i).map_err(|e| e.to_string())))
+                    .collect::<Vec<_>>())
+            })
+            .await;
+        let guardian_sets: HashMap<u32, GuardianSet> = match fetched {
+            Ok(sets) => sets
+                .into_iter()
+                .filter_map(|(i, set)| match set {
+                    Ok(set) => Some((i, set)),
                     Err(e) => {
-                        return Err(Status::new(
-                            Code::Internal,
-                            format!("tx sending failed: {}", e),
-                        ));
+                        warn!("could not fetch guardian set {} of batch: {}", i, e);
+                        None
                     }
-                };
+                })
+                .collect(),
+            Err(e) => {
+                warn!("could not fetch guardian sets of batch: {}", e.message());
+                HashMap::new()
             }
+        };
 
-            let mut transaction2 = Transaction::new_with_payer(&[ix], Some(&key.pubkey()));
-            match sign_and_send(&rpc, &mut transaction2, vec![&key]) {
-                Ok(s) => Ok(Response::new(SubmitVaaResponse {
-                    signature: s.to_string(),
-                })),
-                Err(e) => Err(Status::new(
-                    Code::Internal,
-                    format!("tx sending failed: {}", e),
-                )),
+        // The VAAs are submitted concurrently, bounded by the RPC concurrency of the agent
+        let submitter = self.submitter();
+        let results = join_all(vaas.into_iter().map(|v| {
+            let queue = &self.queue;
+            let submitter = &submitter;
+            let guardian_sets = &guardian_sets;
+            async move {
+                let (hash, vaa) = v?;
+                let guardian_set = guardian_sets.get(&vaa.guardian_set_index).copied();
+                queue::attempt(queue, submitter, hash, vaa, guardian_set).await
             }
-        })
-            .join()
-            .unwrap()
+        }))
+        .await;
+
+        let results = results
+            .into_iter()
+            .map(|r| match r {
+                Ok(response) => SubmitVaaResult {
+                    response: Some(response.into_inner()),
+                    code: Code::Ok as i32,
+                    error: String::new(),
+                },
+                Err(e) => SubmitVaaResult {
+                    response: None,
+                    code: e.code() as i32,
+                    error: e.message().to_string(),
+                },
+            })
+            .collect();
+
+        Ok(Response::new(SubmitVaaBatchResponse { results }))
+    }
+
+    async fn get_queue_status(
+        &self,
+        _request: Request<GetQueueStatusRequest>,
+    ) -> Result<Response<GetQueueStatusResponse>, Status> {
+        let dead_letters = self
+            .queue
+            .dead_letters()?
+            .into_iter()
+            .map(|e| DeadLetter {
+                vaa: e.vaa,
+                attempts: e.attempts,
+                error: e.last_error,
+                rejected_at: e.last_attempt,
+            })
+            .collect();
+
+        Ok(Response::new(GetQueueStatusResponse {
+            pending: self.queue.depth() as u64,
+            dead_letters,
+        }))
     }
 
     async fn get_balance(
         &self,
-        request: Request<GetBalanceRequest>,
+        _request: Request<GetBalanceRequest>,
     ) -> Result<Response<GetBalanceResponse>, Status> {
-        // Hack to clone keypair
-        let b = self.key.pubkey();
-
-        let rpc_url = self.rpc_url.clone();
-
-        // we need to spawn an extra thread because tokio does not allow nested runtimes
-        std::thread::spawn(move || {
-            let rpc = RpcClient::new(rpc_url);
+        self.keys.refresh(&self.rpc).await?;
+
+        let payers: Vec<FeePayer> = self
+            .keys
+            .payers()
+            .iter()
+            .map(|p| FeePayer {
+                address: p.keypair.pubkey().to_string(),
+                balance: p.balance(),
+                active: p.is_active(),
+            })
+            .collect();
+        let balance = payers.iter().map(|p| p.balance).sum();
+        let active_balance = payers.iter().filter(|p| p.active).map(|p| p.balance).sum();
+
+        Ok(Response::new(GetBalanceResponse {
+            balance,
+            payers,
+            active_balance,
+        }))
+    }
 
-            let balance = match rpc.get_balance(&b) {
-                Ok(v) => v,
-                Err(e) => {
+    async fn get_vaa_status(
+        &self,
+        request: Request<GetVaaStatusRequest>,
+    ) -> Result<Response<GetVaaStatusResponse>, Status> {
+        let pda = Pda::new(&self.bridge).map_err(|e| {
+            Status::new(Code::Internal, format!("could not derive bridge: {}", e))
+        })?;
+        let guardian_set_index = request.get_ref().guardian_set_index;
+        let status = match request.into_inner().query {
+            Some(Query::Vaa(data)) => {
+                let vaa = VAA::deserialize(&data).map_err(|e| {
+                    Status::new(Code::InvalidArgument, format!("could not parse VAA: {}", e))
+                })?;
+                self.rpc
+                    .run(move |rpc| {
+                        status::fetch(rpc, &pda, &vaa).map_err(|e| {
+                            Status::new(
+                                Code::Unavailable,
+                                format!("could not fetch VAA status: {}", e),
+                            )
+                        })
+                    })
+                    .await?
+            }
+            Some(Query::VaaHash(data)) => {
+                if data.len() != 32 {
                     return Err(Status::new(
-                        Code::Internal,
-                        format!("failed to fetch balance: {}", e),
+                        Code::InvalidArgument,
+                        "VAA hash must be 32 bytes",
                     ));
                 }
-            };
-
-            Ok(Response::new(GetBalanceResponse {
-                balance,
-            }))
-        })
-            .join()
-            .unwrap()
-    }
-}
-
-fn pack_sig_verification_txs<'a>(
-    rpc: &RpcClient,
-    bridge: &Pubkey,
-    vaa: &VAA,
-    sender_keypair: &'a Keypair,
-) -> Result<Vec<Transaction>, Status> {
-    // Load guardian set
-    let bridge_key = Bridge::derive_bridge_id(bridge).unwrap();
-    let guardian_key =
-        Bridge::derive_guardian_set_id(bridge, &bridge_key, vaa.guardian_set_index).unwrap();
-    let guardian_account = rpc
-        .get_account_with_commitment(
-            &guardian_key,
-            CommitmentConfig {
-                commitment: CommitmentLevel::Single,
-            },
-        )
-        .unwrap()
-        .value
-        .unwrap_or_default();
-    let data = guardian_account.data;
-    let guardian_set: &GuardianSet = Bridge::unpack_immutable(data.as_slice()).unwrap();
-
-    // Map signatures to guardian set
-    let mut signature_items: Vec<SignatureItem> = Vec::new();
-    for s in vaa.signatures.iter() {
-        let mut item = SignatureItem {
-            signature: [0; 64 + 1],
-            key: [0; 20],
-            index: s.index,
+                let mut hash = [0u8; 32];
+                hash.copy_from_slice(&data);
+                self.rpc
+                    .run(move |rpc| {
+                        status::fetch_by_hash(rpc, &pda, &hash, guardian_set_index).map_err(|e| {
+                            Status::new(
+                                Code::Unavailable,
+                                format!("could not fetch VAA status: {}", e),
+                            )
+                        })
+                    })
+                    .await?
+            }
+            None => {
+                return Err(Status::new(
+                    Code::InvalidArgument,
+                    "either the VAA or its hash is required",
+                ));
+            }
         };
 
-        item.signature[0..32].copy_from_slice(&s.r);
-        item.signature[32..64].copy_from_slice(&s.s);
-        item.signature[64] = s.v;
-        item.key = guardian_set.keys[s.index as usize];
-
-        signature_items.push(item);
+        Ok(Response::new(status))
     }
 
-    let vaa_hash = match vaa.body_hash() {
-        Ok(v) => v,
-        Err(e) => {
-            return Err(Status::new(
-                Code::InvalidArgument,
-                format!("could get vaa body hash: {}", e),
-            ));
-        }
-    };
-    let vaa_body = match vaa.signature_body() {
-        Ok(v) => v,
-        Err(e) => {
-            return Err(Status::new(
-                Code::InvalidArgument,
-                format!("could get vaa body: {}", e),
-            ));
-        }
-    };
+    async fn watch_lockups(
+        &self,
+        request: Request<WatchLockupsRequest>,
+    ) -> Result<Response<Self::WatchLockupsStream>, Status> {
+        let cursor = request.get_ref().slot;
+        let bridge = self.bridge;
+        let rpc = self.rpc.clone();
+
+        // Subscribe before the backfill so that no lockup is missed in between
+        let mut lockups = self.watcher.subscribe();
+        let (mut tx, rx) = mpsc::channel(LOCKUP_BUFFER);
+
+        tokio::spawn(async move {
+            let backfill = rpc
+                .run(move |rpc| {
+                    watcher::backfill(rpc, &bridge, cursor).map_err(|e| {
+                        Status::new(
+                            Code::Unavailable,
+                            format!("failed to backfill lockups: {}", e),
+                        )
+                    })
+                })
+                .await;
+            let (backfill_slot, pending) = match backfill {
+                Ok(v) => v,
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
+            };
 
-    let signature_acc =
-        Bridge::derive_signature_id(&bridge, &bridge_key, &vaa_hash, guardian_set.index).unwrap();
-
-    let mut verify_txs: Vec<Transaction> = Vec::new();
-    for (tx_index, chunk) in signature_items.chunks(6).enumerate() {
-        let mut secp_payload = Vec::new();
-        let mut signature_status = [-1i8; 20];
-
-        let data_offset = 1 + chunk.len() * 11;
-        let message_offset = data_offset + chunk.len() * 85;
-
-        // 1 number of signatures
-        secp_payload.write_u8(chunk.len() as u8);
-
-        // Secp signature info description (11 bytes * n)
-        for (i, s) in chunk.iter().enumerate() {
-            secp_payload.write_u16::<LittleEndian>((data_offset + 85 * i) as u16);
-            secp_payload.write_u8(0);
-            secp_payload.write_u16::<LittleEndian>((data_offset + 85 * i + 65) as u16);
-            secp_payload.write_u8(0);
-            secp_payload.write_u16::<LittleEndian>(message_offset as u16);
-            secp_payload.write_u16::<LittleEndian>(vaa_body.len() as u16);
-            secp_payload.write_u8(0);
-            signature_status[s.index as usize] = i as i8;
-        }
+            for lockup in pending {
+                if tx.send(Ok(lockup)).await.is_err() {
+                    return;
+                }
+            }
 
-        // Write signatures and addresses
-        for s in chunk.iter() {
-            secp_payload.write(&s.signature);
-            secp_payload.write(&s.key);
-        }
+            // Lockups up to the backfill slot are already covered by the backfill
+            let start = cursor.max(backfill_slot);
+            loop {
+                match lockups.recv().await {
+                    Ok(lockup) => {
+                        if lockup.slot <= start {
+                            continue;
+                        }
+                        if tx.send(Ok(lockup)).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(RecvError::Lagged(n)) => {
+                        let _ = tx
+                            .send(Err(Status::new(
+                                Code::ResourceExhausted,
+                                format!(
+                                    "stream fell behind by {} lockups, resume from the last slot",
+                                    n
+                                ),
+                            )))
+                            .await;
+                        return;
+                    }
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        });
 
-        // Write body
-        secp_payload.write(&vaa_body);
+        Ok(Response::new(rx))
+    }
+}
 
-        let secp_ix = Instruction {
-            program_id: solana_sdk::secp256k1_program::id(),
-            data: secp_payload,
-            accounts: vec![],
+/// Periodically checks that the RPC node makes progress and that a fee payer can pay for
+/// submissions, reporting the agent as not serving otherwise
+async fn monitor_health(rpc: AsyncRpc, keys: Arc<KeyPool>, mut reporter: HealthReporter) {
+    let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+    let mut last_slot = 0;
+    loop {
+        interval.tick().await;
+        let status = match keys.refresh(&rpc).await {
+            Ok(()) => {
+                rpc.run(|rpc| {
+                    rpc.get_slot().map_err(|e| {
+                        Status::new(Code::Unavailable, format!("failed to fetch slot: {}", e))
+                    })
+                })
+                .await
+            }
+            Err(e) => Err(e),
         };
 
-        let payload = VerifySigPayload {
-            signers: signature_status,
-            hash: vaa_hash,
-            initial_creation: tx_index == 0,
-        };
+        let healthy = match status {
+            Ok(slot) => {
+                metrics::LAST_OBSERVED_SLOT.set(slot as i64);
 
-        let verify_ix = match verify_signatures(
-            &bridge,
-            &signature_acc,
-            &sender_keypair.pubkey(),
-            vaa.guardian_set_index,
-            &payload,
-        ) {
-            Ok(v) => v,
+                let progressing = slot > last_slot;
+                last_slot = slot;
+                let funded = keys.payers().iter().any(|p| p.is_active());
+                if !funded {
+                    warn!("all fee payers are below the minimum balance");
+                }
+                if !progressing {
+                    warn!("RPC node is stuck at slot {}", slot);
+                }
+                funded && progressing
+            }
             Err(e) => {
-                return Err(Status::new(
-                    Code::InvalidArgument,
-                    format!("could not create verify instruction: {}", e),
-                ));
+                warn!("health check failed: {}", e);
+                false
             }
         };
 
-        verify_txs.push(Transaction::new_with_payer(
-            &[secp_ix, verify_ix],
-            Some(&sender_keypair.pubkey()),
-        ))
+        if healthy {
+            reporter.set_serving::<AgentServer<AgentImpl>>().await;
+        } else {
+            reporter.set_not_serving::<AgentServer<AgentImpl>>().await;
+        }
     }
-
-    Ok(verify_txs)
 }
 
-fn sign_and_send(
-    rpc: &RpcClient,
-    tx: &mut Transaction,
-    keys: Vec<&Keypair>,
-) -> Result<Signature, ClientError> {
-    let (recent_blockhash, _fee_calculator) = rpc.get_recent_blockhash()?;
-
-    tx.sign(&keys, recent_blockhash);
-
-    rpc.send_and_confirm_transaction_with_spinner_and_config(
-        &tx,
-        CommitmentConfig {
-            commitment: CommitmentLevel::Single,
-        },
-        RpcSendTransactionConfig {
-            skip_preflight: false,
-            preflight_commitment: Some(CommitmentLevel::SingleGossip),
-            encoding: None,
-        },
-    )
+/// Reads the fee payer keys again whenever the agent receives SIGHUP
+async fn reload_keys_on_hangup(mut hangup: Signal, rpc: AsyncRpc, keys: Arc<KeyPool>) {
+    while hangup.recv().await.is_some() {
+        if let Err(e) = keys.reload() {
+            error!("failed to reload fee payer keys: {}", e);
+            continue;
+        }
+        info!("reloaded {} fee payer keys", keys.payers().len());
+        if let Err(e) = keys.refresh(&rpc).await {
+            warn!("failed to fetch fee payer balances: {}", e);
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    solana_logger::setup_with_default("info");
+
     let matches = App::new("Wormhole Solana agent")
         .arg(Arg::with_name("bridge")
             .long("bridge")
@@ -333,8 +446,47 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .arg(Arg::with_name("keypair")
             .long("keypair")
             .value_name("FILE")
-            .help("Fee payer account key ")
+            .help("Fee payer account key, may be given several times to use a pool of fee payers")
             .required(true)
+            .multiple(true)
+            .number_of_values(1)
+            .takes_value(true))
+        .arg(Arg::with_name("key-selection")
+            .long("key-selection")
+            .value_name("STRATEGY")
+            .help("How the fee payer of a submission is chosen from the pool")
+            .possible_values(&["round-robin", "balance"])
+            .default_value("round-robin")
+            .takes_value(true))
+        .arg(Arg::with_name("rpc-concurrency")
+            .long("rpc-concurrency")
+            .value_name("N")
+            .help("Maximum number of RPC requests in flight across all requests to the agent")
+            .default_value("16")
+            .takes_value(true))
+        .arg(Arg::with_name("rpc-timeout")
+            .long("rpc-timeout")
+            .value_name("SECONDS")
+            .help("Timeout of a single RPC request, including the confirmation of transactions")
+            .default_value("60")
+            .takes_value(true))
+        .arg(Arg::with_name("queue")
+            .long("queue")
+            .value_name("DIR")
+            .help("Directory of the journal of VAAs waiting to be submitted")
+            .default_value("agent-queue")
+            .takes_value(true))
+        .arg(Arg::with_name("metrics")
+            .long("metrics")
+            .value_name("ADDRESS")
+            .help("Address to serve Prometheus metrics on, e.g. 127.0.0.1:9100")
+            .takes_value(true))
+        .arg(Arg::with_name("min-balance")
+            .long("min-balance")
+            .value_name("LAMPORTS")
+            .help("Fee payer balance below which a key is taken out of rotation. The agent reports \
+                   itself as not serving if no key is left.")
+            .default_value("100000000")
             .takes_value(true))
         .get_matches();
 
@@ -342,16 +494,50 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let ws_url = matches.value_of("ws").unwrap();
     let rpc_url = matches.value_of("rpc").unwrap();
     let socket_path = matches.value_of("socket").unwrap();
-    let keypair = read_keypair_file(matches.value_of("keypair").unwrap()).unwrap();
+    let key_paths: Vec<PathBuf> = matches.values_of("keypair").unwrap().map(PathBuf::from).collect();
+    let key_selection: Selection = matches.value_of("key-selection").unwrap().parse()?;
+    let rpc_concurrency: usize = matches.value_of("rpc-concurrency").unwrap().parse()?;
+    let rpc_timeout = Duration::from_secs(matches.value_of("rpc-timeout").unwrap().parse()?);
+    let min_balance: u64 = matches.value_of("min-balance").unwrap().parse()?;
+    let keys = Arc::new(KeyPool::load(key_paths, key_selection, min_balance)?);
+    let submissions = Arc::new(Queue::open(Path::new(matches.value_of("queue").unwrap()))?);
+    let metrics_addr: Option<SocketAddr> = match matches.value_of("metrics") {
+        Some(addr) => Some(addr.parse()?),
+        None => None,
+    };
+
+    for payer in keys.payers() {
+        println!("Agent using account: {}", payer.keypair.pubkey());
+    }
+
+    if let Some(addr) = metrics_addr {
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(addr).await {
+                error!("metrics listener failed: {}", e);
+            }
+        });
+        println!("Agent serving metrics on {}", addr);
+    }
 
-    println!("Agent using account: {}", keypair.pubkey());
+    let rpc = AsyncRpc::new(rpc_url.to_string(), rpc_concurrency, rpc_timeout);
+    let (mut reporter, health_service) = tonic_health::server::health_reporter();
+    // Not serving until the first health check passed
+    reporter.set_not_serving::<AgentServer<AgentImpl>>().await;
+    tokio::spawn(monitor_health(rpc.clone(), keys.clone(), reporter));
+    tokio::spawn(reload_keys_on_hangup(signal(SignalKind::hangup())?, rpc.clone(), keys.clone()));
 
     let agent = AgentImpl {
-        url: ws_url.to_string(),
-        rpc_url: rpc_url.to_string(),
+        watcher: LockupWatcher::start(
+            ws_url.to_string(),
+            rpc_url.to_string(),
+            Pubkey::from_str(bridge).unwrap(),
+        ),
+        rpc,
         bridge: Pubkey::from_str(bridge).unwrap(),
-        key: keypair,
+        keys,
+        queue: submissions.clone(),
     };
+    tokio::spawn(queue::retry_pending(submissions, agent.submitter()));
 
     // Setting a umask appears to be the only way of safely creating a UNIX socket using
     // UnixListener::bind without introducing a TOCTOU race condition.
@@ -367,6 +553,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Agent listening on {}", socket_path);
 
     Server::builder()
+        .add_service(health_service)
         .add_service(AgentServer::new(agent))
         .serve_with_incoming(listener.incoming().map_ok(socket::UnixStream))
         .await?;
@@ -0,0 +1,95 @@
+//! Prometheus metrics of the agent and the HTTP listener exporting them
+
+use std::{convert::Infallible, net::SocketAddr};
+
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server,
+};
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram, register_int_counter, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, Encoder, Histogram, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    TextEncoder,
+};
+use wormhole_solana_client::spl_bridge::vaa::{VAABody, VAA};
+
+lazy_static! {
+    pub static ref VAA_SUBMITTED: IntCounterVec = register_int_counter_vec!(
+        "wormhole_agent_vaa_submitted_total",
+        "VAAs executed on Solana by the agent, by body type",
+        &["body"]
+    )
+    .unwrap();
+    pub static ref VAA_FAILED: IntCounterVec = register_int_counter_vec!(
+        "wormhole_agent_vaa_failed_total",
+        "VAAs the agent failed to execute on Solana, by body type",
+        &["body"]
+    )
+    .unwrap();
+    pub static ref VERIFY_TX_DURATION: Histogram = register_histogram!(
+        "wormhole_agent_verify_tx_duration_seconds",
+        "Time from sending a signature verification transaction to its confirmation"
+    )
+    .unwrap();
+    pub static ref FEE_PAYER_BALANCE: IntGaugeVec = register_int_gauge_vec!(
+        "wormhole_agent_fee_payer_balance_lamports",
+        "Balance of the fee payers of the agent, by address",
+        &["payer"]
+    )
+    .unwrap();
+    pub static ref RPC_ERRORS: IntCounter = register_int_counter!(
+        "wormhole_agent_rpc_errors_total",
+        "Failed or timed out requests to the Solana RPC node"
+    )
+    .unwrap();
+    pub static ref QUEUE_DEPTH: IntGauge = register_int_gauge!(
+        "wormhole_agent_queue_depth",
+        "VAAs journaled by the agent that are waiting to be executed"
+    )
+    .unwrap();
+    pub static ref LAST_OBSERVED_SLOT: IntGauge = register_int_gauge!(
+        "wormhole_agent_last_observed_slot",
+        "Latest slot observed on the Solana RPC node"
+    )
+    .unwrap();
+}
+
+/// Returns the label of the body type of `vaa`
+pub fn body_type(vaa: &VAA) -> &'static str {
+    match vaa.payload {
+        Some(VAABody::UpdateGuardianSet(_)) => "update_guardian_set",
+        Some(VAABody::Transfer(_)) => "transfer",
+        Some(VAABody::UpgradeContract(_)) => "upgrade_contract",
+        Some(VAABody::TreasuryWithdrawal(_)) => "treasury_withdrawal",
+        Some(VAABody::FreezeAccount(_)) => "freeze_account",
+        Some(VAABody::HaltMinting(_)) => "halt_minting",
+        Some(VAABody::RevertTransfer(_)) => "revert_transfer",
+        None => "unknown",
+    }
+}
+
+/// Serves the metrics in the Prometheus text format on `addr`
+pub async fn serve(addr: SocketAddr) -> Result<(), hyper::Error> {
+    let make_service =
+        make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(export)) });
+
+    Server::bind(&addr).serve(make_service).await
+}
+
+async fn export(_request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    if let Err(e) = encoder.encode(&prometheus::gather(), &mut buffer) {
+        return Ok(Response::builder()
+            .status(500)
+            .body(Body::from(format!("failed to encode metrics: {}", e)))
+            .unwrap());
+    }
+
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, encoder.format_type())
+        .body(Body::from(buffer))
+        .unwrap())
+}
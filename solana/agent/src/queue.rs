@@ -0,0 +1,293 @@
+//! Journal of the VAAs accepted by `SubmitVAA`
+//!
+//! VAAs are journaled before the first submission attempt and retried with exponential backoff
+//! until they are executed or rejected by the bridge, surviving restarts of the agent and outages
+//! of the RPC node. Rejected VAAs are moved to a dead letter tree for inspection.
+
+use std::{
+    collections::HashSet,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures::future::join_all;
+use log::{error, warn};
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+use tonic::{Code, Response, Status};
+use wormhole_solana_client::spl_bridge::{state::GuardianSet, vaa::VAA};
+
+use crate::{
+    metrics,
+    service::SubmitVaaResponse,
+    submit::{is_permanent, Submitter},
+};
+
+/// Delay before the first retry in seconds, doubled with every further attempt
+const RETRY_BASE_DELAY: u64 = 5;
+
+/// Maximum delay between retries in seconds
+const RETRY_MAX_DELAY: u64 = 600;
+
+/// Interval in which the journal is checked for VAAs due for a retry
+const RETRY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Error)]
+pub enum QueueError {
+    #[error("store error: {0}")]
+    Store(#[from] sled::Error),
+    #[error("corrupt entry: {0}")]
+    Codec(#[from] serde_json::Error),
+}
+
+impl From<QueueError> for Status {
+    fn from(e: QueueError) -> Self {
+        Status::new(Code::Internal, format!("submission queue failed: {}", e))
+    }
+}
+
+/// A journaled VAA
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Entry {
+    /// the signed VAA
+    #[serde(with = "serde_bytes")]
+    pub vaa: Vec<u8>,
+    /// number of failed submission attempts
+    pub attempts: u32,
+    /// unix time of the last attempt
+    pub last_attempt: u64,
+    /// unix time of the next attempt
+    pub next_attempt: u64,
+    /// error of the last attempt
+    pub last_error: String,
+}
+
+pub struct Queue {
+    db: sled::Db,
+    /// VAAs waiting to be executed, by body hash
+    pending: sled::Tree,
+    /// VAAs rejected by the bridge, by body hash
+    dead: sled::Tree,
+    /// VAAs currently being submitted
+    in_flight: Mutex<HashSet<[u8; 32]>>,
+}
+
+impl Queue {
+    pub fn open(path: &Path) -> Result<Self, QueueError> {
+        let db = sled::open(path)?;
+        Ok(Queue {
+            pending: db.open_tree("pending")?,
+            dead: db.open_tree("dead")?,
+            db,
+            in_flight: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Journals `vaa` unless it is already pending. Returns once the entry is on disk.
+    pub fn push(&self, hash: &[u8; 32], vaa: &[u8]) -> Result<(), QueueError> {
+        // The first attempt is made by the caller, the retries only pick the VAA up if that
+        // attempt is cancelled
+        let entry = Entry {
+            vaa: vaa.to_vec(),
+            next_attempt: now() + RETRY_BASE_DELAY,
+            ..Entry::default()
+        };
+        // A failed swap means that the VAA is already pending
+        let _ = self
+            .pending
+            .compare_and_swap(hash, None as Option<&[u8]>, Some(serde_json::to_vec(&entry)?))?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// Returns the pending VAAs due for a retry at `time`
+    pub fn due(&self, time: u64) -> Result<Vec<([u8; 32], Entry)>, QueueError> {
+        let mut due = Vec::new();
+        for item in self.pending.iter() {
+            let (key, value) = item?;
+            let entry: Entry = serde_json::from_slice(&value)?;
+            if entry.next_attempt > time || key.len() != 32 {
+                continue;
+            }
+            let mut hash = [0u8; 32];
+            hash.copy_from_slice(&key);
+            due.push((hash, entry));
+        }
+        Ok(due)
+    }
+
+    /// Removes an executed VAA
+    pub fn complete(&self, hash: &[u8; 32]) -> Result<(), QueueError> {
+        self.pending.remove(hash)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// Schedules the next attempt of a VAA that failed with `error`
+    pub fn reschedule(&self, hash: &[u8; 32], error: &str) -> Result<(), QueueError> {
+        if let Some(mut entry) = self.get(hash)? {
+            entry.attempts += 1;
+            entry.last_attempt = now();
+            entry.next_attempt = entry.last_attempt + backoff(entry.attempts);
+            entry.last_error = error.to_string();
+            self.pending.insert(hash, serde_json::to_vec(&entry)?)?;
+            self.db.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Moves a VAA rejected with `error` to the dead letters
+    pub fn reject(&self, hash: &[u8; 32], error: &str) -> Result<(), QueueError> {
+        if let Some(mut entry) = self.get(hash)? {
+            entry.attempts += 1;
+            entry.last_attempt = now();
+            entry.next_attempt = 0;
+            entry.last_error = error.to_string();
+            self.dead.insert(hash, serde_json::to_vec(&entry)?)?;
+            self.pending.remove(hash)?;
+            self.db.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Number of VAAs waiting to be executed
+    pub fn depth(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns the VAAs rejected by the bridge
+    pub fn dead_letters(&self) -> Result<Vec<Entry>, QueueError> {
+        let mut entries = Vec::new();
+        for item in self.dead.iter() {
+            let (_, value) = item?;
+            entries.push(serde_json::from_slice(&value)?);
+        }
+        Ok(entries)
+    }
+
+    fn get(&self, hash: &[u8; 32]) -> Result<Option<Entry>, QueueError> {
+        match self.pending.get(hash)? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Marks a VAA as being submitted, returning `None` if it already is
+    fn begin(&self, hash: &[u8; 32]) -> Option<InFlight> {
+        if self.in_flight.lock().unwrap().insert(*hash) {
+            Some(InFlight { queue: self, hash: *hash })
+        } else {
+            None
+        }
+    }
+}
+
+/// Marker of a VAA being submitted, released when the submission ends or is cancelled
+struct InFlight<'a> {
+    queue: &'a Queue,
+    hash: [u8; 32],
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.queue.in_flight.lock().unwrap().remove(&self.hash);
+    }
+}
+
+/// Submits a journaled VAA and updates the journal with the outcome. Transient failures are
+/// reported as queued, the VAA is then retried by `retry_pending`.
+pub async fn attempt(
+    queue: &Queue,
+    submitter: &Submitter,
+    hash: [u8; 32],
+    vaa: Arc<VAA>,
+    guardian_set: Option<GuardianSet>,
+) -> Result<Response<SubmitVaaResponse>, Status> {
+    let _in_flight = match queue.begin(&hash) {
+        Some(v) => v,
+        None => return Ok(queued()),
+    };
+
+    let body = metrics::body_type(&vaa);
+    let result = submitter.submit(vaa, guardian_set).await;
+    match &result {
+        Ok(r) => {
+            if !r.get_ref().already_submitted {
+                metrics::VAA_SUBMITTED.with_label_values(&[body]).inc();
+            }
+            queue.complete(&hash)?;
+        }
+        Err(e) if is_permanent(e) => {
+            metrics::VAA_FAILED.with_label_values(&[body]).inc();
+            queue.reject(&hash, e.message())?;
+        }
+        Err(e) => {
+            queue.reschedule(&hash, e.message())?;
+            return Ok(queued());
+        }
+    }
+
+    result
+}
+
+/// Retries the pending VAAs once they are due
+pub async fn retry_pending(queue: Arc<Queue>, submitter: Submitter) {
+    let mut interval = tokio::time::interval(RETRY_POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        metrics::QUEUE_DEPTH.set(queue.depth() as i64);
+
+        let due = match queue.due(now()) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("failed to read submission queue: {}", e);
+                continue;
+            }
+        };
+        join_all(
+            due.into_iter()
+                .map(|(hash, entry)| retry(&queue, &submitter, hash, entry)),
+        )
+        .await;
+    }
+}
+
+async fn retry(queue: &Queue, submitter: &Submitter, hash: [u8; 32], entry: Entry) {
+    let vaa = match VAA::deserialize(&entry.vaa) {
+        Ok(v) => Arc::new(v),
+        Err(e) => {
+            let error = format!("could not parse VAA: {}", e);
+            warn!("VAA {} rejected: {}", hex::encode(hash), error);
+            if let Err(e) = queue.reject(&hash, &error) {
+                error!("failed to update submission queue: {}", e);
+            }
+            return;
+        }
+    };
+
+    if let Err(e) = attempt(queue, submitter, hash, vaa, None).await {
+        warn!("VAA {} failed: {}", hex::encode(hash), e.message());
+    }
+}
+
+fn queued() -> Response<SubmitVaaResponse> {
+    Response::new(SubmitVaaResponse {
+        signature: String::new(),
+        already_submitted: false,
+        queued: true,
+    })
+}
+
+/// Delay before the attempt following the given number of failed attempts, in seconds
+fn backoff(attempts: u32) -> u64 {
+    // The exponent is capped well before the delay could overflow
+    let exponent = attempts.saturating_sub(1).min(16);
+    (RETRY_BASE_DELAY << exponent).min(RETRY_MAX_DELAY)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
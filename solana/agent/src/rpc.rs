@@ -0,0 +1,95 @@
+//! Asynchronous access to the Solana RPC node
+//!
+//! `solana-client` only ships a blocking client, so its requests are run on the blocking thread
+//! pool of the runtime instead of blocking the handlers. A semaphore bounds the requests in flight
+//! across all handlers and each HTTP request of the client is subject to a timeout.
+
+use std::{sync::Arc, time::Duration};
+
+use solana_client::rpc_client::RpcClient;
+use tokio::sync::Semaphore;
+use tonic::{Code, Status};
+
+use crate::metrics;
+
+#[derive(Clone)]
+pub struct AsyncRpc {
+    url: String,
+    permits: Arc<Semaphore>,
+    timeout: Duration,
+}
+
+impl AsyncRpc {
+    pub fn new(url: String, max_concurrent: usize, timeout: Duration) -> Self {
+        AsyncRpc {
+            url,
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+            timeout,
+        }
+    }
+
+    /// Runs `f` with the blocking client once a permit is available.
+    ///
+    /// The permit is held by the blocking thread, so requests to an unresponsive node cannot
+    /// exhaust the blocking thread pool. The client gives up on each HTTP request after the
+    /// timeout, which ends `f` and frees its thread and permit even if the node never answers.
+    pub async fn run<T, F>(&self, f: F) -> Result<T, Status>
+    where
+        F: FnOnce(&RpcClient) -> Result<T, Status> + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = self.permits.clone().acquire_owned().await;
+        let url = self.url.clone();
+        let timeout = self.timeout;
+        let request = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            f(&RpcClient::new_with_timeout(url, timeout))
+        });
+
+        let result = match tokio::time::timeout(self.timeout, request).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => Err(Status::new(
+                Code::Internal,
+                format!("rpc request failed: {}", e),
+            )),
+            Err(_) => Err(Status::new(
+                Code::DeadlineExceeded,
+                "rpc request timed out",
+            )),
+        };
+        if result.is_err() {
+            metrics::RPC_ERRORS.inc();
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn timed_out_requests_free_their_permit() {
+        // The node accepts connections but never answers
+        let node = TcpListener::bind("127.0.0.1:0").unwrap();
+        let rpc = AsyncRpc::new(
+            format!("http://{}", node.local_addr().unwrap()),
+            1,
+            Duration::from_millis(200),
+        );
+
+        for _ in 0..2 {
+            let request = rpc.run(|rpc| {
+                rpc.get_slot()
+                    .map_err(|e| Status::new(Code::Unavailable, e.to_string()))
+            });
+            let result = tokio::time::timeout(Duration::from_secs(5), request)
+                .await
+                .expect("the permit of the timed out request was not freed");
+            assert!(result.is_err());
+        }
+    }
+}
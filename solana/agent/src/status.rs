@@ -0,0 +1,78 @@
+//! Status of VAAs on chain, served by `GetVAAStatus`
+
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use wormhole_solana_client::{
+    error::Error,
+    ops::{fetch_claim, fetch_signature_state_by_hash, fetch_transfer_out_proposal},
+    spl_bridge::{
+        instruction::CHAIN_ID_SOLANA,
+        state::SignatureState,
+        vaa::{VAABody, VAA},
+    },
+    Pda,
+};
+
+use crate::service::{ClaimStatus, GetVaaStatusResponse, SignatureStatus, TransferOutStatus};
+
+/// Fetches the status of the VAA with the body hash `hash`, signed by the guardian set
+/// `guardian_set_index`
+pub fn fetch_by_hash(
+    rpc: &RpcClient,
+    pda: &Pda,
+    hash: &[u8; 32],
+    guardian_set_index: u32,
+) -> Result<GetVaaStatusResponse, Error> {
+    let signatures = fetch_signature_state_by_hash(rpc, pda, hash, guardian_set_index)?;
+
+    Ok(GetVaaStatusResponse {
+        signatures: signatures.as_ref().map(signature_status),
+        claim: None,
+        transfer_out: None,
+    })
+}
+
+/// Fetches the status of `vaa`
+pub fn fetch(rpc: &RpcClient, pda: &Pda, vaa: &VAA) -> Result<GetVaaStatusResponse, Error> {
+    let mut status = fetch_by_hash(rpc, pda, &vaa.body_hash()?, vaa.guardian_set_index)?;
+
+    let claim = fetch_claim(rpc, pda, vaa)?;
+    status.claim = Some(ClaimStatus {
+        claimed: claim.is_some(),
+        vaa_time: claim.map_or(0, |c| c.vaa_time),
+    });
+
+    if let Some(VAABody::Transfer(t)) = &vaa.payload {
+        if t.source_chain == CHAIN_ID_SOLANA {
+            let address = pda.transfer_out_proposal(
+                &t.asset,
+                t.target_chain,
+                t.target_address,
+                &Pubkey::new(&t.source_address),
+                t.nonce,
+            )?;
+            let proposal = fetch_transfer_out_proposal(rpc, pda, t)?;
+            status.transfer_out = Some(TransferOutStatus {
+                proposal_address: address.to_bytes().to_vec(),
+                exists: proposal.is_some(),
+                vaa_time: proposal.map_or(0, |p| p.vaa_time),
+                poke_counter: proposal.map_or(0, |p| p.poke_counter as u32),
+                reverted: proposal.map_or(false, |p| p.reverted),
+            });
+        }
+    }
+
+    Ok(status)
+}
+
+fn signature_status(state: &SignatureState) -> SignatureStatus {
+    SignatureStatus {
+        guardian_set_index: state.guardian_set_index,
+        verified: state
+            .signatures
+            .iter()
+            .map(|s| s.iter().any(|b| *b != 0))
+            .collect(),
+        creation_time: state.creation_time,
+    }
+}
@@ -0,0 +1,267 @@
+//! Execution of VAAs on chain
+
+use std::sync::Arc;
+
+use futures::future::try_join_all;
+use prost::Message;
+use prost_types::Any;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_client::RpcClient,
+    rpc_config::RpcSendTransactionConfig,
+};
+use solana_sdk::{
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use tonic::{Code, Response, Status};
+use wormhole_solana_client::{
+    ops::{
+        fetch_claimed, fetch_signature_state, redeem, simulate, verify_vaa, verify_vaa_remaining,
+    },
+    spl_bridge::{state::GuardianSet, vaa::VAA},
+    Error, Pda,
+};
+
+use crate::{
+    keys::KeyPool,
+    metrics,
+    rpc::AsyncRpc,
+    service::{ProgramError, SubmitVaaResponse},
+};
+
+/// Submits VAAs with the fee payer of the agent
+#[derive(Clone)]
+pub struct Submitter {
+    pub bridge: Pubkey,
+    pub rpc: AsyncRpc,
+    pub keys: Arc<KeyPool>,
+}
+
+impl Submitter {
+    /// Verifies the signatures of `vaa` and executes it.
+    ///
+    /// Submitting is idempotent, VAAs that have already been executed are not resubmitted and
+    /// signatures that have already been verified are skipped. Errors with the codes
+    /// `InvalidArgument` and `FailedPrecondition` are permanent, the VAA can never be executed.
+    ///
+    /// `guardian_set` may be passed if the guardian set of the VAA has already been fetched.
+    pub async fn submit(
+        &self,
+        vaa: Arc<VAA>,
+        guardian_set: Option<GuardianSet>,
+    ) -> Result<Response<SubmitVaaResponse>, Status> {
+        let pda = Pda::new(&self.bridge).map_err(|e| {
+            Status::new(Code::Internal, format!("could not derive bridge: {}", e))
+        })?;
+        // All transactions of a submission are paid by the same fee payer
+        let key = self.keys.select()?;
+        let payer = key.pubkey();
+
+        if self.fetch_claimed(pda, vaa.clone()).await? {
+            return Ok(already_submitted());
+        }
+
+        let v = vaa.clone();
+        let verify_txs = self
+            .rpc
+            .run(move |rpc| {
+                match guardian_set {
+                    Some(guardian_set) => fetch_signature_state(rpc, &pda, &v).and_then(|state| {
+                        verify_vaa_remaining(&pda, &payer, &v, &guardian_set, state.as_ref())
+                    }),
+                    None => verify_vaa(rpc, &pda, &payer, &v),
+                }
+                .map_err(|e| client_status("could not create verify transactions", e))
+            })
+            .await?;
+        let v = vaa.clone();
+        let post_tx = self
+            .rpc
+            .run(move |rpc| {
+                redeem(rpc, &pda, &payer, &v)
+                    .map_err(|e| client_status("could not create post_vaa instruction", e))
+            })
+            .await?;
+
+        // The first transaction creates the signature state, the others can then be sent
+        // concurrently
+        let mut verify_txs = verify_txs.into_iter();
+        if let Some(tx) = verify_txs.next() {
+            if let Err(e) = self.send_verify(&key, tx).await {
+                return self.submission_failed(pda, vaa, e).await;
+            }
+        }
+        if let Err(e) = try_join_all(verify_txs.map(|tx| self.send_verify(&key, tx))).await {
+            return self.submission_failed(pda, vaa, e).await;
+        }
+
+        match self.send(&key, post_tx).await {
+            Ok(s) => Ok(Response::new(SubmitVaaResponse {
+                signature: s.to_string(),
+                already_submitted: false,
+                queued: false,
+            })),
+            Err(e) => self.submission_failed(pda, vaa, e).await,
+        }
+    }
+
+    /// Returns whether `vaa` has already been executed on chain
+    async fn fetch_claimed(&self, pda: Pda, vaa: Arc<VAA>) -> Result<bool, Status> {
+        self.rpc
+            .run(move |rpc| {
+                fetch_claimed(rpc, &pda, &vaa)
+                    .map_err(|e| client_status("could not fetch claim", e))
+            })
+            .await
+    }
+
+    /// Signs `tx` with the fee payer `key`, simulates and sends it
+    async fn send(&self, key: &Arc<Keypair>, mut tx: Transaction) -> Result<Signature, Status> {
+        let key = key.clone();
+        let program_id = self.bridge;
+        self.rpc
+            .run(move |rpc| {
+                let (recent_blockhash, _fee_calculator) = rpc
+                    .get_recent_blockhash()
+                    .map_err(|e| client_status("tx sending failed", e.into()))?;
+                tx.sign(&[key.as_ref()], recent_blockhash);
+
+                simulate(rpc, &program_id, &tx).map_err(transaction_status)?;
+                send_signed(rpc, &tx).map_err(|e| match e.kind() {
+                    // Without preflight, failed transactions are only reported on confirmation
+                    ClientErrorKind::TransactionError(error) => transaction_status(
+                        Error::from_transaction_error(&program_id, &tx, error.clone(), vec![]),
+                    ),
+                    _ => client_status("tx sending failed", e.into()),
+                })
+            })
+            .await
+    }
+
+    /// Sends a signature verification transaction, recording its latency
+    async fn send_verify(&self, key: &Arc<Keypair>, tx: Transaction) -> Result<Signature, Status> {
+        let timer = metrics::VERIFY_TX_DURATION.start_timer();
+        let result = self.send(key, tx).await;
+        if result.is_ok() {
+            timer.observe_duration();
+        } else {
+            timer.stop_and_discard();
+        }
+
+        result
+    }
+
+    /// Handles a failed submission transaction. If another submission of the VAA won the race
+    /// the failure is reported as success.
+    async fn submission_failed(
+        &self,
+        pda: Pda,
+        vaa: Arc<VAA>,
+        e: Status,
+    ) -> Result<Response<SubmitVaaResponse>, Status> {
+        match self.fetch_claimed(pda, vaa).await {
+            Ok(true) => Ok(already_submitted()),
+            _ => Err(e),
+        }
+    }
+}
+
+/// Returns whether the error `status` of a submission is permanent
+pub fn is_permanent(status: &Status) -> bool {
+    matches!(status.code(), Code::InvalidArgument | Code::FailedPrecondition)
+}
+
+/// Maps an error of the client library to a status, separating failures of the RPC node from
+/// invalid VAAs
+fn client_status(context: &str, e: Error) -> Status {
+    let code = match e {
+        Error::Rpc(_) => Code::Unavailable,
+        // The account might still be created, e.g. by a pending guardian set update
+        Error::AccountNotFound(_) => Code::NotFound,
+        _ => Code::InvalidArgument,
+    };
+    Status::new(code, format!("{}: {}", context, e))
+}
+
+/// Maps the error of a failed transaction to a status. Transactions rejected by a program fail
+/// permanently and carry a `ProgramError` detail if the bridge rejected them.
+fn transaction_status(e: Error) -> Status {
+    let message = format!("tx failed: {}", e);
+    match e {
+        Error::Rejected {
+            instruction,
+            error,
+            logs,
+        } => {
+            let detail = ProgramError {
+                instruction: instruction as u32,
+                code: error.clone() as u32,
+                name: format!("{:?}", error),
+                logs,
+            };
+            let status = RpcStatus {
+                code: Code::FailedPrecondition as i32,
+                message: message.clone(),
+                details: vec![Any {
+                    type_url: PROGRAM_ERROR_TYPE_URL.to_string(),
+                    value: encode(&detail),
+                }],
+            };
+            Status::with_details(Code::FailedPrecondition, message, encode(&status).into())
+        }
+        Error::TransactionFailed {
+            error: TransactionError::InstructionError(..),
+            ..
+        } => Status::new(Code::FailedPrecondition, message),
+        // e.g. an expired blockhash, the transaction can be retried
+        Error::TransactionFailed { .. } => Status::new(Code::Aborted, message),
+        e => client_status("tx failed", e),
+    }
+}
+
+/// Type URL of the `ProgramError` details
+const PROGRAM_ERROR_TYPE_URL: &str = "type.googleapis.com/agent.v1.ProgramError";
+
+/// `google.rpc.Status`, the message carrying the details of a gRPC status
+#[derive(Clone, PartialEq, prost::Message)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<Any>,
+}
+
+fn encode<M: Message>(message: &M) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(message.encoded_len());
+    // Encoding only fails if the buffer is too small
+    message.encode(&mut buf).unwrap();
+    buf
+}
+
+pub fn already_submitted() -> Response<SubmitVaaResponse> {
+    Response::new(SubmitVaaResponse {
+        signature: String::new(),
+        already_submitted: true,
+        queued: false,
+    })
+}
+
+fn send_signed(rpc: &RpcClient, tx: &Transaction) -> Result<Signature, ClientError> {
+    rpc.send_and_confirm_transaction_with_spinner_and_config(
+        tx,
+        CommitmentConfig {
+            commitment: CommitmentLevel::Single,
+        },
+        RpcSendTransactionConfig {
+            // The transaction has just been simulated
+            skip_preflight: true,
+            preflight_commitment: None,
+            encoding: None,
+        },
+    )
+}
@@ -0,0 +1,244 @@
+//! Watches the bridge program for lockups and fans them out to the `WatchLockups` streams
+
+use std::{error::Error, mem::size_of, str::FromStr, thread, time::Duration};
+
+use log::warn;
+use serde_json::{json, Value};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use tokio::sync::broadcast;
+use tungstenite::Message;
+use url::Url;
+use wormhole_solana_client::spl_bridge::state::{Bridge, TransferOutProposal};
+
+use crate::service::Lockup;
+
+/// Number of lockups buffered for slow streams before they are dropped
+pub const LOCKUP_BUFFER: usize = 1024;
+
+/// Delay between reconnects of the program subscription
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+pub struct LockupWatcher {
+    lockups: broadcast::Sender<Lockup>,
+}
+
+impl LockupWatcher {
+    /// Subscribes to the account changes of the bridge `program_id` in a background thread.
+    /// The subscription is renewed when it fails, backfilling the lockups it might have missed.
+    pub fn start(ws_url: String, rpc_url: String, program_id: Pubkey) -> Self {
+        let (lockups, _) = broadcast::channel(LOCKUP_BUFFER);
+        let sender = lockups.clone();
+
+        thread::spawn(move || {
+            // Slot of the last lockup observed, from which a renewed subscription is backfilled
+            let mut cursor = 0;
+            loop {
+                if let Err(e) = watch(&ws_url, &rpc_url, &program_id, &sender, &mut cursor) {
+                    warn!("lockup subscription failed: {}", e);
+                }
+                thread::sleep(RECONNECT_DELAY);
+            }
+        });
+
+        LockupWatcher { lockups }
+    }
+
+    /// Returns a receiver of all lockups observed from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<Lockup> {
+        self.lockups.subscribe()
+    }
+}
+
+fn watch(
+    ws_url: &str,
+    rpc_url: &str,
+    program_id: &Pubkey,
+    sender: &broadcast::Sender<Lockup>,
+    cursor: &mut u64,
+) -> Result<(), Box<dyn Error>> {
+    let (mut socket, _) = tungstenite::connect(Url::parse(ws_url)?)?;
+    socket.write_message(Message::Text(
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "programSubscribe",
+            "params": [
+                program_id.to_string(),
+                {
+                    "encoding": "base64",
+                    "commitment": "max",
+                    "filters": [{ "dataSize": size_of::<TransferOutProposal>() }],
+                },
+            ],
+        })
+        .to_string(),
+    ))?;
+
+    // Lockups made while the subscription was down are only found by scanning the program
+    let rpc = RpcClient::new(rpc_url.to_string());
+    let (slot, pending) = backfill(&rpc, program_id, *cursor)?;
+    *cursor = slot;
+    for lockup in pending {
+        // Sending only fails if no stream is open
+        let _ = sender.send(lockup);
+    }
+
+    loop {
+        let text = match socket.read_message()? {
+            Message::Text(text) => text,
+            Message::Ping(p) => {
+                socket.write_message(Message::Pong(p))?;
+                continue;
+            }
+            Message::Close(_) => return Err("subscription closed by the node".into()),
+            _ => continue,
+        };
+
+        let notification: Value = serde_json::from_str(&text)?;
+        if notification["method"] != "programNotification" {
+            continue;
+        }
+        let result = &notification["params"]["result"];
+        let slot = result["context"]["slot"]
+            .as_u64()
+            .ok_or("notification without slot")?;
+        let address = result["value"]["pubkey"]
+            .as_str()
+            .ok_or("notification without pubkey")?;
+        let data = result["value"]["account"]["data"][0]
+            .as_str()
+            .ok_or("notification without data")?;
+
+        let address = Pubkey::from_str(address)?;
+        if let Some(lockup) = decode_lockup(slot, &address, &base64::decode(data)?) {
+            *cursor = slot;
+            let _ = sender.send(lockup);
+        }
+    }
+}
+
+/// Returns the current slot and the lockups pending at that slot that were made since the
+/// `cursor` slot, or all pending lockups if the cursor is 0
+pub fn backfill(
+    rpc: &RpcClient,
+    program_id: &Pubkey,
+    cursor: u64,
+) -> Result<(u64, Vec<Lockup>), Box<dyn Error>> {
+    // The slot is fetched first so that the accounts are at least as recent as it
+    let slot = rpc.get_slot()?;
+
+    // Lockups only record their time, so the cursor is resolved to the time of its block. Lockups
+    // made in the same second before the cursor are sent again, which clients deduplicate.
+    let since = match cursor {
+        0 => 0,
+        cursor => match rpc.get_block_time(cursor) {
+            Ok(time) => time as u32,
+            Err(e) => {
+                warn!(
+                    "no block time for cursor {}, sending all lockups: {}",
+                    cursor, e
+                );
+                0
+            }
+        },
+    };
+
+    Ok((
+        slot,
+        pending_lockups(slot, &rpc.get_program_accounts(program_id)?, since),
+    ))
+}
+
+/// Decodes the lockups among the program `accounts` that are pending at `slot` and were made at
+/// or after the unix time `since`
+pub fn pending_lockups(slot: u64, accounts: &[(Pubkey, Account)], since: u32) -> Vec<Lockup> {
+    accounts
+        .iter()
+        .filter_map(|(address, account)| decode_lockup(slot, address, &account.data))
+        .filter(|lockup| lockup.lockup_time >= since)
+        .collect()
+}
+
+/// Decodes the TransferOutProposal `data` at `address` if it still waits for a VAA
+pub fn decode_lockup(slot: u64, address: &Pubkey, data: &[u8]) -> Option<Lockup> {
+    if data.len() != size_of::<TransferOutProposal>() {
+        return None;
+    }
+    let proposal: &TransferOutProposal = Bridge::unpack_immutable(data).ok()?;
+    if proposal.vaa_time != 0 || proposal.reverted {
+        return None;
+    }
+
+    let mut amount = [0u8; 32];
+    proposal.amount.to_big_endian(&mut amount);
+
+    Some(Lockup {
+        slot,
+        lockup_address: address.to_bytes().to_vec(),
+        nonce: proposal.sequence,
+        source_address: proposal.source_address.to_vec(),
+        target_address: proposal.foreign_address.to_vec(),
+        target_chain: proposal.to_chain_id as u32,
+        token_chain: proposal.asset.chain as u32,
+        token_address: proposal.asset.address.to_vec(),
+        token_decimals: proposal.asset.decimals as u32,
+        amount: amount.to_vec(),
+        lockup_time: proposal.lockup_time,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proposal_account(lockup_time: u32, vaa_time: u32) -> (Pubkey, Account) {
+        let mut data = vec![0u8; size_of::<TransferOutProposal>()];
+        let proposal: &mut TransferOutProposal = Bridge::unpack_unchecked(&mut data).unwrap();
+        proposal.lockup_time = lockup_time;
+        proposal.vaa_time = vaa_time;
+        proposal.is_initialized = true;
+
+        (
+            Pubkey::new_unique(),
+            Account {
+                data,
+                ..Account::default()
+            },
+        )
+    }
+
+    #[test]
+    fn backfill_resumes_from_cursor() {
+        let accounts = vec![
+            proposal_account(100, 0),
+            proposal_account(200, 0),
+            proposal_account(300, 0),
+            proposal_account(300, 310),
+        ];
+
+        // Without a cursor all pending lockups are sent
+        let lockups = pending_lockups(50, &accounts, 0);
+        assert_eq!(
+            lockups
+                .iter()
+                .map(|l| (l.slot, l.lockup_time))
+                .collect::<Vec<_>>(),
+            vec![(50, 100), (50, 200), (50, 300)]
+        );
+
+        // A resumed stream skips the lockups made before the time of the cursor
+        let lockups = pending_lockups(50, &accounts, 200);
+        assert_eq!(
+            lockups
+                .iter()
+                .map(|l| l.lockup_address.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                accounts[1].0.to_bytes().to_vec(),
+                accounts[2].0.to_bytes().to_vec()
+            ]
+        );
+        assert!(pending_lockups(50, &accounts, 301).is_empty());
+    }
+}
@@ -115,6 +115,39 @@ pub enum Error {
     /// Invalid Chain
     #[error("InvalidChain")]
     InvalidChain,
+    /// The account cannot be closed yet
+    #[error("AccountNotExpired")]
+    AccountNotExpired,
+    /// The program has not been upgraded or was already migrated
+    #[error("NoPendingMigration")]
+    NoPendingMigration,
+    /// No reimbursement is owed or the bridge can't pay it out
+    #[error("NothingToClaim")]
+    NothingToClaim,
+    /// Minting of the wrapped asset was halted by the guardians
+    #[error("MintingHalted")]
+    MintingHalted,
+    /// The transfer cannot be reverted yet
+    #[error("TransferNotExpired")]
+    TransferNotExpired,
+    /// The transfer was reverted
+    #[error("TransferReverted")]
+    TransferReverted,
+    /// The destination is neither the target of the transfer nor its associated token account
+    #[error("InvalidDestination")]
+    InvalidDestination,
+    /// The target address is not a valid address on the target chain
+    #[error("InvalidTargetAddress")]
+    InvalidTargetAddress,
+    /// The revert was not attested by a check of the target chain after the revert timeout
+    #[error("RevertNotAttested")]
+    RevertNotAttested,
+    /// The migration was not signed with the key of the program buffer approved by the upgrade
+    #[error("MigrationNotApproved")]
+    MigrationNotApproved,
+    /// An amount tracked by the bridge overflowed
+    #[error("AmountOverflow")]
+    AmountOverflow,
 }
 
 impl From<Error> for ProgramError {
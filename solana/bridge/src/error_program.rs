@@ -43,6 +43,17 @@ impl PrintProgramError for Error {
             Error::InvalidOwner => msg!("Error: InvalidOwner"),
             Error::InvalidSysvar => msg!("Error: InvalidSysvar"),
             Error::InvalidChain => msg!("Error: InvalidChain"),
+            Error::AccountNotExpired => msg!("Error: AccountNotExpired"),
+            Error::NoPendingMigration => msg!("Error: NoPendingMigration"),
+            Error::NothingToClaim => msg!("Error: NothingToClaim"),
+            Error::MintingHalted => msg!("Error: MintingHalted"),
+            Error::TransferNotExpired => msg!("Error: TransferNotExpired"),
+            Error::TransferReverted => msg!("Error: TransferReverted"),
+            Error::InvalidDestination => msg!("Error: InvalidDestination"),
+            Error::InvalidTargetAddress => msg!("Error: InvalidTargetAddress"),
+            Error::RevertNotAttested => msg!("Error: RevertNotAttested"),
+            Error::MigrationNotApproved => msg!("Error: MigrationNotApproved"),
+            Error::AmountOverflow => msg!("Error: AmountOverflow"),
         }
     }
 }
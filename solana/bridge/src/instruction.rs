@@ -9,21 +9,45 @@ use solana_program::{
     program_error::ProgramError,
     pubkey::Pubkey,
 };
+use solana_sdk::packet::PACKET_DATA_SIZE;
 
 use crate::{
+    error::Error,
     instruction::BridgeInstruction::{
-        CreateWrapped, Initialize, PokeProposal, PostVAA, TransferOut, VerifySignatures,
+        ClaimReimbursement, CloseGuardianSet, CreateWrapped, EvictSignatures, Initialize, Migrate,
+        PokeProposal, PostVAA, TransferOut, TransferOutBatch, TransferOutCPI, VerifySignatures,
     },
     state::{AssetMeta, Bridge, BridgeConfig},
-    vaa::{VAABody, VAA},
+    vaa::{BodyRevertTransfer, VAABody, VAA},
 };
 
 /// chain id of this chain
 pub const CHAIN_ID_SOLANA: u8 = 1;
+/// chain id of Ethereum
+pub const CHAIN_ID_ETHEREUM: u8 = 2;
+/// chain id of Terra
+pub const CHAIN_ID_TERRA: u8 = 3;
 /// maximum number of guardians
 pub const MAX_LEN_GUARDIAN_KEYS: usize = 20;
-/// maximum size of a posted VAA
-pub const MAX_VAA_SIZE: usize = 1000;
+/// number of accounts passed to every `PostVAA` instruction
+const POST_VAA_ACCOUNTS: usize = 10;
+/// number of accounts of a `PostVAA` instruction posting the VAA of a transfer out of Solana
+const POST_VAA_TRANSFER_ACCOUNTS: usize = POST_VAA_ACCOUNTS + 2;
+/// size of a transaction with a single signature and a single `PostVAA` instruction posting the
+/// VAA of a transfer out of Solana, without the VAA. The bridge program is also the first account
+/// of the instruction, so it does not add an account key.
+const POST_VAA_TRANSFER_TX_SIZE: usize = (1 + 64) // signatures
+    + 3 // message header
+    + (1 + POST_VAA_TRANSFER_ACCOUNTS * 32) // account keys
+    + 32 // recent blockhash
+    + 1 // number of instructions
+    + (1 + 1 + POST_VAA_TRANSFER_ACCOUNTS) // program id and account indices
+    + (2 + 1); // data length and instruction tag
+/// maximum size of a posted VAA. VAAs are passed as instruction data, so they are bounded by the
+/// space left in a transaction packet.
+pub const MAX_VAA_SIZE: usize = PACKET_DATA_SIZE - POST_VAA_TRANSFER_TX_SIZE;
+/// maximum number of assets transferred by a single `TransferOutBatch`
+pub const MAX_BATCHED_TRANSFERS: usize = 4;
 /// size of a foreign address in bytes
 const FOREIGN_ADDRESS_SIZE: usize = 32;
 
@@ -56,8 +80,8 @@ pub struct TransferOutPayload {
     pub asset: AssetMeta,
     /// address on the foreign chain to transfer to
     pub target: ForeignAddress,
-    /// unique nonce of the transfer
-    pub nonce: u32,
+    /// bump seed of the custody account (native tokens), filled in by the instruction builders
+    pub custody_bump: u8,
 }
 
 #[repr(C)]
@@ -71,8 +95,82 @@ pub struct TransferOutPayloadRaw {
     pub asset: AssetMeta,
     /// address on the foreign chain to transfer to
     pub target: ForeignAddress,
-    /// unique nonce of the transfer
-    pub nonce: u32,
+    /// bump seed of the custody account (native tokens)
+    pub custody_bump: u8,
+}
+
+/// single asset of a batched transfer out
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BatchedTransfer {
+    /// amount to transfer
+    pub amount: U256,
+    /// Information about the asset to be transferred
+    pub asset: AssetMeta,
+    /// bump seed of the custody account (native tokens), filled in by the instruction builder
+    pub custody_bump: u8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransferOutBatchPayload {
+    /// chain id to transfer to
+    pub chain_id: u8,
+    /// address on the foreign chain to transfer to
+    pub target: ForeignAddress,
+    /// assets to transfer, at most `MAX_BATCHED_TRANSFERS`
+    pub transfers: Vec<BatchedTransfer>,
+}
+
+impl TransferOutBatchPayload {
+    /// Returns the `TransferOut` payload of the `i`th asset of the batch
+    pub fn transfer(&self, i: usize) -> TransferOutPayload {
+        TransferOutPayload {
+            amount: self.transfers[i].amount,
+            chain_id: self.chain_id,
+            asset: self.transfers[i].asset,
+            target: self.target,
+            custody_bump: self.transfers[i].custody_bump,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BatchedTransferRaw {
+    /// amount to transfer
+    pub amount: [u8; 32],
+    /// Information about the asset to be transferred
+    pub asset: AssetMeta,
+    /// bump seed of the custody account (native tokens)
+    pub custody_bump: u8,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct TransferOutBatchPayloadRaw {
+    /// chain id to transfer to
+    pub chain_id: u8,
+    /// address on the foreign chain to transfer to
+    pub target: ForeignAddress,
+    /// number of assets in the batch
+    pub len_transfers: u8,
+    /// assets to transfer
+    pub transfers: [BatchedTransferRaw; MAX_BATCHED_TRANSFERS],
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MigratePayload {
+    /// completes the migration after migrating the accounts, `Migrate` can't run anymore
+    /// afterwards
+    pub finish: bool,
+    /// bump seed of the bridge
+    pub bridge_bump: u8,
+    /// bump seed of the claim of the upgrade VAA
+    pub claim_bump: u8,
+    /// bump seeds of the migrated accounts of the previous layout in the order of the accounts,
+    /// the bump seed of a wrapped asset meta is followed by the bump seed of its mint
+    pub bumps: Vec<u8>,
+    /// serialized upgrade VAA, the signatures may be omitted
+    pub vaa: VAAData,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -83,6 +181,32 @@ pub struct VerifySigPayload {
     pub signers: [i8; MAX_LEN_GUARDIAN_KEYS],
     /// indicates whether this verification should only succeed if the sig account does not exist
     pub initial_creation: bool,
+    /// index of the secp256k1 instruction in the transaction whose signatures are stored
+    pub secp_instruction: u8,
+}
+
+/// Checks that `address` is a valid address on the foreign chain `chain_id`.
+///
+/// Ethereum and Terra addresses are 20 bytes long and left-padded with zeros to 32 bytes. Addresses
+/// on other chains are taken as they are, but can never be zero.
+pub fn check_foreign_address(chain_id: u8, address: &ForeignAddress) -> Result<(), Error> {
+    if chain_id == CHAIN_ID_SOLANA {
+        return Err(Error::SameChainTransfer);
+    }
+    if address.iter().all(|b| *b == 0) {
+        return Err(Error::InvalidTargetAddress);
+    }
+
+    match chain_id {
+        CHAIN_ID_ETHEREUM | CHAIN_ID_TERRA => {
+            if address[..FOREIGN_ADDRESS_SIZE - 20].iter().any(|b| *b != 0) {
+                return Err(Error::InvalidTargetAddress);
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
 }
 
 /// Instructions supported by the SwapInfo program.
@@ -99,27 +223,22 @@ pub enum BridgeInstruction {
     Initialize(InitializePayload),
 
     /// Burns or locks a (wrapped) asset `token` from `sender` on the Solana chain.
+    /// The transfer is assigned the next sequence number of `sender`.
     ///
-    ///   Wrapped asset transfer out
-    ///   0. `[writable]`  The from token account
-    ///   1. `[]` The System program.
-    ///   2. `[]` The spl token program.
-    ///   3. `[]` The clock SysVar
-    ///   4. `[derived]` The bridge config
-    ///   5. `[writable, derived, empty]` The new transfer out tracking account
-    ///   6. `[writable, derived]` The mint of the wrapped asset
-    ///   7. ..7+M '[signer]' M signer accounts (from token authority)
-    ///
-    ///   Native token transfer out
-    ///   0. `[writable]`  The from token account
+    ///   0. `[]` The bridge program
     ///   1. `[]` The System program.
     ///   2. `[]` The spl token program.
-    ///   3. `[]` The clock SysVar
-    ///   4. `[derived]` The bridge config
-    ///   5. `[writable, derived, empty]` The new transfer out tracking account
-    ///   6. `[writable, derived]` The mint of the wrapped asset
-    ///   7. `[writable, derived]` The custody token account of the bridge
-    ///   8. ..8+M '[signer]' M signer accounts (from token authority)
+    ///   3. `[]` The rent SysVar
+    ///   4. `[]` The clock SysVar
+    ///   5. `[]` The instructions SysVar
+    ///   6. `[writable]`  The from token account
+    ///   7. `[derived]` The bridge config
+    ///   8. `[writable, derived, empty]` The new transfer out tracking account
+    ///   9. `[writable, derived]` The mint of the (wrapped) asset
+    ///   10. `[writable, signer]` The fee payer for new account creation
+    ///   11. `[writable, derived]` The transfer sequence of the from token account
+    ///   12. `[writable, derived]` The custody token account of the bridge (native tokens), or
+    ///       `[derived]` the wrapped asset meta of the mint (wrapped tokens)
     TransferOut(TransferOutPayload),
 
     /// Submits a VAA signed by `guardian` on a valid `proposal`.
@@ -137,11 +256,95 @@ pub enum BridgeInstruction {
     /// Pokes a proposal with no valid VAAs attached so guardians reprocess it.
     PokeProposal(),
 
-    /// Verifies signature instructions
+    /// Stores the signatures checked by the secp256k1 instruction `secp_instruction` of the
+    /// transaction. The signatures, addresses and message may be read from any instruction.
+    /// See docs for accounts
     VerifySignatures(VerifySigPayload),
 
     /// Creates a new wrapped asset
     CreateWrapped(AssetMeta),
+
+    /// Burns or locks a (wrapped) asset `token` from `sender` on behalf of another program.
+    /// The token authority (e.g. a PDA of the invoking program) signs the transfer instead of
+    /// approving the bridge and the fee is taken from the payer.
+    ///
+    ///   0. `[]` The bridge program
+    ///   1. `[]` The System program.
+    ///   2. `[]` The spl token program.
+    ///   3. `[]` The rent SysVar
+    ///   4. `[]` The clock SysVar
+    ///   5. `[signer]` The authority of the from token account
+    ///   6. `[writable]`  The from token account
+    ///   7. `[writable, derived]` The bridge config
+    ///   8. `[writable, derived, empty]` The new transfer out tracking account
+    ///   9. `[writable, derived]` The mint of the (wrapped) asset
+    ///   10. `[writable, signer]` The fee payer for new account creation
+    ///   11. `[writable, derived]` The transfer sequence of the from token account
+    ///   12. `[writable, derived]` The custody token account of the bridge (native tokens), or
+    ///       `[derived]` the wrapped asset meta of the mint (wrapped tokens)
+    TransferOutCPI(TransferOutPayload),
+
+    /// Closes a guardian set after it has expired and `GUARDIAN_SET_CLOSE_DELAY` has passed.
+    /// The rent is returned to the bridge.
+    ///
+    ///   0. `[]` The clock SysVar
+    ///   1. `[writable, derived]` The bridge config
+    ///   2. `[writable, derived]` The guardian set
+    CloseGuardianSet(),
+
+    /// Evicts a signature state after `SIGNATURE_EVICTION_TIME` has passed.
+    /// The rent is returned to the bridge.
+    ///
+    ///   0. `[]` The clock SysVar
+    ///   1. `[writable, derived]` The bridge config
+    ///   2. `[writable, derived]` The signature state
+    EvictSignatures(),
+
+    /// Migrates the program accounts after a contract upgrade until the migration is finished.
+    /// Must be signed with the key of the program buffer approved by the upgrade VAA.
+    ///
+    /// Accounts whose layout changed are recreated in two steps, each account is followed by its
+    /// migration stage. The first `Migrate` stages the converted data and closes the account, the
+    /// next one recreates the account from its stage. See docs for the accounts.
+    ///
+    ///   0. `[]` The system program
+    ///   1. `[]` The clock SysVar
+    ///   2. `[writable, derived]` The bridge config
+    ///   3. `[writable, signer]` The buffer key of the upgrade, pays for the migration
+    ///   4. `[writable, derived]` The claim of the upgrade VAA
+    ///   5. `[writable, derived]` The migration stage of the bridge
+    ///   6..n. `[writable]` Accounts to migrate
+    Migrate(MigratePayload),
+
+    /// Pays out the reimbursements owed to a guardian as far as the funds of the bridge allow.
+    ///
+    ///   0. `[writable, derived]` The bridge config
+    ///   1. `[writable, derived]` The ledger of the guardian
+    ///   2. `[writable, signer]` The fee payer of the guardian
+    ClaimReimbursement(),
+
+    /// Burns or locks several (wrapped) assets to the same target in one instruction. A proposal
+    /// is created for each asset and the fees of all transfers are paid with a single transfer to
+    /// the bridge in the preceding instruction.
+    ///
+    ///   0. `[]` The bridge program
+    ///   1. `[]` The System program.
+    ///   2. `[]` The spl token program.
+    ///   3. `[]` The rent SysVar
+    ///   4. `[]` The clock SysVar
+    ///   5. `[]` The instructions SysVar
+    ///   6. `[derived]` The bridge config
+    ///   7. `[writable, signer]` The fee payer for new account creation
+    ///
+    /// Followed by these accounts for each asset of the batch:
+    ///
+    ///   0. `[writable]`  The from token account
+    ///   1. `[writable, derived, empty]` The new transfer out tracking account
+    ///   2. `[writable, derived]` The mint of the (wrapped) asset
+    ///   3. `[writable, derived]` The transfer sequence of the from token account
+    ///   4. `[writable, derived]` The custody token account of the bridge (native tokens), or
+    ///       `[derived]` the wrapped asset meta of the mint (wrapped tokens)
+    TransferOutBatch(TransferOutBatchPayload),
 }
 
 impl BridgeInstruction {
@@ -165,7 +368,7 @@ impl BridgeInstruction {
                     chain_id: payload.chain_id,
                     asset: payload.asset,
                     target: payload.target,
-                    nonce: payload.nonce,
+                    custody_bump: payload.custody_bump,
                 })
             }
             2 => {
@@ -183,6 +386,55 @@ impl BridgeInstruction {
 
                 CreateWrapped(*payload)
             }
+            8 => {
+                let payload: &TransferOutPayloadRaw = unpack(input)?;
+                let amount = U256::from_big_endian(&payload.amount);
+
+                TransferOutCPI(TransferOutPayload {
+                    amount,
+                    chain_id: payload.chain_id,
+                    asset: payload.asset,
+                    target: payload.target,
+                    custody_bump: payload.custody_bump,
+                })
+            }
+            9 => CloseGuardianSet(),
+            10 => EvictSignatures(),
+            11 => {
+                if input.len() < 5 || input.len() < 5 + input[4] as usize {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                let bumps_end = 5 + input[4] as usize;
+
+                Migrate(MigratePayload {
+                    finish: input[1] != 0,
+                    bridge_bump: input[2],
+                    claim_bump: input[3],
+                    bumps: input[5..bumps_end].to_vec(),
+                    vaa: input[bumps_end..].to_vec(),
+                })
+            }
+            12 => ClaimReimbursement(),
+            13 => {
+                let payload: &TransferOutBatchPayloadRaw = unpack(input)?;
+                let len_transfers = payload.len_transfers as usize;
+                if len_transfers == 0 || len_transfers > MAX_BATCHED_TRANSFERS {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+
+                TransferOutBatch(TransferOutBatchPayload {
+                    chain_id: payload.chain_id,
+                    target: payload.target,
+                    transfers: payload.transfers[..len_transfers]
+                        .iter()
+                        .map(|t| BatchedTransfer {
+                            amount: U256::from_big_endian(&t.amount),
+                            asset: t.asset,
+                            custody_bump: t.custody_bump,
+                        })
+                        .collect(),
+                })
+            }
             _ => return Err(ProgramError::InvalidInstructionData),
         })
     }
@@ -217,7 +469,7 @@ impl BridgeInstruction {
                     chain_id: payload.chain_id,
                     asset: payload.asset,
                     target: payload.target,
-                    nonce: payload.nonce,
+                    custody_bump: payload.custody_bump,
                 };
             }
             Self::PostVAA(payload) => {
@@ -255,6 +507,77 @@ impl BridgeInstruction {
                     unsafe { &mut *(&mut output[size_of::<u8>()] as *mut u8 as *mut AssetMeta) };
                 *value = payload;
             }
+            Self::TransferOutCPI(payload) => {
+                output.resize(size_of::<TransferOutPayloadRaw>() + 1, 0);
+                output[0] = 8;
+                #[allow(clippy::cast_ptr_alignment)]
+                    let value = unsafe {
+                    &mut *(&mut output[size_of::<u8>()] as *mut u8 as *mut TransferOutPayloadRaw)
+                };
+
+                let mut amount_bytes = [0u8; 32];
+                payload.amount.to_big_endian(&mut amount_bytes);
+
+                *value = TransferOutPayloadRaw {
+                    amount: amount_bytes,
+                    chain_id: payload.chain_id,
+                    asset: payload.asset,
+                    target: payload.target,
+                    custody_bump: payload.custody_bump,
+                };
+            }
+            Self::CloseGuardianSet() => {
+                output.resize(1, 0);
+                output[0] = 9;
+            }
+            Self::EvictSignatures() => {
+                output.resize(1, 0);
+                output[0] = 10;
+            }
+            Self::Migrate(payload) => {
+                if payload.bumps.len() > u8::MAX as usize {
+                    return Err(ProgramError::InvalidArgument);
+                }
+                output.resize(5, 0);
+                output[0] = 11;
+                output[1] = payload.finish as u8;
+                output[2] = payload.bridge_bump;
+                output[3] = payload.claim_bump;
+                output[4] = payload.bumps.len() as u8;
+                output.extend_from_slice(&payload.bumps);
+                output.extend_from_slice(&payload.vaa);
+            }
+            Self::ClaimReimbursement() => {
+                output.resize(1, 0);
+                output[0] = 12;
+            }
+            Self::TransferOutBatch(payload) => {
+                if payload.transfers.is_empty() || payload.transfers.len() > MAX_BATCHED_TRANSFERS {
+                    return Err(ProgramError::InvalidArgument);
+                }
+
+                output.resize(size_of::<TransferOutBatchPayloadRaw>() + 1, 0);
+                output[0] = 13;
+                #[allow(clippy::cast_ptr_alignment)]
+                    let value = unsafe {
+                    &mut *(&mut output[size_of::<u8>()] as *mut u8
+                        as *mut TransferOutBatchPayloadRaw)
+                };
+
+                let mut transfers = [BatchedTransferRaw::default(); MAX_BATCHED_TRANSFERS];
+                for (raw, t) in transfers.iter_mut().zip(payload.transfers.iter()) {
+                    t.amount.to_big_endian(&mut raw.amount);
+                    raw.asset = t.asset;
+                    raw.custody_bump = t.custody_bump;
+                }
+
+                *value = TransferOutBatchPayloadRaw {
+                    chain_id: payload.chain_id,
+                    target: payload.target,
+                    len_transfers: payload.transfers.len() as u8,
+                    transfers,
+                };
+            }
         }
         Ok(output)
     }
@@ -301,6 +624,7 @@ pub fn initialize(
 }
 
 /// Creates an 'TransferOut' instruction.
+/// `sequence` is the next sequence number stored in the transfer sequence of `token_account`.
 #[cfg(not(target_arch = "bpf"))]
 pub fn transfer_out(
     program_id: &Pubkey,
@@ -308,9 +632,9 @@ pub fn transfer_out(
     token_account: &Pubkey,
     token_mint: &Pubkey,
     t: &TransferOutPayload,
+    sequence: u32,
 ) -> Result<Instruction, ProgramError> {
-    let data = BridgeInstruction::TransferOut(*t).serialize()?;
-
+    let mut t = *t;
     let bridge_key = Bridge::derive_bridge_id(program_id)?;
     let transfer_key = Bridge::derive_transfer_id(
         program_id,
@@ -320,8 +644,9 @@ pub fn transfer_out(
         t.chain_id,
         t.target,
         token_account.to_bytes(),
-        t.nonce,
+        sequence,
     )?;
+    let sequence_key = Bridge::derive_sequence_id(program_id, &bridge_key, token_account)?;
 
     let mut accounts = vec![
         AccountMeta::new_readonly(*program_id, false),
@@ -335,18 +660,150 @@ pub fn transfer_out(
         AccountMeta::new(transfer_key, false),
         AccountMeta::new(*token_mint, false),
         AccountMeta::new(*payer, true),
+        AccountMeta::new(sequence_key, false),
     ];
 
-    // If the token is a native solana token add a custody account
+    // If the token is a native solana token add a custody account, otherwise the wrapped meta
     if t.asset.chain == CHAIN_ID_SOLANA {
-        let custody_key = Bridge::derive_custody_id(program_id, &bridge_key, token_mint)?;
+        let (custody_key, custody_bump) =
+            Bridge::derive_custody_id_and_bump(program_id, &bridge_key, token_mint)?;
         accounts.push(AccountMeta::new(custody_key, false));
+        t.custody_bump = custody_bump;
+    } else {
+        let wrapped_meta_key = Bridge::derive_wrapped_meta_id(program_id, &bridge_key, token_mint)?;
+        accounts.push(AccountMeta::new_readonly(wrapped_meta_key, false));
     }
 
     Ok(Instruction {
         program_id: *program_id,
         accounts,
-        data,
+        data: BridgeInstruction::TransferOut(t).serialize()?,
+    })
+}
+
+/// Creates a 'TransferOutCPI' instruction.
+/// `authority` is the owner or delegate of `token_account` and must sign the instruction.
+///
+/// The accounts are those of `transfer_out` followed by `authority`:
+/// bridge program, system program, token program, rent, clock, instructions, `token_account`,
+/// bridge (writable, receives the fee), proposal, `token_mint`, `payer`, sequence, custody account
+/// or wrapped meta, `authority`.
+pub fn transfer_out_cpi(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    authority: &Pubkey,
+    token_account: &Pubkey,
+    token_mint: &Pubkey,
+    t: &TransferOutPayload,
+    sequence: u32,
+) -> Result<Instruction, ProgramError> {
+    let mut t = *t;
+    let bridge_key = Bridge::derive_bridge_id(program_id)?;
+    let transfer_key = Bridge::derive_transfer_id(
+        program_id,
+        &bridge_key,
+        t.asset.chain,
+        t.asset.address,
+        t.chain_id,
+        t.target,
+        token_account.to_bytes(),
+        sequence,
+    )?;
+    let sequence_key = Bridge::derive_sequence_id(program_id, &bridge_key, token_account)?;
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*program_id, false),
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(solana_program::sysvar::rent::id(), false),
+        AccountMeta::new_readonly(solana_program::sysvar::clock::id(), false),
+        AccountMeta::new_readonly(solana_program::sysvar::instructions::id(), false),
+        AccountMeta::new(*token_account, false),
+        AccountMeta::new(bridge_key, false),
+        AccountMeta::new(transfer_key, false),
+        AccountMeta::new(*token_mint, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(sequence_key, false),
+    ];
+
+    // If the token is a native solana token add a custody account, otherwise the wrapped meta
+    if t.asset.chain == CHAIN_ID_SOLANA {
+        let (custody_key, custody_bump) =
+            Bridge::derive_custody_id_and_bump(program_id, &bridge_key, token_mint)?;
+        accounts.push(AccountMeta::new(custody_key, false));
+        t.custody_bump = custody_bump;
+    } else {
+        let wrapped_meta_key = Bridge::derive_wrapped_meta_id(program_id, &bridge_key, token_mint)?;
+        accounts.push(AccountMeta::new_readonly(wrapped_meta_key, false));
+    }
+    accounts.push(AccountMeta::new_readonly(*authority, true));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: BridgeInstruction::TransferOutCPI(t).serialize()?,
+    })
+}
+
+/// Creates a 'TransferOutBatch' instruction.
+/// `sources` lists the token account, its mint and the next sequence number of the token account
+/// for each transfer of the batch, in order.
+#[cfg(not(target_arch = "bpf"))]
+pub fn transfer_out_batch(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    sources: &[(Pubkey, Pubkey, u32)],
+    b: &TransferOutBatchPayload,
+) -> Result<Instruction, ProgramError> {
+    if sources.len() != b.transfers.len() {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let mut b = b.clone();
+    let bridge_key = Bridge::derive_bridge_id(program_id)?;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*program_id, false),
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(solana_program::sysvar::rent::id(), false),
+        AccountMeta::new_readonly(solana_program::sysvar::clock::id(), false),
+        AccountMeta::new_readonly(solana_program::sysvar::instructions::id(), false),
+        AccountMeta::new_readonly(bridge_key, false),
+        AccountMeta::new(*payer, true),
+    ];
+
+    for ((token_account, token_mint, sequence), t) in sources.iter().zip(b.transfers.iter_mut()) {
+        let transfer_key = Bridge::derive_transfer_id(
+            program_id,
+            &bridge_key,
+            t.asset.chain,
+            t.asset.address,
+            b.chain_id,
+            b.target,
+            token_account.to_bytes(),
+            *sequence,
+        )?;
+        let sequence_key = Bridge::derive_sequence_id(program_id, &bridge_key, token_account)?;
+
+        accounts.push(AccountMeta::new(*token_account, false));
+        accounts.push(AccountMeta::new(transfer_key, false));
+        accounts.push(AccountMeta::new(*token_mint, false));
+        accounts.push(AccountMeta::new(sequence_key, false));
+        if t.asset.chain == CHAIN_ID_SOLANA {
+            let (custody_key, custody_bump) =
+                Bridge::derive_custody_id_and_bump(program_id, &bridge_key, token_mint)?;
+            accounts.push(AccountMeta::new(custody_key, false));
+            t.custody_bump = custody_bump;
+        } else {
+            let wrapped_meta_key =
+                Bridge::derive_wrapped_meta_id(program_id, &bridge_key, token_mint)?;
+            accounts.push(AccountMeta::new_readonly(wrapped_meta_key, false));
+        }
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: BridgeInstruction::TransferOutBatch(b).serialize()?,
     })
 }
 
@@ -373,6 +830,7 @@ pub fn verify_signatures(
         AccountMeta::new(*signature_acc, false),
         AccountMeta::new_readonly(guardian_set_key, false),
         AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(solana_program::sysvar::clock::id(), false),
     ];
 
     Ok(Instruction {
@@ -389,9 +847,6 @@ pub fn post_vaa(
     payer: &Pubkey,
     v: VAAData,
 ) -> Result<Instruction, ProgramError> {
-    let mut data = v.clone();
-    data.insert(0, 2);
-
     // Parse VAA
     let vaa = VAA::deserialize(&v[..])?;
 
@@ -406,6 +861,7 @@ pub fn post_vaa(
         &vaa.body_hash()?,
         vaa.guardian_set_index,
     )?;
+    let ledger_key = Bridge::derive_ledger_id(program_id, &bridge_key, payer)?;
 
     let mut accounts = vec![
         AccountMeta::new_readonly(*program_id, false),
@@ -417,6 +873,7 @@ pub fn post_vaa(
         AccountMeta::new(claim_key, false),
         AccountMeta::new(signature_acc, false),
         AccountMeta::new(*payer, true),
+        AccountMeta::new(ledger_key, false),
     ];
 
     match vaa.payload.unwrap() {
@@ -426,11 +883,60 @@ pub fn post_vaa(
             accounts.push(AccountMeta::new(guardian_set_key, false));
         }
         VAABody::UpgradeContract(u) => {
+            // The program account is modified by the upgrade
+            accounts[0] = AccountMeta::new(*program_id, false);
             accounts.push(AccountMeta::new(u.buffer, false));
             let (programdata_address, _) = Pubkey::find_program_address(&[program_id.as_ref()], &solana_program::bpf_loader_upgradeable::id());
             accounts.push(AccountMeta::new(programdata_address, false));
             accounts.push(AccountMeta::new_readonly(solana_program::bpf_loader_upgradeable::id(), false));
         }
+        VAABody::TreasuryWithdrawal(w) => {
+            accounts.push(AccountMeta::new(w.recipient, false));
+        }
+        VAABody::FreezeAccount(f) => {
+            accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+            accounts.push(AccountMeta::new_readonly(f.mint, false));
+            accounts.push(AccountMeta::new(f.token_account, false));
+        }
+        VAABody::HaltMinting(h) => {
+            let wrapped_meta_key =
+                Bridge::derive_wrapped_meta_id(program_id, &bridge_key, &h.mint)?;
+            accounts.push(AccountMeta::new(wrapped_meta_key, false));
+        }
+        VAABody::RevertTransfer(BodyRevertTransfer { transfer: t, .. }) => {
+            let transfer_key = Bridge::derive_transfer_id(
+                program_id,
+                &bridge_key,
+                t.asset.chain,
+                t.asset.address,
+                t.target_chain,
+                t.target_address,
+                t.source_address,
+                t.nonce,
+            )?;
+            accounts.push(AccountMeta::new(transfer_key, false));
+            accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+            if t.asset.chain == CHAIN_ID_SOLANA {
+                let mint_key = Pubkey::new(&t.asset.address);
+                let custody_key = Bridge::derive_custody_id(program_id, &bridge_key, &mint_key)?;
+                accounts.push(AccountMeta::new(mint_key, false));
+                accounts.push(AccountMeta::new(Pubkey::new(&t.source_address), false));
+                accounts.push(AccountMeta::new(custody_key, false));
+            } else {
+                let wrapped_key = Bridge::derive_wrapped_asset_id(
+                    program_id,
+                    &bridge_key,
+                    t.asset.chain,
+                    t.asset.decimals,
+                    t.asset.address,
+                )?;
+                let wrapped_meta_key =
+                    Bridge::derive_wrapped_meta_id(program_id, &bridge_key, &wrapped_key)?;
+                accounts.push(AccountMeta::new(wrapped_key, false));
+                accounts.push(AccountMeta::new(Pubkey::new(&t.source_address), false));
+                accounts.push(AccountMeta::new_readonly(wrapped_meta_key, false));
+            }
+        }
         VAABody::Transfer(t) => {
             if t.source_chain == CHAIN_ID_SOLANA {
                 // Solana (any) -> Ethereum (any)
@@ -444,7 +950,10 @@ pub fn post_vaa(
                     t.source_address,
                     t.nonce,
                 )?;
-                accounts.push(AccountMeta::new(transfer_key, false))
+                let posted_vaa_key =
+                    Bridge::derive_posted_vaa_id(program_id, &bridge_key, &transfer_key)?;
+                accounts.push(AccountMeta::new(transfer_key, false));
+                accounts.push(AccountMeta::new(posted_vaa_key, false));
             } else if t.asset.chain == CHAIN_ID_SOLANA {
                 // Foreign (wrapped) -> Solana (native)
                 let mint_key = Pubkey::new(&t.asset.address);
@@ -475,7 +984,7 @@ pub fn post_vaa(
     Ok(Instruction {
         program_id: *program_id,
         accounts,
-        data,
+        data: BridgeInstruction::PostVAA(v).serialize()?,
     })
 }
 
@@ -532,6 +1041,134 @@ pub fn poke_proposal(
     })
 }
 
+/// Creates a 'CloseGuardianSet' instruction.
+#[cfg(not(target_arch = "bpf"))]
+pub fn close_guardian_set(
+    program_id: &Pubkey,
+    guardian_set_index: u32,
+) -> Result<Instruction, ProgramError> {
+    let data = BridgeInstruction::CloseGuardianSet().serialize()?;
+
+    let bridge_key = Bridge::derive_bridge_id(program_id)?;
+    let guardian_set_key =
+        Bridge::derive_guardian_set_id(program_id, &bridge_key, guardian_set_index)?;
+
+    let accounts = vec![
+        AccountMeta::new_readonly(solana_program::sysvar::clock::id(), false),
+        AccountMeta::new(bridge_key, false),
+        AccountMeta::new(guardian_set_key, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'EvictSignatures' instruction.
+#[cfg(not(target_arch = "bpf"))]
+pub fn evict_signatures(
+    program_id: &Pubkey,
+    signature_acc: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = BridgeInstruction::EvictSignatures().serialize()?;
+
+    let bridge_key = Bridge::derive_bridge_id(program_id)?;
+
+    let accounts = vec![
+        AccountMeta::new_readonly(solana_program::sysvar::clock::id(), false),
+        AccountMeta::new(bridge_key, false),
+        AccountMeta::new(*signature_acc, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'Migrate' instruction. `migrated_accounts` lists each account to migrate followed
+/// by its migration stage and the additional accounts its conversion needs. `bumps` are the bump
+/// seeds of the accounts of the previous layout, see `MigratePayload`.
+#[cfg(not(target_arch = "bpf"))]
+pub fn migrate(
+    program_id: &Pubkey,
+    vaa: &VAA,
+    finish: bool,
+    migrated_accounts: &[Pubkey],
+    bumps: &[u8],
+) -> Result<Instruction, ProgramError> {
+    let upgrade = match &vaa.payload {
+        Some(VAABody::UpgradeContract(u)) => u,
+        _ => return Err(ProgramError::InvalidArgument),
+    };
+
+    let (bridge_key, bridge_seeds) = Bridge::derive_key(program_id, &Bridge::derive_bridge_seeds())?;
+    let (claim_key, claim_seeds) = Bridge::derive_key(
+        program_id,
+        &Bridge::derive_claim_seeds(&bridge_key, vaa.signature_body()?),
+    )?;
+
+    // The signatures were verified when the upgrade was executed
+    let mut v = vaa.clone();
+    v.signatures.clear();
+    let data = BridgeInstruction::Migrate(MigratePayload {
+        finish,
+        bridge_bump: bridge_seeds.last().map(|b| b[0]).unwrap_or_default(),
+        claim_bump: claim_seeds.last().map(|b| b[0]).unwrap_or_default(),
+        bumps: bumps.to_vec(),
+        vaa: v.serialize()?,
+    })
+    .serialize()?;
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+        AccountMeta::new_readonly(solana_program::sysvar::clock::id(), false),
+        AccountMeta::new(bridge_key, false),
+        AccountMeta::new(upgrade.buffer, true),
+        AccountMeta::new(claim_key, false),
+        AccountMeta::new(
+            Bridge::derive_migration_stage_id(program_id, &bridge_key, &bridge_key)?,
+            false,
+        ),
+    ];
+    for k in migrated_accounts {
+        accounts.push(AccountMeta::new(*k, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'ClaimReimbursement' instruction.
+#[cfg(not(target_arch = "bpf"))]
+pub fn claim_reimbursement(
+    program_id: &Pubkey,
+    guardian: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = BridgeInstruction::ClaimReimbursement().serialize()?;
+
+    let bridge_key = Bridge::derive_bridge_id(program_id)?;
+    let ledger_key = Bridge::derive_ledger_id(program_id, &bridge_key, guardian)?;
+
+    let accounts = vec![
+        AccountMeta::new(bridge_key, false),
+        AccountMeta::new(ledger_key, false),
+        AccountMeta::new(*guardian, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
 /// Unpacks a reference from a bytes buffer.
 pub fn unpack<T>(input: &[u8]) -> Result<&T, ProgramError> {
     if input.len() < size_of::<u8>() + size_of::<T>() {
@@ -541,3 +1178,80 @@ pub fn unpack<T>(input: &[u8]) -> Result<&T, ProgramError> {
         let val: &T = unsafe { &*(&input[1] as *const u8 as *const T) };
     Ok(val)
 }
+
+#[cfg(test)]
+mod tests {
+    use solana_program::message::Message;
+
+    use crate::vaa::BodyTransfer;
+
+    use super::*;
+
+    /// Size of a transaction carrying `message` and its signatures
+    fn transaction_size(message: &Message) -> usize {
+        1 + 64 * message.header.num_required_signatures as usize + message.serialize().len()
+    }
+
+    #[test]
+    fn max_size_vaa_fits_in_post_vaa_transaction() {
+        let program_id = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        let vaa = VAA {
+            version: 1,
+            guardian_set_index: 0,
+            signatures: vec![],
+            timestamp: 100,
+            payload: Some(VAABody::Transfer(BodyTransfer {
+                nonce: 1,
+                source_chain: CHAIN_ID_SOLANA,
+                target_chain: CHAIN_ID_ETHEREUM,
+                source_address: [1; 32],
+                target_address: [2; 32],
+                asset: AssetMeta {
+                    address: [3; 32],
+                    chain: CHAIN_ID_SOLANA,
+                    decimals: 9,
+                },
+                amount: U256::from(1000),
+            })),
+        };
+        let mut data = vaa.serialize().unwrap();
+        data.resize(MAX_VAA_SIZE, 0);
+
+        let ix = post_vaa(&program_id, &payer, data).unwrap();
+        assert_eq!(ix.accounts.len(), POST_VAA_TRANSFER_ACCOUNTS);
+        let message = Message::new(&[ix], Some(&payer));
+        assert_eq!(transaction_size(&message), PACKET_DATA_SIZE);
+    }
+
+    #[test]
+    fn foreign_addresses_are_checked_per_chain() {
+        let mut ethereum = [0; FOREIGN_ADDRESS_SIZE];
+        ethereum[FOREIGN_ADDRESS_SIZE - 20..].copy_from_slice(&[4; 20]);
+        let mut dirty = ethereum;
+        dirty[0] = 1;
+
+        assert_eq!(
+            check_foreign_address(CHAIN_ID_SOLANA, &[4; FOREIGN_ADDRESS_SIZE]),
+            Err(Error::SameChainTransfer)
+        );
+        for chain in &[CHAIN_ID_ETHEREUM, CHAIN_ID_TERRA, 4] {
+            assert_eq!(
+                check_foreign_address(*chain, &[0; FOREIGN_ADDRESS_SIZE]),
+                Err(Error::InvalidTargetAddress)
+            );
+            assert_eq!(check_foreign_address(*chain, &ethereum), Ok(()));
+        }
+
+        // Ethereum and Terra addresses only have 20 bytes, other chains use all 32
+        assert_eq!(
+            check_foreign_address(CHAIN_ID_ETHEREUM, &dirty),
+            Err(Error::InvalidTargetAddress)
+        );
+        assert_eq!(
+            check_foreign_address(CHAIN_ID_TERRA, &dirty),
+            Err(Error::InvalidTargetAddress)
+        );
+        assert_eq!(check_foreign_address(4, &dirty), Ok(()));
+    }
+}
@@ -0,0 +1,335 @@
+//! Account layouts of the previous program version, converted by `Migrate`
+
+use primitive_types::U256;
+use solana_program::pubkey::Pubkey;
+
+use crate::{
+    instruction::{ForeignAddress, MAX_LEN_GUARDIAN_KEYS},
+    state::{
+        AssetMeta, Bridge, BridgeConfig, GuardianSet, IsInitialized, SignatureState,
+        TransferOutProposal, UpgradeRecord, WrappedAssetMeta,
+    },
+};
+
+/// Maximum size of a VAA stored in a proposal of the previous version
+pub const LEGACY_MAX_VAA_SIZE: usize = 1000;
+
+/// Byte terminating the VAA stored in a proposal of the previous version
+const LEGACY_VAA_TERMINATOR: u8 = 0xff;
+
+/// Size of the payload of a transfer VAA
+const TRANSFER_PAYLOAD_SIZE: usize = 136;
+
+/// Bridge state of the previous version
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LegacyBridge {
+    /// the currently active guardian set
+    pub guardian_set_index: u32,
+
+    /// read-only config parameters for a bridge instance.
+    pub config: BridgeConfig,
+
+    /// Is `true` if this structure has been initialized.
+    pub is_initialized: bool,
+}
+
+impl IsInitialized for LegacyBridge {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl LegacyBridge {
+    /// Converts the bridge, recording the upgrade that deployed this version
+    pub fn convert(&self, bump: u8, last_upgrade: UpgradeRecord) -> Bridge {
+        Bridge {
+            guardian_set_index: self.guardian_set_index,
+            config: self.config,
+            last_upgrade,
+            owed: 0,
+            is_initialized: self.is_initialized,
+            bump,
+        }
+    }
+}
+
+/// guardian set of the previous version
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct LegacyGuardianSet {
+    /// index of the set
+    pub index: u32,
+    /// number of keys stored
+    pub len_keys: u8,
+    /// public key hashes of the guardian set
+    pub keys: [[u8; 20]; MAX_LEN_GUARDIAN_KEYS],
+    /// creation time
+    pub creation_time: u32,
+    /// expiration time when VAAs issued by this set are no longer valid
+    pub expiration_time: u32,
+
+    /// Is `true` if this structure has been initialized.
+    pub is_initialized: bool,
+}
+
+impl IsInitialized for LegacyGuardianSet {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl LegacyGuardianSet {
+    pub fn convert(&self, bump: u8) -> GuardianSet {
+        GuardianSet {
+            index: self.index,
+            len_keys: self.len_keys,
+            keys: self.keys,
+            creation_time: self.creation_time,
+            expiration_time: self.expiration_time,
+            is_initialized: self.is_initialized,
+            bump,
+        }
+    }
+}
+
+/// proposal to transfer tokens to a foreign chain of the previous version
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct LegacyTransferOutProposal {
+    /// amount to transfer
+    pub amount: U256,
+    /// chain id to transfer to
+    pub to_chain_id: u8,
+    /// address the transfer was initiated from
+    pub source_address: ForeignAddress,
+    /// address on the foreign chain to transfer to
+    pub foreign_address: ForeignAddress,
+    /// asset that is being transferred
+    pub asset: AssetMeta,
+    /// nonce of the transfer
+    pub nonce: u32,
+    /// vaa to unlock the tokens on the foreign chain, terminated by `LEGACY_VAA_TERMINATOR`
+    pub vaa: [u8; LEGACY_MAX_VAA_SIZE + 1],
+    /// time the vaa was submitted
+    pub vaa_time: u32,
+    /// time the lockup was created
+    pub lockup_time: u32,
+    /// times the proposal has been poked
+    pub poke_counter: u8,
+    /// Account where signatures are stored
+    pub signature_account: Pubkey,
+
+    /// Is `true` if this structure has been initialized.
+    pub is_initialized: bool,
+}
+
+impl IsInitialized for LegacyTransferOutProposal {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl LegacyTransferOutProposal {
+    /// Returns the VAA stored in the proposal, `None` if no VAA was posted yet
+    pub fn posted_vaa(&self) -> Option<&[u8]> {
+        if self.vaa_time == 0 {
+            return None;
+        }
+
+        // The stored VAA is a transfer, its length follows from the number of signatures
+        let len = 1 + 4 + 1 + 66 * self.vaa[5] as usize + 4 + 1 + TRANSFER_PAYLOAD_SIZE;
+        if len > LEGACY_MAX_VAA_SIZE || self.vaa[len] != LEGACY_VAA_TERMINATOR {
+            return None;
+        }
+        Some(&self.vaa[..len])
+    }
+
+    /// Converts the proposal. The nonce chosen by the sender becomes the sequence of the
+    /// transfer, it is part of the seeds of the proposal.
+    pub fn convert(&self, bump: u8, vaa_account: Pubkey) -> TransferOutProposal {
+        TransferOutProposal {
+            amount: self.amount,
+            to_chain_id: self.to_chain_id,
+            source_address: self.source_address,
+            foreign_address: self.foreign_address,
+            asset: self.asset,
+            sequence: self.nonce,
+            vaa_account,
+            vaa_time: self.vaa_time,
+            lockup_time: self.lockup_time,
+            poke_counter: self.poke_counter,
+            signature_account: self.signature_account,
+            reverted: false,
+            is_initialized: self.is_initialized,
+            bump,
+        }
+    }
+}
+
+/// metadata tracking for wrapped assets of the previous version
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LegacyWrappedAssetMeta {
+    /// chain id of the native chain of this asset
+    pub chain: u8,
+    /// address of the asset on the native chain
+    pub address: ForeignAddress,
+
+    /// Is `true` if this structure has been initialized.
+    pub is_initialized: bool,
+}
+
+impl IsInitialized for LegacyWrappedAssetMeta {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl LegacyWrappedAssetMeta {
+    pub fn convert(&self, bump: u8, mint_bump: u8) -> WrappedAssetMeta {
+        WrappedAssetMeta {
+            chain: self.chain,
+            address: self.address,
+            minting_halted: false,
+            is_initialized: self.is_initialized,
+            bump,
+            mint_bump,
+        }
+    }
+}
+
+/// Signature state of the previous version
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct LegacySignatureState {
+    /// signatures of validators
+    pub signatures: [[u8; 65]; MAX_LEN_GUARDIAN_KEYS],
+
+    /// hash of the data
+    pub hash: [u8; 32],
+
+    /// index of the guardian set
+    pub guardian_set_index: u32,
+
+    /// Is `true` if this structure has been initialized.
+    pub is_initialized: bool,
+}
+
+impl IsInitialized for LegacySignatureState {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl LegacySignatureState {
+    /// Converts the signature state. The previous version did not record when the first
+    /// signature was verified, so the eviction period starts at `creation_time`.
+    pub fn convert(&self, bump: u8, creation_time: u32) -> SignatureState {
+        SignatureState {
+            signatures: self.signatures,
+            hash: self.hash,
+            guardian_set_index: self.guardian_set_index,
+            creation_time,
+            is_initialized: self.is_initialized,
+            bump,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::size_of;
+
+    use super::*;
+
+    #[test]
+    fn legacy_sizes_are_distinct() {
+        let sizes = [
+            size_of::<LegacyBridge>(),
+            size_of::<LegacyGuardianSet>(),
+            size_of::<LegacyTransferOutProposal>(),
+            size_of::<LegacyWrappedAssetMeta>(),
+            size_of::<LegacySignatureState>(),
+        ];
+        for (i, a) in sizes.iter().enumerate() {
+            for b in sizes[i + 1..].iter() {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    fn proposal(vaa_time: u32) -> LegacyTransferOutProposal {
+        LegacyTransferOutProposal {
+            amount: U256::from(5),
+            to_chain_id: 2,
+            source_address: [1; 32],
+            foreign_address: [2; 32],
+            asset: AssetMeta {
+                address: [3; 32],
+                chain: 1,
+                decimals: 9,
+            },
+            nonce: 7,
+            vaa: [0; LEGACY_MAX_VAA_SIZE + 1],
+            vaa_time,
+            lockup_time: 10,
+            poke_counter: 1,
+            signature_account: Pubkey::new_unique(),
+            is_initialized: true,
+        }
+    }
+
+    #[test]
+    fn legacy_proposal_posted_vaa() {
+        assert_eq!(proposal(0).posted_vaa(), None);
+
+        // A VAA with two signatures
+        let mut p = proposal(20);
+        let len = 6 + 66 * 2 + 5 + TRANSFER_PAYLOAD_SIZE;
+        for (i, b) in p.vaa[..len].iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        p.vaa[5] = 2;
+        p.vaa[len] = LEGACY_VAA_TERMINATOR;
+        assert_eq!(p.posted_vaa(), Some(&p.vaa[..len]));
+
+        // The terminator needs to follow the VAA
+        p.vaa[len] = 0;
+        assert_eq!(p.posted_vaa(), None);
+
+        // A VAA that would exceed the stored data
+        p.vaa[5] = 20;
+        assert_eq!(p.posted_vaa(), None);
+    }
+
+    #[test]
+    fn legacy_proposal_convert() {
+        let p = proposal(20);
+        let vaa_account = Pubkey::new_unique();
+        let c = p.convert(254, vaa_account);
+        assert_eq!(c.sequence, p.nonce);
+        assert_eq!(c.vaa_account, vaa_account);
+        assert_eq!(c.vaa_time, p.vaa_time);
+        assert_eq!(c.lockup_time, p.lockup_time);
+        assert_eq!(c.signature_account, p.signature_account);
+        assert_eq!(c.bump, 254);
+        assert!(!c.reverted);
+        assert!(c.is_initialized);
+    }
+
+    #[test]
+    fn legacy_signature_state_convert() {
+        let s = LegacySignatureState {
+            signatures: [[4; 65]; MAX_LEN_GUARDIAN_KEYS],
+            hash: [5; 32],
+            guardian_set_index: 3,
+            is_initialized: true,
+        };
+        let c = s.convert(253, 1000);
+        assert_eq!(c.hash, s.hash);
+        assert_eq!(c.guardian_set_index, 3);
+        assert_eq!(c.creation_time, 1000);
+        assert_eq!(c.bump, 253);
+    }
+}
@@ -3,10 +3,12 @@ extern crate zerocopy;
 #[macro_use]
 extern crate solana_program;
 
+pub mod associated_token;
 pub mod entrypoint;
 pub mod error;
 pub mod error_program;
 pub mod instruction;
+pub mod legacy;
 pub mod processor;
 pub mod state;
 pub mod vaa;
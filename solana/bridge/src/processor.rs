@@ -7,36 +7,45 @@ use byteorder::ByteOrder;
 use num_traits::AsPrimitive;
 use primitive_types::U256;
 use sha3::Digest;
-use solana_program::program::invoke_signed;
+use solana_program::program::{invoke, invoke_signed};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
     clock::Clock,
     entrypoint::ProgramResult,
-    hash::Hasher,
+    hash::{hash, Hasher},
     info,
     instruction::Instruction,
     program_error::ProgramError,
     pubkey::Pubkey,
     rent::Rent,
-    system_instruction::{create_account, SystemInstruction},
+    system_instruction::{self, create_account, SystemInstruction},
     sysvar::Sysvar,
 };
 use spl_token::{state::Mint};
 
 use crate::{
+    associated_token,
     error::Error,
     instruction::{
-        BridgeInstruction, BridgeInstruction::*, TransferOutPayload, VAAData, VerifySigPayload,
-        CHAIN_ID_SOLANA, MAX_LEN_GUARDIAN_KEYS, MAX_VAA_SIZE,
+        BridgeInstruction, BridgeInstruction::*, MigratePayload, TransferOutBatchPayload,
+        TransferOutPayload, VAAData, check_foreign_address, VerifySigPayload, CHAIN_ID_SOLANA,
+        MAX_LEN_GUARDIAN_KEYS, MAX_VAA_SIZE,
+    },
+    legacy::{
+        LegacyBridge, LegacyGuardianSet, LegacySignatureState, LegacyTransferOutProposal,
+        LegacyWrappedAssetMeta,
     },
     state::*,
-    vaa::{BodyTransfer, BodyUpdateGuardianSet, VAABody, VAA},
+    vaa::{BodyRevertTransfer, BodyTransfer, BodyUpdateGuardianSet, VAABody, VAA},
 };
 use solana_program::program_pack::Pack;
 use std::borrow::BorrowMut;
 use std::ops::Add;
 use solana_program::fee_calculator::FeeCalculator;
-use crate::vaa::BodyContractUpgrade;
+use crate::vaa::{
+    BodyContractUpgrade, BodyFreezeAccount, BodyHaltMinting, BodyTreasuryWithdrawal,
+};
 
 /// SigInfo contains metadata about signers in a VerifySignature ix
 struct SigInfo {
@@ -46,11 +55,12 @@ struct SigInfo {
     sig_index: u8,
 }
 
-struct SecpInstructionPart<'a> {
-    address: &'a [u8],
-    signature: &'a [u8],
-    msg_offset: u16,
-    msg_size: u16,
+/// size of the signature offsets of a secp instruction
+const SECP_OFFSETS_SIZE: usize = 11;
+
+struct SecpInstructionPart {
+    address: [u8; 20],
+    signature: [u8; 65],
 }
 
 /// Instruction processing logic
@@ -73,9 +83,18 @@ impl Bridge {
                 msg!("Instruction: TransferOut");
 
                 if p.asset.chain == CHAIN_ID_SOLANA {
-                    Self::process_transfer_native_out(program_id, accounts, &p)
+                    Self::process_transfer_native_out(program_id, accounts, &p, false)
+                } else {
+                    Self::process_transfer_out(program_id, accounts, &p, false)
+                }
+            }
+            TransferOutCPI(p) => {
+                msg!("Instruction: TransferOutCPI");
+
+                if p.asset.chain == CHAIN_ID_SOLANA {
+                    Self::process_transfer_native_out(program_id, accounts, &p, true)
                 } else {
-                    Self::process_transfer_out(program_id, accounts, &p)
+                    Self::process_transfer_out(program_id, accounts, &p, true)
                 }
             }
             PostVAA(vaa_body) => {
@@ -98,7 +117,27 @@ impl Bridge {
                 msg!("Instruction: CreateWrapped");
                 Self::process_create_wrapped(program_id, accounts, &meta)
             }
-            _ => panic!(""),
+            CloseGuardianSet() => {
+                msg!("Instruction: CloseGuardianSet");
+                Self::process_close_guardian_set(program_id, accounts)
+            }
+            EvictSignatures() => {
+                msg!("Instruction: EvictSignatures");
+                Self::process_evict_signatures(program_id, accounts)
+            }
+            Migrate(p) => {
+                msg!("Instruction: Migrate");
+                Self::process_migrate(program_id, accounts, &p)
+            }
+            ClaimReimbursement() => {
+                msg!("Instruction: ClaimReimbursement");
+                Self::process_claim_reimbursement(program_id, accounts)
+            }
+            TransferOutBatch(p) => {
+                msg!("Instruction: TransferOutBatch");
+                Self::process_transfer_out_batch(program_id, accounts, &p)
+            }
+            _ => Err(ProgramError::InvalidInstructionData),
         }
     }
 
@@ -121,7 +160,7 @@ impl Bridge {
 
         // Create bridge account
         let bridge_seed = Bridge::derive_bridge_seeds();
-        Bridge::check_and_create_account::<Bridge>(
+        let bridge_bump = Bridge::check_and_create_account::<Bridge>(
             program_id,
             accounts,
             new_bridge_info.key,
@@ -139,7 +178,7 @@ impl Bridge {
 
         // Create guardian set account
         let guardian_seed = Bridge::derive_guardian_set_seeds(new_bridge_info.key, 0);
-        Bridge::check_and_create_account::<GuardianSet>(
+        let guardian_bump = Bridge::check_and_create_account::<GuardianSet>(
             program_id,
             accounts,
             new_guardian_info.key,
@@ -163,6 +202,7 @@ impl Bridge {
         bridge.is_initialized = true;
         bridge.guardian_set_index = 0;
         bridge.config = config;
+        bridge.bump = bridge_bump;
 
         // Initialize the initial guardian set
         guardian_info.is_initialized = true;
@@ -170,6 +210,7 @@ impl Bridge {
         guardian_info.creation_time = clock.unix_timestamp.as_();
         guardian_info.keys = initial_guardian_key;
         guardian_info.len_keys = len_guardians;
+        guardian_info.bump = guardian_bump;
 
         Ok(())
     }
@@ -184,6 +225,9 @@ impl Bridge {
         if proposal.vaa_time != 0 {
             return Err(Error::VAAAlreadySubmitted.into());
         }
+        if proposal.reverted {
+            return Err(Error::TransferReverted.into());
+        }
 
         // Increase poke counter
         proposal.poke_counter += 1;
@@ -191,6 +235,70 @@ impl Bridge {
         Ok(())
     }
 
+    /// Closes an expired guardian set and refunds its rent to the bridge
+    pub fn process_close_guardian_set(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let clock_info = next_account_info(account_info_iter)?;
+        let bridge_info = Self::next_bridge_info(account_info_iter, program_id)?;
+        let guardian_set_info = Self::next_account_info_with_owner(account_info_iter, program_id)?;
+
+        let clock = Clock::from_account_info(clock_info)?;
+        {
+            let guardian_data = guardian_set_info.try_borrow_data()?;
+            let guardian_set: &GuardianSet = Self::unpack_immutable(&guardian_data)?;
+            Bridge::check_derived_key(
+                program_id,
+                guardian_set_info.key,
+                &Bridge::derive_guardian_set_seeds(bridge_info.key, guardian_set.index),
+                guardian_set.bump,
+            )?;
+
+            // The active guardian set never expires
+            if guardian_set.expiration_time == 0
+                || (guardian_set.expiration_time as i64 + Self::GUARDIAN_SET_CLOSE_DELAY as i64)
+                    >= clock.unix_timestamp
+            {
+                return Err(Error::AccountNotExpired.into());
+            }
+        }
+
+        Self::close_account(guardian_set_info, bridge_info)
+    }
+
+    /// Evicts a stale signature state and refunds its rent to the bridge
+    pub fn process_evict_signatures(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let clock_info = next_account_info(account_info_iter)?;
+        let bridge_info = Self::next_bridge_info(account_info_iter, program_id)?;
+        let sig_info = Self::next_account_info_with_owner(account_info_iter, program_id)?;
+
+        let clock = Clock::from_account_info(clock_info)?;
+        {
+            let sig_data = sig_info.try_borrow_data()?;
+            let sig_state: &SignatureState = Self::unpack_immutable(&sig_data)?;
+            Bridge::check_derived_key(
+                program_id,
+                sig_info.key,
+                &Bridge::derive_signature_seeds(
+                    bridge_info.key,
+                    &sig_state.hash,
+                    sig_state.guardian_set_index,
+                ),
+                sig_state.bump,
+            )?;
+
+            // States created before the creation time was recorded have no known age
+            if sig_state.creation_time == 0
+                || (sig_state.creation_time as i64 + Self::SIGNATURE_EVICTION_TIME as i64)
+                    >= clock.unix_timestamp
+            {
+                return Err(Error::AccountNotExpired.into());
+            }
+        }
+
+        Self::close_account(sig_info, bridge_info)
+    }
+
     /// Processes signature verifications
     pub fn process_verify_signatures(
         program_id: &Pubkey,
@@ -201,10 +309,11 @@ impl Bridge {
         next_account_info(account_info_iter)?; // Bridge program
         next_account_info(account_info_iter)?; // System program
         let instruction_accounts = next_account_info(account_info_iter)?;
-        let bridge_info = Self::next_account_info_with_owner(account_info_iter, program_id)?;
+        let bridge_info = Self::next_bridge_info(account_info_iter, program_id)?;
         let sig_info = next_account_info(account_info_iter)?;
         let guardian_set_info = Self::next_account_info_with_owner(account_info_iter, program_id)?;
         let payer_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
 
         if *instruction_accounts.key != solana_program::sysvar::instructions::id() {
             return Err(Error::InvalidSysvar.into());
@@ -229,82 +338,75 @@ impl Bridge {
             })
             .collect();
 
-        let current_instruction = solana_program::sysvar::instructions::load_current_index(
-            &instruction_accounts.try_borrow_mut_data()?,
-        );
-        if current_instruction == 0 {
-            return Err(ProgramError::InvalidInstructionData);
-        }
-
-        // The previous ix must be a secp verification instruction
-        let secp_ix_index = (current_instruction - 1) as u8;
-        let secp_ix = solana_program::sysvar::instructions::load_instruction_at(
-            secp_ix_index as usize,
-            &instruction_accounts.try_borrow_mut_data()?,
-        )
-            .map_err(|_| ProgramError::InvalidAccountData)?;
-
-        // Check that the instruction is actually for the secp program
+        // The referenced ix must be a secp verification instruction
+        let secp_ix = Self::load_instruction(instruction_accounts, payload.secp_instruction)?;
         if secp_ix.program_id != solana_program::secp256k1_program::id() {
             return Err(ProgramError::InvalidArgument);
         }
 
-        let secp_data_len = secp_ix.data.len();
-        if secp_data_len < 2 {
-            return Err(ProgramError::InvalidAccountData);
+        // The secp program has already verified all signatures of the instruction against the
+        // data referenced by its offsets, which may point into any instruction of the transaction
+        let sig_len = *secp_ix.data.get(0).ok_or(ProgramError::InvalidInstructionData)?;
+        if secp_ix.data.len() < 1 + sig_len as usize * SECP_OFFSETS_SIZE {
+            return Err(ProgramError::InvalidInstructionData);
         }
 
-        let sig_len = secp_ix.data[0];
-        let mut index = 1;
-
+        let mut referenced_ixs: Vec<(u8, Instruction)> = vec![(payload.secp_instruction, secp_ix)];
         let mut secp_ixs: Vec<SecpInstructionPart> = Vec::with_capacity(sig_len as usize);
-        for i in 0..sig_len {
-            let sig_offset = byteorder::LE::read_u16(&secp_ix.data[index..index + 2]) as usize;
-            index += 2;
-            let sig_ix = secp_ix.data[index];
-            index += 1;
-            let address_offset = byteorder::LE::read_u16(&secp_ix.data[index..index + 2]) as usize;
-            index += 2;
-            let address_ix = secp_ix.data[index];
-            index += 1;
-            let msg_offset = byteorder::LE::read_u16(&secp_ix.data[index..index + 2]);
-            index += 2;
-            let msg_size = byteorder::LE::read_u16(&secp_ix.data[index..index + 2]);
-            index += 2;
-            let msg_ix = secp_ix.data[index];
-            index += 1;
-
-            if address_ix != secp_ix_index || msg_ix != secp_ix_index || sig_ix != secp_ix_index {
-                return Err(ProgramError::InvalidArgument);
-            }
-
-            let address: &[u8] = &secp_ix.data[address_offset..address_offset + 20];
-            let signature: &[u8] = &secp_ix.data[sig_offset..sig_offset + 65];
+        let mut message: Vec<u8> = Vec::new();
+        for i in 0..sig_len as usize {
+            let offsets = &referenced_ixs[0].1.data
+                [1 + i * SECP_OFFSETS_SIZE..1 + (i + 1) * SECP_OFFSETS_SIZE];
+            let sig_offset = byteorder::LE::read_u16(&offsets[0..2]) as usize;
+            let sig_ix = offsets[2];
+            let address_offset = byteorder::LE::read_u16(&offsets[3..5]) as usize;
+            let address_ix = offsets[5];
+            let msg_offset = byteorder::LE::read_u16(&offsets[6..8]) as usize;
+            let msg_size = byteorder::LE::read_u16(&offsets[8..10]) as usize;
+            let msg_ix = offsets[10];
+
+            let mut part = SecpInstructionPart {
+                address: [0; 20],
+                signature: [0; 65],
+            };
+            part.address.copy_from_slice(Self::referenced_data(
+                instruction_accounts,
+                &mut referenced_ixs,
+                address_ix,
+                address_offset,
+                20,
+            )?);
+            part.signature.copy_from_slice(Self::referenced_data(
+                instruction_accounts,
+                &mut referenced_ixs,
+                sig_ix,
+                sig_offset,
+                65,
+            )?);
+            let msg = Self::referenced_data(
+                instruction_accounts,
+                &mut referenced_ixs,
+                msg_ix,
+                msg_offset,
+                msg_size,
+            )?;
 
             // Make sure that all messages are equal
-            if i > 0 {
-                if msg_offset != secp_ixs[0].msg_offset || msg_size != secp_ixs[0].msg_size {
-                    return Err(ProgramError::InvalidArgument);
-                }
+            if i == 0 {
+                message = msg.to_vec();
+            } else if msg != message.as_slice() {
+                return Err(ProgramError::InvalidArgument);
             }
-            secp_ixs.push(SecpInstructionPart {
-                address,
-                signature,
-                msg_offset,
-                msg_size,
-            });
+            secp_ixs.push(part);
         }
 
-        if sig_infos.len() != secp_ixs.len() {
+        if secp_ixs.is_empty() || sig_infos.len() != secp_ixs.len() {
             return Err(ProgramError::InvalidArgument);
         }
 
         // Check message
-        let message = &secp_ix.data[secp_ixs[0].msg_offset as usize
-            ..(secp_ixs[0].msg_offset + secp_ixs[0].msg_size) as usize];
-
         let mut h = sha3::Keccak256::default();
-        if let Err(_) = h.write(message) {
+        if let Err(_) = h.write(&message) {
             return Err(ProgramError::InvalidArgument);
         };
         let msg_hash: [u8; 32] = h.finalize().into();
@@ -312,11 +414,11 @@ impl Bridge {
             return Err(ProgramError::InvalidArgument);
         }
 
+        let mut sig_bump = 0;
         if sig_info.data_is_empty() {
-            let bridge_key = Bridge::derive_bridge_id(program_id)?;
             let sig_seeds =
-                Bridge::derive_signature_seeds(&bridge_key, &msg_hash, guardian_set.index);
-            Bridge::check_and_create_account::<SignatureState>(
+                Bridge::derive_signature_seeds(bridge_info.key, &msg_hash, guardian_set.index);
+            sig_bump = Bridge::check_and_create_account::<SignatureState>(
                 program_id,
                 accounts,
                 sig_info.key,
@@ -343,15 +445,17 @@ impl Bridge {
             sig_state.is_initialized = true;
             sig_state.guardian_set_index = guardian_set.index;
             sig_state.hash = payload.hash;
+            sig_state.bump = sig_bump;
+            sig_state.creation_time = Clock::from_account_info(clock_info)?.unix_timestamp as u32;
         }
 
         // Check addresses
         for s in sig_infos {
-            if s.signer_index > guardian_set.len_keys {
+            if s.signer_index >= guardian_set.len_keys {
                 return Err(ProgramError::InvalidArgument);
             }
 
-            if s.sig_index + 1 > sig_len {
+            if s.sig_index >= sig_len {
                 return Err(ProgramError::InvalidArgument);
             }
 
@@ -362,17 +466,56 @@ impl Bridge {
             }
 
             sig_state.signatures[s.signer_index as usize]
-                .copy_from_slice(secp_ixs[s.sig_index as usize].signature);
+                .copy_from_slice(&secp_ixs[s.sig_index as usize].signature);
         }
 
         Ok(())
     }
 
-    /// Transfers a wrapped asset out
+    /// Loads the instruction at `index` of the current transaction
+    fn load_instruction(
+        instructions_info: &AccountInfo,
+        index: u8,
+    ) -> Result<Instruction, ProgramError> {
+        solana_program::sysvar::instructions::load_instruction_at(
+            index as usize,
+            &instructions_info.try_borrow_data()?,
+        )
+            .map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// Returns `size` bytes at `offset` of the data of the instruction at `index`. Instructions are
+    /// loaded once and kept in `loaded`.
+    fn referenced_data<'a>(
+        instructions_info: &AccountInfo,
+        loaded: &'a mut Vec<(u8, Instruction)>,
+        index: u8,
+        offset: usize,
+        size: usize,
+    ) -> Result<&'a [u8], ProgramError> {
+        let position = match loaded.iter().position(|(i, _)| *i == index) {
+            Some(p) => p,
+            None => {
+                loaded.push((index, Self::load_instruction(instructions_info, index)?));
+                loaded.len() - 1
+            }
+        };
+
+        loaded[position]
+            .1
+            .data
+            .get(offset..offset + size)
+            .ok_or(ProgramError::InvalidInstructionData)
+    }
+
+    /// Transfers a wrapped asset out.
+    /// If `cpi` is set the tokens are burned using a signing authority, passed as the last account,
+    /// instead of a delegation to the bridge.
     pub fn process_transfer_out(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         t: &TransferOutPayload,
+        cpi: bool,
     ) -> ProgramResult {
         msg!("wrapped transfer out");
         let account_info_iter = &mut accounts.iter();
@@ -383,42 +526,101 @@ impl Bridge {
         let clock_info = next_account_info(account_info_iter)?;
         let instructions_info = next_account_info(account_info_iter)?;
         let sender_account_info = next_account_info(account_info_iter)?;
-        let bridge_info = Self::next_account_info_with_owner(account_info_iter, program_id)?;
+        let bridge_info = Self::next_bridge_info(account_info_iter, program_id)?;
         let transfer_info = next_account_info(account_info_iter)?;
         let mint_info = next_account_info(account_info_iter)?;
         let payer_info = next_account_info(account_info_iter)?;
+        let sequence_info = next_account_info(account_info_iter)?;
+        let wrapped_meta_info = Self::next_account_info_with_owner(account_info_iter, program_id)?;
+        // Transfers invoked via CPI are signed by the token authority instead of a delegation
+        let authority_info = if cpi {
+            Some(next_account_info(account_info_iter)?)
+        } else {
+            None
+        };
+
+        // Fee handling
+        let fee = Self::transfer_fee();
+        Self::charge_transfer_fee(
+            accounts,
+            instructions_info,
+            bridge_info,
+            payer_info,
+            fee,
+            authority_info,
+        )?;
+
+        let clock = Clock::from_account_info(clock_info)?;
+        let authority = authority_info.map_or(*bridge_info.key, |a| *a.key);
+        Self::burn_wrapped_asset(
+            program_id,
+            accounts,
+            &clock,
+            bridge_info,
+            payer_info,
+            sender_account_info,
+            transfer_info,
+            mint_info,
+            sequence_info,
+            wrapped_meta_info,
+            &authority,
+            t,
+        )
+    }
+
+    /// Burns a wrapped asset of `sender_account_info` signed by `authority` and records the
+    /// transfer in a new proposal.
+    pub fn burn_wrapped_asset(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        clock: &Clock,
+        bridge_info: &AccountInfo,
+        payer_info: &AccountInfo,
+        sender_account_info: &AccountInfo,
+        transfer_info: &AccountInfo,
+        mint_info: &AccountInfo,
+        sequence_info: &AccountInfo,
+        wrapped_meta_info: &AccountInfo,
+        authority: &Pubkey,
+        t: &TransferOutPayload,
+    ) -> ProgramResult {
+        // Reject targets that cannot receive the funds on the foreign chain
+        check_foreign_address(t.chain_id, &t.target)?;
 
         let sender = Bridge::token_account_deserialize(sender_account_info)?;
         let bridge_data = bridge_info.try_borrow_data()?;
         let bridge: &Bridge = Self::unpack_immutable(&bridge_data)?;
         let mint = Bridge::mint_deserialize(mint_info)?;
-        let clock = Clock::from_account_info(clock_info)?;
-
-        if *instructions_info.key != solana_program::sysvar::instructions::id() {
-            return Err(Error::InvalidSysvar.into());
-        }
-
-        // Fee handling
-        let fee = Self::transfer_fee();
-        Self::check_fees(instructions_info, bridge_info, fee)?;
+        let wrapped_meta_data = wrapped_meta_info.try_borrow_data()?;
+        let wrapped_meta: &WrappedAssetMeta = Self::unpack_immutable(&wrapped_meta_data)?;
 
         // Does the token belong to the mint
         if sender.mint != *mint_info.key {
             return Err(Error::TokenMintMismatch.into());
         }
 
-        // Check that the mint is actually a wrapped asset belonging to *this* bridge instance
-        let expected_mint_address = Bridge::derive_wrapped_asset_id(
+        // Check that the mint is actually a wrapped asset belonging to *this* bridge instance.
+        // The meta account is derived from the mint and records the asset the mint was created for.
+        Bridge::check_derived_key(
             program_id,
-            bridge_info.key,
-            t.asset.chain,
-            t.asset.decimals,
-            t.asset.address,
+            wrapped_meta_info.key,
+            &Bridge::derive_wrapped_meta_seeds(bridge_info.key, mint_info.key),
+            wrapped_meta.bump,
         )?;
-        if expected_mint_address != *mint_info.key {
-            return Err(Error::InvalidDerivedAccount.into());
+        if wrapped_meta.chain != t.asset.chain || wrapped_meta.address != t.asset.address {
+            return Err(Error::TokenMintMismatch.into());
         }
 
+        // Assign the next sequence number of the sender to this transfer
+        let sequence = Self::take_transfer_sequence(
+            program_id,
+            accounts,
+            bridge_info,
+            sequence_info,
+            sender_account_info.key,
+            payer_info,
+        )?;
+
         // Create transfer account
         let transfer_seed = Bridge::derive_transfer_id_seeds(
             bridge_info.key,
@@ -427,9 +629,9 @@ impl Bridge {
             t.chain_id,
             t.target,
             sender_account_info.key.to_bytes(),
-            t.nonce,
+            sequence,
         );
-        Bridge::check_and_create_account::<TransferOutProposal>(
+        let transfer_bump = Bridge::check_and_create_account::<TransferOutProposal>(
             program_id,
             accounts,
             transfer_info.key,
@@ -447,15 +649,17 @@ impl Bridge {
         Bridge::wrapped_burn(
             program_id,
             accounts,
-            &bridge.config.token_program,
+            bridge,
             sender_account_info.key,
             mint_info.key,
+            authority,
             t.amount,
         )?;
 
         // Initialize transfer
         transfer.is_initialized = true;
-        transfer.nonce = t.nonce;
+        transfer.bump = transfer_bump;
+        transfer.sequence = sequence;
         transfer.source_address = sender_account_info.key.to_bytes();
         transfer.foreign_address = t.target;
         transfer.amount = t.amount;
@@ -464,19 +668,22 @@ impl Bridge {
 
         // Make sure decimals are correct
         transfer.asset = AssetMeta {
-            chain: t.asset.chain, // Chain and address cannot be spoofed because the account is derived from it
-            address: t.asset.address,
+            chain: wrapped_meta.chain, // Chain and address cannot be spoofed because the meta is derived from the mint
+            address: wrapped_meta.address,
             decimals: mint.decimals, // We use the info from mint because it can be spoofed
         };
 
         Ok(())
     }
 
-    /// Transfers a native token to a foreign chain
+    /// Transfers a native token to a foreign chain.
+    /// If `cpi` is set the tokens are transferred using a signing authority, passed as the last
+    /// account, instead of a delegation to the bridge.
     pub fn process_transfer_native_out(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         t: &TransferOutPayload,
+        cpi: bool,
     ) -> ProgramResult {
         msg!("native transfer out");
         let account_info_iter = &mut accounts.iter();
@@ -487,26 +694,87 @@ impl Bridge {
         let clock_info = next_account_info(account_info_iter)?;
         let instructions_info = next_account_info(account_info_iter)?;
         let sender_account_info = next_account_info(account_info_iter)?;
-        let bridge_info = Self::next_account_info_with_owner(account_info_iter, program_id)?;
+        let bridge_info = Self::next_bridge_info(account_info_iter, program_id)?;
         let transfer_info = next_account_info(account_info_iter)?;
         let mint_info = next_account_info(account_info_iter)?;
         let payer_info = next_account_info(account_info_iter)?;
+        let sequence_info = next_account_info(account_info_iter)?;
         let custody_info = next_account_info(account_info_iter)?;
+        // Transfers invoked via CPI are signed by the token authority instead of a delegation
+        let authority_info = if cpi {
+            Some(next_account_info(account_info_iter)?)
+        } else {
+            None
+        };
+
+        // Fee handling
+        let fee = Self::transfer_fee();
+        Self::charge_transfer_fee(
+            accounts,
+            instructions_info,
+            bridge_info,
+            payer_info,
+            fee,
+            authority_info,
+        )?;
+
+        let clock = Clock::from_account_info(clock_info)?;
+        let authority = authority_info.map_or(*bridge_info.key, |a| *a.key);
+        Self::lock_native_asset(
+            program_id,
+            accounts,
+            &clock,
+            bridge_info,
+            payer_info,
+            sender_account_info,
+            transfer_info,
+            mint_info,
+            sequence_info,
+            custody_info,
+            &authority,
+            t,
+        )
+    }
+
+    /// Locks a native asset of `sender_account_info` in custody, signed by `authority`, and
+    /// records the transfer in a new proposal. The custody account is created if it does not exist.
+    pub fn lock_native_asset(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        clock: &Clock,
+        bridge_info: &AccountInfo,
+        payer_info: &AccountInfo,
+        sender_account_info: &AccountInfo,
+        transfer_info: &AccountInfo,
+        mint_info: &AccountInfo,
+        sequence_info: &AccountInfo,
+        custody_info: &AccountInfo,
+        authority: &Pubkey,
+        t: &TransferOutPayload,
+    ) -> ProgramResult {
+        // Reject targets that cannot receive the funds on the foreign chain
+        check_foreign_address(t.chain_id, &t.target)?;
 
         let sender = Bridge::token_account_deserialize(sender_account_info)?;
         let mint = Bridge::mint_deserialize(mint_info)?;
         let bridge_data = bridge_info.try_borrow_data()?;
         let bridge: &Bridge = Self::unpack_immutable(&bridge_data)?;
-        let clock = Clock::from_account_info(clock_info)?;
-
-        let fee = Self::transfer_fee();
-        Self::check_fees(instructions_info, bridge_info, fee)?;
 
         // Does the token belong to the mint
         if sender.mint != *mint_info.key {
             return Err(Error::TokenMintMismatch.into());
         }
 
+        // Assign the next sequence number of the sender to this transfer
+        let sequence = Self::take_transfer_sequence(
+            program_id,
+            accounts,
+            bridge_info,
+            sequence_info,
+            sender_account_info.key,
+            payer_info,
+        )?;
+
         // Create transfer account
         let transfer_seed = Bridge::derive_transfer_id_seeds(
             bridge_info.key,
@@ -515,9 +783,9 @@ impl Bridge {
             t.chain_id,
             t.target,
             sender_account_info.key.to_bytes(),
-            t.nonce,
+            sequence,
         );
-        Bridge::check_and_create_account::<TransferOutProposal>(
+        let transfer_bump = Bridge::check_and_create_account::<TransferOutProposal>(
             program_id,
             accounts,
             transfer_info.key,
@@ -531,12 +799,14 @@ impl Bridge {
         let mut transfer_data = transfer_info.try_borrow_mut_data()?;
         let mut transfer: &mut TransferOutProposal = Self::unpack_unchecked(&mut transfer_data)?;
 
-        // Check that custody account was derived correctly
-        let expected_custody_id =
-            Bridge::derive_custody_id(program_id, bridge_info.key, mint_info.key)?;
-        if expected_custody_id != *custody_info.key {
-            return Err(Error::InvalidDerivedAccount.into());
-        }
+        // Check that custody account was derived correctly. A new custody account is created at
+        // the canonical address, so a non-canonical bump seed fails the creation.
+        Bridge::check_derived_key(
+            program_id,
+            custody_info.key,
+            &Bridge::derive_custody_seeds(bridge_info.key, mint_info.key),
+            t.custody_bump,
+        )?;
 
         // Create the account if it does not exist
         if custody_info.data_is_empty() {
@@ -552,11 +822,9 @@ impl Bridge {
             )?;
         }
 
-        let bridge_authority = Self::derive_bridge_id(program_id)?;
-
         // Check that the custody token account is owned by the derived key
         let custody = Self::token_account_deserialize(custody_info)?;
-        if custody.owner != bridge_authority {
+        if custody.owner != *bridge_info.key {
             return Err(Error::WrongTokenAccountOwner.into());
         }
 
@@ -570,20 +838,21 @@ impl Bridge {
         Bridge::token_transfer_caller(
             program_id,
             accounts,
-            &bridge.config.token_program,
+            bridge,
             sender_account_info.key,
             custody_info.key,
-            &bridge_authority,
+            authority,
             t.amount,
         )?;
 
         // Initialize proposal
         transfer.is_initialized = true;
+        transfer.bump = transfer_bump;
         transfer.amount = t.amount;
         transfer.to_chain_id = t.chain_id;
         transfer.source_address = sender_account_info.key.to_bytes();
         transfer.foreign_address = t.target;
-        transfer.nonce = t.nonce;
+        transfer.sequence = sequence;
         transfer.lockup_time = clock.unix_timestamp as u32;
 
         // Don't use the user-given data as we don't check mint = AssetMeta.address
@@ -596,6 +865,155 @@ impl Bridge {
         Ok(())
     }
 
+    /// Transfers several assets to the same foreign target, paying the fees of all transfers at
+    /// once.
+    pub fn process_transfer_out_batch(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        b: &TransferOutBatchPayload,
+    ) -> ProgramResult {
+        msg!("batched transfer out");
+        let account_info_iter = &mut accounts.iter();
+        next_account_info(account_info_iter)?; // Bridge program
+        next_account_info(account_info_iter)?; // System program
+        next_account_info(account_info_iter)?; // Token program
+        next_account_info(account_info_iter)?; // Rent sysvar
+        let clock_info = next_account_info(account_info_iter)?;
+        let instructions_info = next_account_info(account_info_iter)?;
+        let bridge_info = Self::next_bridge_info(account_info_iter, program_id)?;
+        let payer_info = next_account_info(account_info_iter)?;
+
+        // Fee handling, a single transfer covers the fees of the whole batch
+        let fee = Self::transfer_fee()
+            .checked_mul(b.transfers.len() as u64)
+            .ok_or(ProgramError::InvalidArgument)?;
+        Self::charge_transfer_fee(accounts, instructions_info, bridge_info, payer_info, fee, None)?;
+
+        let clock = Clock::from_account_info(clock_info)?;
+        for i in 0..b.transfers.len() {
+            let t = b.transfer(i);
+            let sender_account_info = next_account_info(account_info_iter)?;
+            let transfer_info = next_account_info(account_info_iter)?;
+            let mint_info = next_account_info(account_info_iter)?;
+            let sequence_info = next_account_info(account_info_iter)?;
+
+            if t.asset.chain == CHAIN_ID_SOLANA {
+                let custody_info = next_account_info(account_info_iter)?;
+                Self::lock_native_asset(
+                    program_id,
+                    accounts,
+                    &clock,
+                    bridge_info,
+                    payer_info,
+                    sender_account_info,
+                    transfer_info,
+                    mint_info,
+                    sequence_info,
+                    custody_info,
+                    bridge_info.key,
+                    &t,
+                )?;
+            } else {
+                let wrapped_meta_info =
+                    Self::next_account_info_with_owner(account_info_iter, program_id)?;
+                Self::burn_wrapped_asset(
+                    program_id,
+                    accounts,
+                    &clock,
+                    bridge_info,
+                    payer_info,
+                    sender_account_info,
+                    transfer_info,
+                    mint_info,
+                    sequence_info,
+                    wrapped_meta_info,
+                    bridge_info.key,
+                    &t,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the next sequence number of `sender` and increments its counter.
+    /// The sequence account is created on the first transfer of a sender.
+    pub fn take_transfer_sequence(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        bridge_info: &AccountInfo,
+        sequence_info: &AccountInfo,
+        sender: &Pubkey,
+        payer_info: &AccountInfo,
+    ) -> Result<u32, ProgramError> {
+        let sequence_seeds = Bridge::derive_sequence_seeds(bridge_info.key, sender);
+        let mut sequence_bump = None;
+        if sequence_info.data_is_empty() {
+            sequence_bump = Some(Bridge::check_and_create_account::<TransferSequence>(
+                program_id,
+                accounts,
+                sequence_info.key,
+                payer_info,
+                program_id,
+                &sequence_seeds,
+                None,
+            )?);
+        } else if sequence_info.owner != program_id {
+            return Err(Error::InvalidOwner.into());
+        }
+
+        let mut sequence_data = sequence_info.try_borrow_mut_data()?;
+        let sequence_state: &mut TransferSequence = Self::unpack_unchecked(&mut sequence_data)?;
+        match sequence_bump {
+            Some(bump) => {
+                sequence_state.is_initialized = true;
+                sequence_state.bump = bump;
+            }
+            None => Bridge::check_derived_key(
+                program_id,
+                sequence_info.key,
+                &sequence_seeds,
+                sequence_state.bump,
+            )?,
+        }
+        let sequence = sequence_state.sequence;
+
+        sequence_state.sequence = sequence
+            .checked_add(1)
+            .ok_or(ProgramError::InvalidArgument)?;
+
+        Ok(sequence)
+    }
+
+    /// Charges the fee of a transfer out.
+    /// Transfers invoked via CPI pay the fee from the payer account and must be signed by the
+    /// token `authority_info`. All other transfers must send the fee to the bridge in the
+    /// instruction preceding the transfer, which is checked using `instructions_info`.
+    pub fn charge_transfer_fee(
+        accounts: &[AccountInfo],
+        instructions_info: &AccountInfo,
+        bridge_info: &AccountInfo,
+        payer_info: &AccountInfo,
+        fee: u64,
+        authority_info: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        let authority_info = match authority_info {
+            Some(a) => a,
+            None => {
+                if *instructions_info.key != solana_program::sysvar::instructions::id() {
+                    return Err(Error::InvalidSysvar.into());
+                }
+                return Self::check_fees(instructions_info, bridge_info, fee);
+            }
+        };
+        if !authority_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let ix = system_instruction::transfer(payer_info.key, bridge_info.key, fee);
+        invoke(&ix, accounts)
+    }
+
     /// Verify that a certain fee was sent to the bridge in the preceding instruction
     pub fn check_fees(instructions_info: &AccountInfo, bridge_info: &AccountInfo, fee: u64) -> Result<(), ProgramError> {
         let current_instruction = solana_program::sysvar::instructions::load_current_index(
@@ -667,6 +1085,16 @@ impl Bridge {
         Ok(())
     }
 
+    /// Closes a program account by moving all of its lamports to `recipient` and clearing its data
+    pub fn close_account(account: &AccountInfo, recipient: &AccountInfo) -> ProgramResult {
+        Self::transfer_sol(account, recipient, account.lamports())?;
+        for b in account.try_borrow_mut_data()?.iter_mut() {
+            *b = 0;
+        }
+
+        Ok(())
+    }
+
     /// Processes a VAA
     pub fn process_vaa(
         program_id: &Pubkey,
@@ -681,22 +1109,43 @@ impl Bridge {
         next_account_info(account_info_iter)?; // System program
         next_account_info(account_info_iter)?; // Rent sysvar
         let clock_info = next_account_info(account_info_iter)?;
-        let bridge_info = Self::next_account_info_with_owner(account_info_iter, program_id)?;
+        let bridge_info = Self::next_bridge_info(account_info_iter, program_id)?;
         let guardian_set_info = Self::next_account_info_with_owner(account_info_iter, program_id)?;
         let claim_info = next_account_info(account_info_iter)?;
         let sig_info = Self::next_account_info_with_owner(account_info_iter, program_id)?;
         let payer_info = next_account_info(account_info_iter)?;
+        let ledger_info = next_account_info(account_info_iter)?;
+
+        // Balance of the payer before it advances rent for the accounts created by this VAA
+        let payer_balance = payer_info.lamports();
+
+        // Create the ledger of the guardian on its first submission
+        let ledger_seeds = Bridge::derive_ledger_seeds(bridge_info.key, payer_info.key);
+        let ledger_bump = if ledger_info.data_is_empty() {
+            Some(Bridge::check_and_create_account::<GuardianLedger>(
+                program_id,
+                accounts,
+                ledger_info.key,
+                payer_info,
+                program_id,
+                &ledger_seeds,
+                Some(bridge_info),
+            )?)
+        } else {
+            None
+        };
 
         let clock = Clock::from_account_info(clock_info)?;
         let mut guardian_data = guardian_set_info.try_borrow_mut_data()?;
         let guardian_set: &mut GuardianSet = Bridge::unpack(&mut guardian_data)?;
 
         // Check that the guardian set is valid
-        let expected_guardian_set =
-            Bridge::derive_guardian_set_id(program_id, bridge_info.key, vaa.guardian_set_index)?;
-        if expected_guardian_set != *guardian_set_info.key {
-            return Err(Error::InvalidDerivedAccount.into());
-        }
+        Bridge::check_derived_key(
+            program_id,
+            guardian_set_info.key,
+            &Bridge::derive_guardian_set_seeds(bridge_info.key, vaa.guardian_set_index),
+            guardian_set.bump,
+        )?;
 
         // Check that the guardian set is still active
         if guardian_set.expiration_time != 0 && (guardian_set.expiration_time as i64) < clock.unix_timestamp {
@@ -717,11 +1166,11 @@ impl Bridge {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        let signature_count = (sig_state
+        let signature_count = sig_state
             .signatures
             .iter()
             .filter(|v| v.iter().filter(|v| **v != 0).count() != 0)
-            .count() as u8);
+            .count() as u8;
         // Check quorum
         // We're using a fixed point number transformation with 1 decimal to deal with rounding.
         // The cast to u16 exists to prevent issues where len_keys * 10 might overflow.
@@ -730,6 +1179,8 @@ impl Bridge {
         }
 
         let mut evict_signatures = false;
+        // Rent the payer paid for accounts owned by the recipient of a transfer
+        let mut recipient_rent = 0;
         let payload = vaa.payload.as_ref().ok_or(Error::InvalidVAAAction)?;
         match payload {
             VAABody::UpdateGuardianSet(v) => {
@@ -752,8 +1203,10 @@ impl Bridge {
                 if v.source_chain == CHAIN_ID_SOLANA {
                     Self::process_vaa_transfer_post(
                         program_id,
+                        accounts,
                         account_info_iter,
                         bridge_info,
+                        payer_info,
                         vaa,
                         &v,
                         vaa_data,
@@ -763,14 +1216,16 @@ impl Bridge {
                     let bridge_data = bridge_info.try_borrow_data()?;
                     let bridge: &Bridge = Self::unpack_immutable(&bridge_data)?;
                     evict_signatures = true;
-                    Self::process_vaa_transfer(
+                    recipient_rent = Self::process_vaa_transfer(
                         program_id,
                         accounts,
                         account_info_iter,
                         bridge_info,
+                        payer_info,
                         bridge,
                         &v,
-                    )
+                    )?;
+                    Ok(())
                 }
             }
             VAABody::UpgradeContract(v) => {
@@ -779,37 +1234,141 @@ impl Bridge {
                     Self::process_vaa_upgrade(
                         program_id,
                         accounts,
+                        account_info_iter,
+                        &clock,
                         bridge_info,
+                        hash,
                         v,
                     )
                 } else {
                     return Err(Error::InvalidChain.into());
                 }
             }
-        }?;
-
-        // Check and create claim
-        let claim_seeds = Bridge::derive_claim_seeds(bridge_info.key, vaa.signature_body()?);
-        Bridge::check_and_create_account::<ClaimedVAA>(
-            program_id,
-            accounts,
-            claim_info.key,
-            payer_info,
-            program_id,
-            &claim_seeds,
-            Some(bridge_info),
-        )?;
-
-        // If the signatures are not needed anymore, evict them and reclaim rent.
+            VAABody::TreasuryWithdrawal(v) => {
+                if v.chain_id == CHAIN_ID_SOLANA {
+                    let bridge_data = bridge_info.try_borrow_data()?;
+                    let bridge: &Bridge = Self::unpack_immutable(&bridge_data)?;
+                    evict_signatures = true;
+                    Self::process_vaa_treasury_withdrawal(
+                        account_info_iter,
+                        bridge_info,
+                        bridge,
+                        guardian_set,
+                        v,
+                    )
+                } else {
+                    return Err(Error::InvalidChain.into());
+                }
+            }
+            VAABody::FreezeAccount(v) => {
+                if v.chain_id == CHAIN_ID_SOLANA {
+                    let bridge_data = bridge_info.try_borrow_data()?;
+                    let bridge: &Bridge = Self::unpack_immutable(&bridge_data)?;
+                    evict_signatures = true;
+                    Self::process_vaa_freeze_account(
+                        program_id,
+                        accounts,
+                        account_info_iter,
+                        bridge_info,
+                        bridge,
+                        guardian_set,
+                        v,
+                    )
+                } else {
+                    return Err(Error::InvalidChain.into());
+                }
+            }
+            VAABody::HaltMinting(v) => {
+                if v.chain_id == CHAIN_ID_SOLANA {
+                    let bridge_data = bridge_info.try_borrow_data()?;
+                    let bridge: &Bridge = Self::unpack_immutable(&bridge_data)?;
+                    evict_signatures = true;
+                    Self::process_vaa_halt_minting(
+                        program_id,
+                        account_info_iter,
+                        bridge_info,
+                        bridge,
+                        guardian_set,
+                        v,
+                    )
+                } else {
+                    return Err(Error::InvalidChain.into());
+                }
+            }
+            VAABody::RevertTransfer(v) => {
+                if v.transfer.source_chain == CHAIN_ID_SOLANA {
+                    let bridge_data = bridge_info.try_borrow_data()?;
+                    let bridge: &Bridge = Self::unpack_immutable(&bridge_data)?;
+                    evict_signatures = true;
+                    Self::process_vaa_revert_transfer(
+                        program_id,
+                        accounts,
+                        account_info_iter,
+                        &clock,
+                        bridge_info,
+                        bridge,
+                        guardian_set,
+                        v,
+                    )
+                } else {
+                    return Err(Error::InvalidChain.into());
+                }
+            }
+        }?;
+
+        // Check and create claim
+        let claim_seeds = Bridge::derive_claim_seeds(bridge_info.key, vaa.signature_body()?);
+        let claim_bump = Bridge::check_and_create_account::<ClaimedVAA>(
+            program_id,
+            accounts,
+            claim_info.key,
+            payer_info,
+            program_id,
+            &claim_seeds,
+            Some(bridge_info),
+        )?;
+
+        // Rent the payer paid for new accounts of the bridge that was not subsidized by the bridge.
+        // Accounts created for the recipient are not reimbursed.
+        let rent_advanced = payer_balance
+            .saturating_sub(payer_info.lamports())
+            .saturating_sub(recipient_rent);
+
+        // If the signatures are not needed anymore, evict them and reclaim rent.
         // This should cover most of the costs of the guardian.
         if evict_signatures {
             Self::transfer_sol(sig_info, payer_info, sig_info.lamports())?;
         }
 
-        // Refund tx fee if possible
-        if bridge_info.lamports().checked_sub(Self::MIN_BRIDGE_BALANCE).unwrap_or(0) >= Self::VAA_TX_FEE {
-            Self::transfer_sol(bridge_info, payer_info, Self::VAA_TX_FEE)?;
+        // Record the costs of the guardian and reimburse them if possible
+        let mut ledger_data = ledger_info.try_borrow_mut_data()?;
+        let ledger: &mut GuardianLedger = Bridge::unpack_unchecked(&mut ledger_data)?;
+        match ledger_bump {
+            Some(bump) => {
+                ledger.is_initialized = true;
+                ledger.guardian = *payer_info.key;
+                ledger.bump = bump;
+            }
+            None => {
+                if !ledger.is_initialized {
+                    return Err(Error::UninitializedState.into());
+                }
+                Bridge::check_derived_key(program_id, ledger_info.key, &ledger_seeds, ledger.bump)?;
+            }
         }
+        let mut bridge_data = bridge_info.try_borrow_mut_data()?;
+        let bridge: &mut Bridge = Self::unpack(&mut bridge_data)?;
+        let cost = rent_advanced
+            .checked_add(Self::VAA_TX_FEE)
+            .ok_or(Error::AmountOverflow)?;
+        ledger.submissions = ledger.submissions.checked_add(1).ok_or(Error::AmountOverflow)?;
+        ledger.rent_advanced = ledger
+            .rent_advanced
+            .checked_add(rent_advanced)
+            .ok_or(Error::AmountOverflow)?;
+        ledger.owed = ledger.owed.checked_add(cost).ok_or(Error::AmountOverflow)?;
+        bridge.owed = bridge.owed.checked_add(cost).ok_or(Error::AmountOverflow)?;
+        Self::reimburse(bridge_info, bridge, payer_info, ledger)?;
 
         // Load claim account
         let mut claim_data = claim_info.try_borrow_mut_data()?;
@@ -820,6 +1379,7 @@ impl Bridge {
 
         // Set claimed
         claim.is_initialized = true;
+        claim.bump = claim_bump;
         claim.vaa_time = clock.unix_timestamp as u32;
 
         Ok(())
@@ -856,7 +1416,7 @@ impl Bridge {
 
         // Check whether the new guardian set was derived correctly
         let guardian_seed = Bridge::derive_guardian_set_seeds(bridge_info.key, b.new_index);
-        Bridge::check_and_create_account::<GuardianSet>(
+        let guardian_bump = Bridge::check_and_create_account::<GuardianSet>(
             program_id,
             accounts,
             new_guardian_info.key,
@@ -885,6 +1445,7 @@ impl Bridge {
 
         // Set values on the new guardian set
         guardian_set_new.is_initialized = true;
+        guardian_set_new.bump = guardian_bump;
         // Force the new guardian set to not expire
         guardian_set_new.expiration_time = 0;
         guardian_set_new.index = b.new_index;
@@ -902,18 +1463,50 @@ impl Bridge {
         Ok(())
     }
 
-    /// Processes a VAA transfer in
+    /// Processes a VAA transfer in. Returns the rent the payer paid for accounts of the recipient.
     pub fn process_vaa_transfer(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         account_info_iter: &mut Iter<AccountInfo>,
         bridge_info: &AccountInfo,
+        payer_info: &AccountInfo,
         bridge: &Bridge,
         b: &BodyTransfer,
-    ) -> ProgramResult {
+    ) -> Result<u64, ProgramError> {
         next_account_info(account_info_iter)?; // Token program
         let mint_info = next_account_info(account_info_iter)?;
         let destination_info = next_account_info(account_info_iter)?;
+        let asset_info = next_account_info(account_info_iter)?;
+
+        // The target is either the token account itself or the wallet owning the associated token
+        // account the transfer is redeemed to
+        let mut recipient_rent = 0;
+        if destination_info.key.to_bytes() != b.target_address {
+            let wallet_info = next_account_info(account_info_iter)?;
+            let associated_token_info = next_account_info(account_info_iter)?;
+            if wallet_info.key.to_bytes() != b.target_address
+                || *associated_token_info.key != associated_token::id()
+                || *destination_info.key
+                    != associated_token::get_associated_token_address(
+                        wallet_info.key,
+                        mint_info.key,
+                    )
+            {
+                return Err(Error::InvalidDestination.into());
+            }
+
+            // Create the associated token account on the first redemption, the payer advances
+            // the rent
+            if destination_info.data_is_empty() {
+                let ix = associated_token::create_associated_token_account(
+                    payer_info.key,
+                    wallet_info.key,
+                    mint_info.key,
+                );
+                invoke(&ix, accounts)?;
+                recipient_rent = destination_info.lamports();
+            }
+        }
 
         let destination = Self::token_account_deserialize(destination_info)?;
         if destination.mint != *mint_info.key {
@@ -921,7 +1514,7 @@ impl Bridge {
         }
 
         if b.asset.chain == CHAIN_ID_SOLANA {
-            let custody_info = next_account_info(account_info_iter)?;
+            let custody_info = asset_info;
             let expected_custody_id =
                 Bridge::derive_custody_id(program_id, bridge_info.key, mint_info.key)?;
             if expected_custody_id != *custody_info.key {
@@ -932,22 +1525,40 @@ impl Bridge {
             Bridge::token_transfer_custody(
                 program_id,
                 accounts,
-                &bridge.config.token_program,
+                bridge,
+                bridge_info.key,
                 custody_info.key,
                 destination_info.key,
                 b.amount,
             )?;
         } else {
             // Foreign chain asset, mint wrapped asset
-            let expected_mint_address = Bridge::derive_wrapped_asset_id(
+            let wrapped_meta_info = asset_info;
+            if wrapped_meta_info.owner != program_id {
+                return Err(Error::InvalidOwner.into());
+            }
+            let wrapped_meta_data = wrapped_meta_info.try_borrow_data()?;
+            let wrapped_meta: &WrappedAssetMeta = Self::unpack_immutable(&wrapped_meta_data)?;
+            Bridge::check_derived_key(
                 program_id,
-                bridge_info.key,
-                b.asset.chain,
-                b.asset.decimals,
-                b.asset.address,
+                wrapped_meta_info.key,
+                &Bridge::derive_wrapped_meta_seeds(bridge_info.key, mint_info.key),
+                wrapped_meta.bump,
             )?;
-            if expected_mint_address != *mint_info.key {
-                return Err(Error::InvalidDerivedAccount.into());
+            Bridge::check_derived_key(
+                program_id,
+                mint_info.key,
+                &Bridge::derive_wrapped_asset_seeds(
+                    bridge_info.key,
+                    b.asset.chain,
+                    b.asset.decimals,
+                    b.asset.address,
+                ),
+                wrapped_meta.mint_bump,
+            )?;
+
+            if wrapped_meta.minting_halted {
+                return Err(Error::MintingHalted.into());
             }
 
             // This automatically asserts that the mint was created by this account by using
@@ -955,21 +1566,24 @@ impl Bridge {
             Bridge::wrapped_mint_to(
                 program_id,
                 accounts,
-                &bridge.config.token_program,
+                bridge,
+                bridge_info.key,
                 mint_info.key,
                 destination_info.key,
                 b.amount,
             )?;
         }
 
-        Ok(())
+        Ok(recipient_rent)
     }
 
     /// Processes a VAA post for data availability (for Solana -> foreign transfers)
     pub fn process_vaa_transfer_post(
         program_id: &Pubkey,
+        accounts: &[AccountInfo],
         account_info_iter: &mut Iter<AccountInfo>,
         bridge_info: &AccountInfo,
+        payer_info: &AccountInfo,
         vaa: &VAA,
         b: &BodyTransfer,
         vaa_data: VAAData,
@@ -977,113 +1591,894 @@ impl Bridge {
     ) -> ProgramResult {
         msg!("posting VAA");
         let proposal_info = Self::next_account_info_with_owner(account_info_iter, program_id)?;
+        let posted_vaa_info = next_account_info(account_info_iter)?;
+
+        let mut transfer_data = proposal_info.try_borrow_mut_data()?;
+        let mut proposal: &mut TransferOutProposal = Self::unpack(&mut transfer_data)?;
 
         // Check whether the proposal was derived correctly
-        let expected_proposal = Bridge::derive_transfer_id(
+        Bridge::check_derived_key(
             program_id,
-            bridge_info.key,
-            b.asset.chain,
-            b.asset.address,
-            b.target_chain,
-            b.target_address,
-            b.source_address,
-            b.nonce,
+            proposal_info.key,
+            &Bridge::derive_transfer_id_seeds(
+                bridge_info.key,
+                b.asset.chain,
+                b.asset.address,
+                b.target_chain,
+                b.target_address,
+                b.source_address,
+                b.nonce,
+            ),
+            proposal.bump,
         )?;
-        if expected_proposal != *proposal_info.key {
-            return Err(Error::InvalidDerivedAccount.into());
-        }
-
-        let mut transfer_data = proposal_info.try_borrow_mut_data()?;
-        let mut proposal: &mut TransferOutProposal = Self::unpack(&mut transfer_data)?;
         if !proposal.matches_vaa(b) {
             return Err(Error::VAAProposalMismatch.into());
         }
         if proposal.vaa_time != 0 {
             return Err(Error::VAAAlreadySubmitted.into());
         }
+        if proposal.reverted {
+            return Err(Error::TransferReverted.into());
+        }
         if vaa_data.len() > MAX_VAA_SIZE {
             return Err(Error::VAATooLong.into());
         }
 
-        // Set vaa
-        for i in 0..vaa_data.len() {
-            proposal.vaa[i] = vaa_data[i]
+        // Store the VAA in an account sized to fit it exactly
+        let posted_vaa_seeds = Bridge::derive_posted_vaa_seeds(bridge_info.key, proposal_info.key);
+        let posted_vaa_bump = Bridge::check_and_create_sized_account(
+            program_id,
+            accounts,
+            posted_vaa_info.key,
+            payer_info,
+            program_id,
+            &posted_vaa_seeds,
+            PostedVAA::size(vaa_data.len()),
+            Some(bridge_info),
+        )?;
+
+        let mut posted_vaa_data = posted_vaa_info.try_borrow_mut_data()?;
+        let (posted_vaa, posted_vaa_body) =
+            Bridge::unpack_posted_vaa_unchecked(&mut posted_vaa_data)?;
+        if posted_vaa.is_initialized {
+            return Err(Error::AlreadyExists.into());
         }
-        // Stop byte
-        proposal.vaa[vaa_data.len()] = 0xff;
+
+        // Set vaa
+        posted_vaa.is_initialized = true;
+        posted_vaa.bump = posted_vaa_bump;
+        posted_vaa.len = vaa_data.len() as u32;
+        posted_vaa.proposal = *proposal_info.key;
+        posted_vaa_body.copy_from_slice(&vaa_data);
+
+        proposal.vaa_account = *posted_vaa_info.key;
         proposal.vaa_time = vaa.timestamp;
         proposal.signature_account = *sig_account;
 
         Ok(())
     }
 
+    /// Processes reverting a transfer out that never received a VAA by refunding its tokens
+    pub fn process_vaa_revert_transfer(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        account_info_iter: &mut Iter<AccountInfo>,
+        clock: &Clock,
+        bridge_info: &AccountInfo,
+        bridge: &Bridge,
+        guardian_set: &GuardianSet,
+        r: &BodyRevertTransfer,
+    ) -> ProgramResult {
+        let b = &r.transfer;
+        let proposal_info = Self::next_account_info_with_owner(account_info_iter, program_id)?;
+        next_account_info(account_info_iter)?; // Token program
+        let mint_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+
+        // Reverts must be approved by the current guardian set
+        if bridge.guardian_set_index != guardian_set.index {
+            return Err(Error::OldGuardianSet.into());
+        }
+
+        let mut transfer_data = proposal_info.try_borrow_mut_data()?;
+        let proposal: &mut TransferOutProposal = Self::unpack(&mut transfer_data)?;
+
+        // Check whether the proposal was derived correctly
+        Bridge::check_derived_key(
+            program_id,
+            proposal_info.key,
+            &Bridge::derive_transfer_id_seeds(
+                bridge_info.key,
+                b.asset.chain,
+                b.asset.address,
+                b.target_chain,
+                b.target_address,
+                b.source_address,
+                b.nonce,
+            ),
+            proposal.bump,
+        )?;
+        if !proposal.matches_vaa(b) {
+            return Err(Error::VAAProposalMismatch.into());
+        }
+        if proposal.vaa_time != 0 {
+            return Err(Error::VAAAlreadySubmitted.into());
+        }
+        if proposal.reverted {
+            return Err(Error::TransferReverted.into());
+        }
+        if (proposal.lockup_time as i64 + Self::TRANSFER_REVERT_TIMEOUT as i64)
+            >= clock.unix_timestamp
+        {
+            return Err(Error::TransferNotExpired.into());
+        }
+        // The guardians attest that the transfer was not redeemed on the target chain when they
+        // checked it, which must have happened after the timeout
+        if (r.target_check_time as i64)
+            <= (proposal.lockup_time as i64 + Self::TRANSFER_REVERT_TIMEOUT as i64)
+            || (r.target_check_time as i64) > clock.unix_timestamp
+        {
+            return Err(Error::RevertNotAttested.into());
+        }
+
+        // Tokens are refunded to the account they were transferred from
+        if source_info.key.to_bytes() != proposal.source_address {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        if b.asset.chain == CHAIN_ID_SOLANA {
+            let custody_info = next_account_info(account_info_iter)?;
+            if mint_info.key.to_bytes() != b.asset.address {
+                return Err(Error::TokenMintMismatch.into());
+            }
+            let expected_custody_id =
+                Bridge::derive_custody_id(program_id, bridge_info.key, mint_info.key)?;
+            if expected_custody_id != *custody_info.key {
+                return Err(Error::InvalidDerivedAccount.into());
+            }
+
+            // Native Solana asset, release from custody
+            Bridge::token_transfer_custody(
+                program_id,
+                accounts,
+                bridge,
+                bridge_info.key,
+                custody_info.key,
+                source_info.key,
+                proposal.amount,
+            )?;
+        } else {
+            let wrapped_meta_info =
+                Self::next_account_info_with_owner(account_info_iter, program_id)?;
+            let wrapped_meta_data = wrapped_meta_info.try_borrow_data()?;
+            let wrapped_meta: &WrappedAssetMeta = Self::unpack_immutable(&wrapped_meta_data)?;
+            Bridge::check_derived_key(
+                program_id,
+                wrapped_meta_info.key,
+                &Bridge::derive_wrapped_meta_seeds(bridge_info.key, mint_info.key),
+                wrapped_meta.bump,
+            )?;
+            Bridge::check_derived_key(
+                program_id,
+                mint_info.key,
+                &Bridge::derive_wrapped_asset_seeds(
+                    bridge_info.key,
+                    b.asset.chain,
+                    b.asset.decimals,
+                    b.asset.address,
+                ),
+                wrapped_meta.mint_bump,
+            )?;
+
+            // Foreign chain asset, mint the burned tokens again
+            Bridge::wrapped_mint_to(
+                program_id,
+                accounts,
+                bridge,
+                bridge_info.key,
+                mint_info.key,
+                source_info.key,
+                proposal.amount,
+            )?;
+        }
+
+        proposal.reverted = true;
+
+        Ok(())
+    }
+
     /// Processes a VAA contract upgrade
     pub fn process_vaa_upgrade(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
+        account_info_iter: &mut Iter<AccountInfo>,
+        clock: &Clock,
         bridge_info: &AccountInfo,
+        vaa_hash: [u8; 32],
         b: &BodyContractUpgrade,
     ) -> ProgramResult {
+        let buffer_info = next_account_info(account_info_iter)?;
+
+        // Only the buffer approved by the VAA may be deployed
+        if *buffer_info.key != b.buffer {
+            return Err(ProgramError::InvalidArgument);
+        }
+        if *buffer_info.owner != bpf_loader_upgradeable::id() {
+            return Err(Error::InvalidOwner.into());
+        }
+
+        // Hash the program in the buffer before it is consumed by the upgrade
+        let mut new_buffer_hash = [0u8; 32];
+        {
+            let buffer_data = buffer_info.try_borrow_data()?;
+            let offset = UpgradeableLoaderState::buffer_data_offset()
+                .map_err(|_| ProgramError::InvalidAccountData)?;
+            if buffer_data.len() < offset {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            new_buffer_hash.copy_from_slice(hash(&buffer_data[offset..]).as_ref());
+        }
+
+        // Copy the bridge out of the account so that it is not borrowed during the invocation
+        let bridge: Bridge = *Self::unpack_immutable::<Bridge>(&bridge_info.try_borrow_data()?)?;
+
         // Invoke upgrade
-        let upgrade_ix = solana_program::bpf_loader_upgradeable::upgrade(
+        let upgrade_ix = bpf_loader_upgradeable::upgrade(
             program_id,
             &b.buffer,
             bridge_info.key,
             bridge_info.key,
         );
-        Self::invoke_as_bridge(program_id, &upgrade_ix, accounts);
+        Self::invoke_as_bridge(program_id, &bridge, &upgrade_ix, accounts)?;
+
+        // Record the upgrade, the new program needs to run the migration
+        let mut bridge_data = bridge_info.try_borrow_mut_data()?;
+        let bridge: &mut Bridge = Self::unpack(&mut bridge_data)?;
+        bridge.last_upgrade = UpgradeRecord {
+            old_buffer_hash: bridge.last_upgrade.new_buffer_hash,
+            new_buffer_hash,
+            vaa_hash,
+            upgrade_time: clock.unix_timestamp as u32,
+            migrated: false,
+        };
 
         Ok(())
     }
 
-    /// Creates a new wrapped asset
-    pub fn process_create_wrapped(
+    /// Processes a treasury withdrawal of surplus lamports from the bridge
+    pub fn process_vaa_treasury_withdrawal(
+        account_info_iter: &mut Iter<AccountInfo>,
+        bridge_info: &AccountInfo,
+        bridge: &Bridge,
+        guardian_set: &GuardianSet,
+        b: &BodyTreasuryWithdrawal,
+    ) -> ProgramResult {
+        let recipient_info = next_account_info(account_info_iter)?;
+
+        // Withdrawals must be signed by the current guardian set
+        if bridge.guardian_set_index != guardian_set.index {
+            return Err(Error::OldGuardianSet.into());
+        }
+
+        if *recipient_info.key != b.recipient {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // Only the surplus may be withdrawn, the bridge needs to stay rent exempt and keep the
+        // reimbursements owed to guardians
+        let surplus = bridge_info
+            .lamports()
+            .checked_sub(Self::MIN_BRIDGE_BALANCE)
+            .and_then(|l| l.checked_sub(bridge.owed))
+            .ok_or(ProgramError::InsufficientFunds)?;
+        if b.amount > surplus {
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        Self::transfer_sol(bridge_info, recipient_info, b.amount)
+    }
+
+    /// Processes freezing or thawing a token account of a wrapped asset
+    pub fn process_vaa_freeze_account(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        a: &AssetMeta,
+        account_info_iter: &mut Iter<AccountInfo>,
+        bridge_info: &AccountInfo,
+        bridge: &Bridge,
+        guardian_set: &GuardianSet,
+        b: &BodyFreezeAccount,
     ) -> ProgramResult {
-        msg!("create wrapped");
-        let account_info_iter = &mut accounts.iter();
-        next_account_info(account_info_iter)?; // System program
         next_account_info(account_info_iter)?; // Token program
-        next_account_info(account_info_iter)?; // Rent sysvar
-        let bridge_info = Self::next_account_info_with_owner(account_info_iter, program_id)?;
-        let payer_info = next_account_info(account_info_iter)?;
         let mint_info = next_account_info(account_info_iter)?;
-        let wrapped_meta_info = next_account_info(account_info_iter)?;
+        let token_account_info = next_account_info(account_info_iter)?;
 
-        let bridge_data = bridge_info.data.try_borrow().map_err(|_| ProgramError::AccountBorrowFailed)?;
-        let bridge: &Bridge = Self::unpack_immutable(&bridge_data)?;
-
-        // Foreign chain asset, mint wrapped asset
-        let expected_mint_address = Bridge::derive_wrapped_asset_id(
-            program_id,
-            bridge_info.key,
-            a.chain,
-            a.decimals,
-            a.address,
-        )?;
-        if expected_mint_address != *mint_info.key {
-            return Err(Error::InvalidDerivedAccount.into());
+        // Freezing must be approved by the current guardian set
+        if bridge.guardian_set_index != guardian_set.index {
+            return Err(Error::OldGuardianSet.into());
         }
 
-        // Create wrapped mint
-        Self::create_wrapped_mint(
-            program_id,
-            accounts,
-            &bridge.config.token_program,
-            mint_info.key,
-            bridge_info.key,
-            payer_info,
-            &a,
-            a.decimals,
-            None,
-        )?;
+        if *mint_info.key != b.mint || *token_account_info.key != b.token_account {
+            return Err(ProgramError::InvalidArgument);
+        }
 
-        // Check and create wrapped asset meta to allow reverse resolution of info
-        let wrapped_meta_seeds = Bridge::derive_wrapped_meta_seeds(bridge_info.key, mint_info.key);
-        Bridge::check_and_create_account::<WrappedAssetMeta>(
-            program_id,
+        // The token program checks that the bridge is the freeze authority of the mint, which is
+        // only the case for wrapped assets
+        let ix = if b.freeze {
+            spl_token::instruction::freeze_account(
+                &bridge.config.token_program,
+                token_account_info.key,
+                mint_info.key,
+                bridge_info.key,
+                &[],
+            )?
+        } else {
+            spl_token::instruction::thaw_account(
+                &bridge.config.token_program,
+                token_account_info.key,
+                mint_info.key,
+                bridge_info.key,
+                &[],
+            )?
+        };
+        Self::invoke_as_bridge(program_id, bridge, &ix, accounts)
+    }
+
+    /// Processes halting or resuming the minting of a wrapped asset
+    pub fn process_vaa_halt_minting(
+        program_id: &Pubkey,
+        account_info_iter: &mut Iter<AccountInfo>,
+        bridge_info: &AccountInfo,
+        bridge: &Bridge,
+        guardian_set: &GuardianSet,
+        b: &BodyHaltMinting,
+    ) -> ProgramResult {
+        let wrapped_meta_info = Self::next_account_info_with_owner(account_info_iter, program_id)?;
+
+        // Halting must be approved by the current guardian set
+        if bridge.guardian_set_index != guardian_set.index {
+            return Err(Error::OldGuardianSet.into());
+        }
+
+        let mut wrapped_meta_data = wrapped_meta_info.try_borrow_mut_data()?;
+        let wrapped_meta: &mut WrappedAssetMeta = Self::unpack(&mut wrapped_meta_data)?;
+        Bridge::check_derived_key(
+            program_id,
+            wrapped_meta_info.key,
+            &Bridge::derive_wrapped_meta_seeds(bridge_info.key, &b.mint),
+            wrapped_meta.bump,
+        )?;
+
+        wrapped_meta.minting_halted = b.halt;
+
+        Ok(())
+    }
+
+    /// Pays out the reimbursements owed to a guardian
+    pub fn process_claim_reimbursement(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let bridge_info = Self::next_bridge_info(account_info_iter, program_id)?;
+        let ledger_info = Self::next_account_info_with_owner(account_info_iter, program_id)?;
+        let guardian_info = next_account_info(account_info_iter)?;
+
+        if !guardian_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut ledger_data = ledger_info.try_borrow_mut_data()?;
+        let ledger: &mut GuardianLedger = Self::unpack(&mut ledger_data)?;
+        // The ledger is derived from the bridge, this also verifies the bridge account
+        Bridge::check_derived_key(
+            program_id,
+            ledger_info.key,
+            &Bridge::derive_ledger_seeds(bridge_info.key, guardian_info.key),
+            ledger.bump,
+        )?;
+
+        let mut bridge_data = bridge_info.try_borrow_mut_data()?;
+        let bridge: &mut Bridge = Self::unpack(&mut bridge_data)?;
+        if Self::reimburse(bridge_info, bridge, guardian_info, ledger)? == 0 {
+            return Err(Error::NothingToClaim.into());
+        }
+
+        Ok(())
+    }
+
+    /// Pays out as much of the reimbursements owed to a guardian as the surplus of the bridge
+    /// allows. Returns the amount paid out.
+    pub fn reimburse(
+        bridge_info: &AccountInfo,
+        bridge: &mut Bridge,
+        guardian_info: &AccountInfo,
+        ledger: &mut GuardianLedger,
+    ) -> Result<u64, ProgramError> {
+        let surplus = bridge_info
+            .lamports()
+            .checked_sub(Self::MIN_BRIDGE_BALANCE)
+            .unwrap_or(0);
+        let amount = ledger.owed.min(surplus);
+        if amount > 0 {
+            Self::transfer_sol(bridge_info, guardian_info, amount)?;
+            ledger.owed -= amount;
+            ledger.refunds_received += amount;
+            bridge.owed = bridge.owed.saturating_sub(amount);
+        }
+
+        Ok(amount)
+    }
+
+    /// Runs the migration of the upgraded program once after a contract upgrade
+    pub fn process_migrate(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        payload: &MigratePayload,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        next_account_info(account_info_iter)?; // System program
+        let clock_info = next_account_info(account_info_iter)?;
+        let bridge_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let claim_info = Self::next_account_info_with_owner(account_info_iter, program_id)?;
+        let bridge_stage_info = next_account_info(account_info_iter)?;
+
+        let clock = Clock::from_account_info(clock_info)?;
+        let vaa = VAA::deserialize(&payload.vaa)?;
+        let upgrade = match &vaa.payload {
+            Some(VAABody::UpgradeContract(u)) if u.chain_id == CHAIN_ID_SOLANA => u,
+            _ => return Err(Error::InvalidVAAAction.into()),
+        };
+
+        // Only the deployer of the program approved by the guardians holds the buffer key
+        if !authority_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *authority_info.key != upgrade.buffer {
+            return Err(Error::MigrationNotApproved.into());
+        }
+
+        // The claim proves that the guardians signed the upgrade and that it was executed
+        Bridge::check_derived_key(
+            program_id,
+            bridge_info.key,
+            &Bridge::derive_bridge_seeds(),
+            payload.bridge_bump,
+        )?;
+        Bridge::check_derived_key(
+            program_id,
+            claim_info.key,
+            &Bridge::derive_claim_seeds(bridge_info.key, vaa.signature_body()?),
+            payload.claim_bump,
+        )?;
+        let vaa_time = {
+            let mut claim_data = claim_info.try_borrow_mut_data()?;
+            let claim: &mut ClaimedVAA = Self::unpack(&mut claim_data)?;
+            // Claims of the previous version did not store their bump seed
+            if claim.bump == 0 {
+                claim.bump = payload.claim_bump;
+            }
+            claim.vaa_time
+        };
+        let vaa_hash = vaa.body_hash()?;
+
+        if bridge_info.data_len() == size_of::<Bridge>() {
+            // The bridge has the layout of this version and recorded the upgrade
+            if bridge_info.owner != program_id {
+                return Err(Error::InvalidOwner.into());
+            }
+            let mut bridge_data = bridge_info.try_borrow_mut_data()?;
+            let bridge: &mut Bridge = Self::unpack(&mut bridge_data)?;
+            bridge.check_bridge_key(program_id, bridge_info.key)?;
+
+            Self::check_upgrade_record(&mut bridge.last_upgrade, &vaa_hash, vaa_time)?;
+            bridge.last_upgrade.migrated = payload.finish;
+        } else if payload.finish {
+            // The bridge still has the layout of the previous version or is staged
+            return Err(Error::NoPendingMigration.into());
+        } else if bridge_info.data_is_empty() {
+            // The bridge is staged, its stage holds the recorded upgrade
+            if bridge_stage_info.owner != program_id {
+                return Err(Error::InvalidOwner.into());
+            }
+            let mut stage_data = bridge_stage_info.try_borrow_mut_data()?;
+            let (stage, data, _) = Bridge::unpack_migration_stage_unchecked(&mut stage_data)?;
+            if !stage.is_initialized || stage.account != *bridge_info.key {
+                return Err(Error::InvalidDerivedAccount.into());
+            }
+            Bridge::check_derived_key(
+                program_id,
+                bridge_stage_info.key,
+                &Bridge::derive_migration_stage_seeds(bridge_info.key, bridge_info.key),
+                stage.bump,
+            )?;
+            let bridge: &mut Bridge = Self::unpack(data)?;
+
+            Self::check_upgrade_record(&mut bridge.last_upgrade, &vaa_hash, vaa_time)?;
+        }
+
+        // The previous version did not record upgrades, converting its bridge records this one
+        let last_upgrade = UpgradeRecord {
+            old_buffer_hash: [0; 32],
+            new_buffer_hash: [0; 32],
+            vaa_hash,
+            upgrade_time: vaa_time,
+            migrated: false,
+        };
+        Self::migrate_accounts(
+            program_id,
+            accounts,
+            &clock,
+            bridge_info.key,
+            authority_info,
+            &last_upgrade,
+            &mut payload.bumps.iter(),
+            account_info_iter,
+        )
+    }
+
+    /// Checks that the upgrade VAA with `vaa_hash`, which was claimed at `vaa_time`, is the
+    /// recorded upgrade and was not migrated yet. Upgrades executed by the previous version were
+    /// not recorded, so the conversion of its bridge may have recorded an older upgrade. A later
+    /// upgrade replaces the record, older upgrades are rejected.
+    fn check_upgrade_record(
+        record: &mut UpgradeRecord,
+        vaa_hash: &[u8; 32],
+        vaa_time: u32,
+    ) -> ProgramResult {
+        if vaa_time > record.upgrade_time {
+            *record = UpgradeRecord {
+                vaa_hash: *vaa_hash,
+                upgrade_time: vaa_time,
+                ..UpgradeRecord::default()
+            };
+        }
+        if record.vaa_hash != *vaa_hash || record.upgrade_time == 0 || record.migrated {
+            return Err(Error::NoPendingMigration.into());
+        }
+        Ok(())
+    }
+
+    /// Converts accounts created by the previous version of the program to the layout of this
+    /// version. Each account is followed by its migration stage. Accounts of the previous layout
+    /// are staged and closed, closed accounts are recreated from their stage. The addresses of
+    /// accounts of the previous layout are verified with the bump seeds taken from `bumps`.
+    pub fn migrate_accounts<'a, 'b>(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        clock: &Clock,
+        bridge_key: &Pubkey,
+        payer_info: &AccountInfo,
+        last_upgrade: &UpgradeRecord,
+        bumps: &mut Iter<u8>,
+        account_info_iter: &mut Iter<'a, AccountInfo<'b>>,
+    ) -> ProgramResult {
+        while let Some(account_info) = account_info_iter.next() {
+            let stage_info = next_account_info(account_info_iter)?;
+            if account_info.data_is_empty() {
+                Self::restore_account(
+                    program_id,
+                    accounts,
+                    bridge_key,
+                    payer_info,
+                    account_info,
+                    stage_info,
+                )?;
+            } else {
+                Self::stage_legacy_account(
+                    program_id,
+                    accounts,
+                    clock,
+                    bridge_key,
+                    payer_info,
+                    last_upgrade,
+                    bumps,
+                    account_info,
+                    stage_info,
+                    account_info_iter,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts an account of the previous version, which is identified by its size, and stages
+    /// the converted data
+    fn stage_legacy_account<'a, 'b>(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        clock: &Clock,
+        bridge_key: &Pubkey,
+        payer_info: &AccountInfo,
+        last_upgrade: &UpgradeRecord,
+        bumps: &mut Iter<u8>,
+        account_info: &AccountInfo,
+        stage_info: &AccountInfo,
+        account_info_iter: &mut Iter<'a, AccountInfo<'b>>,
+    ) -> ProgramResult {
+        if account_info.owner != program_id {
+            return Err(Error::InvalidOwner.into());
+        }
+
+        let size = account_info.data_len();
+        if size == size_of::<LegacyBridge>() {
+            let (seeds, bridge) = {
+                let data = account_info.try_borrow_data()?;
+                let legacy: &LegacyBridge = Self::unpack_immutable(&data)?;
+                let (bump, seeds) = Self::legacy_seeds(
+                    program_id,
+                    account_info.key,
+                    &Bridge::derive_bridge_seeds(),
+                    bumps,
+                )?;
+                (seeds, legacy.convert(bump, *last_upgrade))
+            };
+            Self::stage_account(program_id, accounts, bridge_key, payer_info, account_info, stage_info, &seeds, &bridge)
+        } else if size == size_of::<LegacyGuardianSet>() {
+            let (seeds, guardian_set) = {
+                let data = account_info.try_borrow_data()?;
+                let legacy: &LegacyGuardianSet = Self::unpack_immutable(&data)?;
+                let (bump, seeds) = Self::legacy_seeds(
+                    program_id,
+                    account_info.key,
+                    &Bridge::derive_guardian_set_seeds(bridge_key, legacy.index),
+                    bumps,
+                )?;
+                (seeds, legacy.convert(bump))
+            };
+            Self::stage_account(program_id, accounts, bridge_key, payer_info, account_info, stage_info, &seeds, &guardian_set)
+        } else if size == size_of::<LegacySignatureState>() {
+            let (seeds, signature_state) = {
+                let data = account_info.try_borrow_data()?;
+                let legacy: &LegacySignatureState = Self::unpack_immutable(&data)?;
+                let (bump, seeds) = Self::legacy_seeds(
+                    program_id,
+                    account_info.key,
+                    &Bridge::derive_signature_seeds(bridge_key, &legacy.hash, legacy.guardian_set_index),
+                    bumps,
+                )?;
+                (seeds, legacy.convert(bump, clock.unix_timestamp as u32))
+            };
+            Self::stage_account(program_id, accounts, bridge_key, payer_info, account_info, stage_info, &seeds, &signature_state)
+        } else if size == size_of::<LegacyWrappedAssetMeta>() {
+            let mint_info = next_account_info(account_info_iter)?;
+            let (seeds, wrapped_meta) = {
+                let data = account_info.try_borrow_data()?;
+                let legacy: &LegacyWrappedAssetMeta = Self::unpack_immutable(&data)?;
+                let (bump, seeds) = Self::legacy_seeds(
+                    program_id,
+                    account_info.key,
+                    &Bridge::derive_wrapped_meta_seeds(bridge_key, mint_info.key),
+                    bumps,
+                )?;
+                let mint = Bridge::mint_deserialize(mint_info)?;
+                let (mint_bump, _) = Self::legacy_seeds(
+                    program_id,
+                    mint_info.key,
+                    &Bridge::derive_wrapped_asset_seeds(bridge_key, legacy.chain, mint.decimals, legacy.address),
+                    bumps,
+                )?;
+                (seeds, legacy.convert(bump, mint_bump))
+            };
+            Self::stage_account(program_id, accounts, bridge_key, payer_info, account_info, stage_info, &seeds, &wrapped_meta)
+        } else if size == size_of::<LegacyTransferOutProposal>() {
+            let (seeds, proposal) = {
+                let data = account_info.try_borrow_data()?;
+                let legacy: &LegacyTransferOutProposal = Self::unpack_immutable(&data)?;
+                let (bump, seeds) = Self::legacy_seeds(
+                    program_id,
+                    account_info.key,
+                    &Bridge::derive_transfer_id_seeds(
+                        bridge_key,
+                        legacy.asset.chain,
+                        legacy.asset.address,
+                        legacy.to_chain_id,
+                        legacy.foreign_address,
+                        legacy.source_address,
+                        legacy.nonce,
+                    ),
+                    bumps,
+                )?;
+
+                // The VAA stored in the proposal moves to the posted VAA account of the proposal
+                let vaa_account = if legacy.vaa_time != 0 {
+                    let posted_vaa_info = next_account_info(account_info_iter)?;
+                    let vaa = legacy.posted_vaa().ok_or(ProgramError::InvalidAccountData)?;
+                    Self::post_legacy_vaa(
+                        program_id,
+                        accounts,
+                        bridge_key,
+                        payer_info,
+                        account_info.key,
+                        posted_vaa_info,
+                        vaa,
+                    )?;
+                    *posted_vaa_info.key
+                } else {
+                    Pubkey::default()
+                };
+                (seeds, legacy.convert(bump, vaa_account))
+            };
+            Self::stage_account(program_id, accounts, bridge_key, payer_info, account_info, stage_info, &seeds, &proposal)
+        } else {
+            Err(ProgramError::InvalidAccountData)
+        }
+    }
+
+    /// Checks the address of an account of the previous version with the next bump seed of
+    /// `bumps` and returns the bump seed and the seeds including it. The previous version did not
+    /// store bump seeds, the converted account stores it.
+    fn legacy_seeds(
+        program_id: &Pubkey,
+        key: &Pubkey,
+        seeds: &Vec<Vec<u8>>,
+        bumps: &mut Iter<u8>,
+    ) -> Result<(u8, Vec<Vec<u8>>), ProgramError> {
+        let bump = *bumps.next().ok_or(ProgramError::InvalidInstructionData)?;
+        Bridge::check_derived_key(program_id, key, seeds, bump)?;
+
+        let mut full_seeds = seeds.clone();
+        full_seeds.push(vec![bump]);
+        Ok((bump, full_seeds))
+    }
+
+    /// Creates the posted VAA account of a proposal of the previous version, which stored the
+    /// VAA in the proposal itself
+    fn post_legacy_vaa(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        bridge_key: &Pubkey,
+        payer_info: &AccountInfo,
+        proposal_key: &Pubkey,
+        posted_vaa_info: &AccountInfo,
+        vaa: &[u8],
+    ) -> ProgramResult {
+        let bump = Bridge::check_and_create_sized_account(
+            program_id,
+            accounts,
+            posted_vaa_info.key,
+            payer_info,
+            program_id,
+            &Bridge::derive_posted_vaa_seeds(bridge_key, proposal_key),
+            PostedVAA::size(vaa.len()),
+            None,
+        )?;
+
+        let mut posted_vaa_data = posted_vaa_info.try_borrow_mut_data()?;
+        let (posted_vaa, body) = Bridge::unpack_posted_vaa_unchecked(&mut posted_vaa_data)?;
+        posted_vaa.proposal = *proposal_key;
+        posted_vaa.len = vaa.len() as u32;
+        posted_vaa.is_initialized = true;
+        posted_vaa.bump = bump;
+        body.copy_from_slice(vaa);
+
+        Ok(())
+    }
+
+    /// Stores the converted data `value` of an account derived from `seeds` in the stage of the
+    /// account and closes the account, moving its lamports to the stage
+    fn stage_account<T: IsInitialized + Copy>(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        bridge_key: &Pubkey,
+        payer_info: &AccountInfo,
+        account_info: &AccountInfo,
+        stage_info: &AccountInfo,
+        seeds: &Vec<Vec<u8>>,
+        value: &T,
+    ) -> ProgramResult {
+        let stage_bump = Bridge::check_and_create_sized_account(
+            program_id,
+            accounts,
+            stage_info.key,
+            payer_info,
+            program_id,
+            &Bridge::derive_migration_stage_seeds(bridge_key, account_info.key),
+            MigrationStage::size(size_of::<T>(), seeds),
+            None,
+        )?;
+
+        {
+            let mut stage_data = stage_info.try_borrow_mut_data()?;
+            {
+                let header: &mut MigrationStage =
+                    Self::unpack_unchecked(&mut stage_data[..size_of::<MigrationStage>()])?;
+                header.len = size_of::<T>() as u32;
+                header.seeds_len = MigrationStage::seeds_len(seeds) as u32;
+            }
+            let (stage, data, seed_data) = Bridge::unpack_migration_stage_unchecked(&mut stage_data)?;
+            stage.lamports = account_info.lamports();
+            stage.account = *account_info.key;
+            stage.is_initialized = true;
+            stage.bump = stage_bump;
+            *Self::unpack_unchecked::<T>(data)? = *value;
+            MigrationStage::write_seeds(seeds, seed_data)?;
+        }
+
+        // The runtime deletes the account at the end of the transaction
+        Self::close_account(account_info, stage_info)
+    }
+
+    /// Recreates a staged account with its converted data and closes the stage
+    fn restore_account(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        bridge_key: &Pubkey,
+        payer_info: &AccountInfo,
+        account_info: &AccountInfo,
+        stage_info: &AccountInfo,
+    ) -> ProgramResult {
+        if stage_info.owner != program_id {
+            return Err(Error::InvalidOwner.into());
+        }
+
+        let lamports = {
+            let stage_data = stage_info.try_borrow_data()?;
+            let (stage, data, seeds) = Bridge::unpack_migration_stage(&stage_data)?;
+            Bridge::check_derived_key(
+                program_id,
+                stage_info.key,
+                &Bridge::derive_migration_stage_seeds(bridge_key, account_info.key),
+                stage.bump,
+            )?;
+            if stage.account != *account_info.key {
+                return Err(Error::InvalidDerivedAccount.into());
+            }
+
+            // Signing with the staged seeds assures that the account is recreated at its address
+            Self::create_account_raw(
+                program_id,
+                accounts,
+                account_info.key,
+                payer_info.key,
+                program_id,
+                &MigrationStage::read_seeds(seeds)?,
+                data.len(),
+            )?;
+            account_info.try_borrow_mut_data()?.copy_from_slice(data);
+            stage.lamports
+        };
+
+        // The payer is refunded the rent it paid, the account keeps the remaining lamports it held
+        let rent = Rent::default().minimum_balance(account_info.data_len());
+        Self::transfer_sol(stage_info, payer_info, lamports.min(rent))?;
+        Self::transfer_sol(stage_info, account_info, lamports.saturating_sub(rent))?;
+        Self::close_account(stage_info, payer_info)
+    }
+
+    /// Creates a new wrapped asset
+    pub fn process_create_wrapped(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        a: &AssetMeta,
+    ) -> ProgramResult {
+        msg!("create wrapped");
+        let account_info_iter = &mut accounts.iter();
+        next_account_info(account_info_iter)?; // System program
+        next_account_info(account_info_iter)?; // Token program
+        next_account_info(account_info_iter)?; // Rent sysvar
+        let bridge_info = Self::next_bridge_info(account_info_iter, program_id)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let wrapped_meta_info = next_account_info(account_info_iter)?;
+
+        let bridge_data = bridge_info.data.try_borrow().map_err(|_| ProgramError::AccountBorrowFailed)?;
+        let bridge: &Bridge = Self::unpack_immutable(&bridge_data)?;
+
+        // Create wrapped mint, this checks that the mint address was derived correctly
+        let mint_bump = Self::create_wrapped_mint(
+            program_id,
+            accounts,
+            &bridge.config.token_program,
+            mint_info.key,
+            bridge_info.key,
+            payer_info,
+            &a,
+            a.decimals,
+            None,
+        )?;
+
+        // Check and create wrapped asset meta to allow reverse resolution of info
+        let wrapped_meta_seeds = Bridge::derive_wrapped_meta_seeds(bridge_info.key, mint_info.key);
+        let wrapped_meta_bump = Bridge::check_and_create_account::<WrappedAssetMeta>(
+            program_id,
             accounts,
             wrapped_meta_info.key,
             payer_info,
@@ -1096,8 +2491,11 @@ impl Bridge {
         let wrapped_meta: &mut WrappedAssetMeta = Bridge::unpack_unchecked(&mut wrapped_meta_data)?;
 
         wrapped_meta.is_initialized = true;
+        wrapped_meta.bump = wrapped_meta_bump;
+        wrapped_meta.mint_bump = mint_bump;
         wrapped_meta.address = a.address;
         wrapped_meta.chain = a.chain;
+        wrapped_meta.minting_halted = false;
 
         Ok(())
     }
@@ -1109,81 +2507,84 @@ impl Bridge {
     pub fn wrapped_burn(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        token_program_id: &Pubkey,
+        bridge: &Bridge,
         token_account: &Pubkey,
         mint_account: &Pubkey,
+        authority: &Pubkey,
         amount: U256,
     ) -> Result<(), ProgramError> {
         let ix = spl_token::instruction::burn(
-            token_program_id,
+            &bridge.config.token_program,
             token_account,
             mint_account,
-            &Self::derive_bridge_id(program_id)?,
+            authority,
             &[],
             amount.as_u64(),
         )?;
-        Self::invoke_as_bridge(program_id, &ix, accounts)
+        Self::invoke_as_bridge(program_id, bridge, &ix, accounts)
     }
 
     /// Mint a wrapped asset to account
     pub fn wrapped_mint_to(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        token_program_id: &Pubkey,
+        bridge: &Bridge,
+        bridge_key: &Pubkey,
         mint: &Pubkey,
         destination: &Pubkey,
         amount: U256,
     ) -> Result<(), ProgramError> {
         let ix = spl_token::instruction::mint_to(
-            token_program_id,
+            &bridge.config.token_program,
             mint,
             destination,
-            &Self::derive_bridge_id(program_id)?,
+            bridge_key,
             &[],
             amount.as_u64(),
         )?;
-        Self::invoke_as_bridge(program_id, &ix, accounts)
+        Self::invoke_as_bridge(program_id, bridge, &ix, accounts)
     }
 
     /// Transfer tokens from a caller
     pub fn token_transfer_caller(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        token_program_id: &Pubkey,
+        bridge: &Bridge,
         source: &Pubkey,
         destination: &Pubkey,
         authority: &Pubkey,
         amount: U256,
     ) -> Result<(), ProgramError> {
         let ix = spl_token::instruction::transfer(
-            token_program_id,
+            &bridge.config.token_program,
             source,
             destination,
             authority,
             &[],
             amount.as_u64(),
         )?;
-        Self::invoke_as_bridge(program_id, &ix, accounts)
+        Self::invoke_as_bridge(program_id, bridge, &ix, accounts)
     }
 
     /// Transfer tokens from a custody account
     pub fn token_transfer_custody(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        token_program_id: &Pubkey,
+        bridge: &Bridge,
+        bridge_key: &Pubkey,
         source: &Pubkey,
         destination: &Pubkey,
         amount: U256,
     ) -> Result<(), ProgramError> {
         let ix = spl_token::instruction::transfer(
-            token_program_id,
+            &bridge.config.token_program,
             source,
             destination,
-            &Self::derive_bridge_id(program_id)?,
+            bridge_key,
             &[],
             amount.as_u64(),
         )?;
-        Self::invoke_as_bridge(program_id, &ix, accounts)
+        Self::invoke_as_bridge(program_id, bridge, &ix, accounts)
     }
 
     /// Create a new account
@@ -1211,12 +2612,12 @@ impl Bridge {
             token_program,
             account,
             mint,
-            &Self::derive_bridge_id(program_id)?,
+            bridge,
         )?;
         invoke_signed(&ix, accounts, &[])
     }
 
-    /// Create a mint for a wrapped asset
+    /// Create a mint for a wrapped asset and return the bump seed of its address
     pub fn create_wrapped_mint(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
@@ -1227,8 +2628,8 @@ impl Bridge {
         asset: &AssetMeta,
         decimals: u8,
         subsidizer: Option<&AccountInfo>,
-    ) -> Result<(), ProgramError> {
-        Self::check_and_create_account::<[u8; spl_token::state::Mint::LEN]>(
+    ) -> Result<u8, ProgramError> {
+        let bump = Self::check_and_create_account::<[u8; spl_token::state::Mint::LEN]>(
             program_id,
             accounts,
             mint,
@@ -1237,28 +2638,30 @@ impl Bridge {
             &Self::derive_wrapped_asset_seeds(bridge, asset.chain, asset.decimals, asset.address),
             subsidizer,
         )?;
+        // The bridge is the freeze authority so that the guardians can contain the wrapped supply
+        // if the asset is exploited on its native chain
         let ix = spl_token::instruction::initialize_mint(
             token_program,
             mint,
-            &Self::derive_bridge_id(program_id)?,
-            None,
+            bridge,
+            Some(bridge),
             decimals,
         )?;
-        invoke_signed(&ix, accounts, &[])
+        invoke_signed(&ix, accounts, &[])?;
+        Ok(bump)
     }
 
     pub fn invoke_as_bridge<'a>(
         program_id: &Pubkey,
+        bridge: &Bridge,
         instruction: &Instruction,
         account_infos: &[AccountInfo<'a>],
     ) -> ProgramResult {
-        let (_, seeds) =
-            Self::find_program_address(&vec!["bridge".as_bytes().to_vec()], program_id);
-        Self::invoke_vec_seed(program_id, instruction, account_infos, &seeds)
+        Self::invoke_vec_seed(program_id, instruction, account_infos, &bridge.signer_seeds())
     }
 
     pub fn invoke_vec_seed<'a>(
-        program_id: &Pubkey,
+        _program_id: &Pubkey,
         instruction: &Instruction,
         account_infos: &[AccountInfo<'a>],
         seeds: &Vec<Vec<u8>>,
@@ -1273,7 +2676,8 @@ impl Bridge {
         solana_program::rent::DEFAULT_LAMPORTS_PER_BYTE_YEAR) as f64
         * solana_program::rent::DEFAULT_EXEMPTION_THRESHOLD) as u64;
 
-    /// Check that a key was derived correctly and create account
+    /// Check that a key was derived correctly and create account.
+    /// Returns the bump seed of the derived address.
     pub fn check_and_create_account<T: Sized>(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
@@ -1282,7 +2686,32 @@ impl Bridge {
         owner: &Pubkey,
         seeds: &Vec<Vec<u8>>,
         subsidizer: Option<&AccountInfo>,
-    ) -> Result<Vec<Vec<u8>>, ProgramError> {
+    ) -> Result<u8, ProgramError> {
+        Self::check_and_create_sized_account(
+            program_id,
+            accounts,
+            new_account,
+            payer,
+            owner,
+            seeds,
+            size_of::<T>(),
+            subsidizer,
+        )
+    }
+
+    /// Check that a key was derived correctly and create an account of the given size
+    pub fn check_and_create_sized_account(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        new_account: &Pubkey,
+        payer: &AccountInfo,
+        owner: &Pubkey,
+        seeds: &Vec<Vec<u8>>,
+        size: usize,
+        subsidizer: Option<&AccountInfo>,
+    ) -> Result<u8, ProgramError> {
+        // New accounts are created at the canonical address. Claims and proposals are unique
+        // because of that, so the bump seed must not be chosen by the caller.
         msg!("deriving key");
         let (expected_key, full_seeds) = Bridge::derive_key(program_id, seeds)?;
         if expected_key != *new_account {
@@ -1290,13 +2719,14 @@ impl Bridge {
         }
 
         msg!("deploying contract");
-        Self::create_account_raw::<T>(
+        Self::create_account_raw(
             program_id,
             accounts,
             new_account,
             payer.key,
             owner,
             &full_seeds,
+            size,
         )?;
 
         // The subsidizer refunds the rent that needs to be paid to create the account.
@@ -1307,7 +2737,7 @@ impl Bridge {
             None => {}
             Some(v) => {
                 let bal = v.try_lamports()?;
-                let rent = Rent::default().minimum_balance(size_of::<T>());
+                let rent = Rent::default().minimum_balance(size);
                 if bal.checked_sub(Self::MIN_BRIDGE_BALANCE).ok_or(ProgramError::InsufficientFunds)? >= rent {
                     // Refund rent to payer
                     Self::transfer_sol(v, payer, rent)?;
@@ -1315,19 +2745,19 @@ impl Bridge {
             }
         }
 
-        Ok(full_seeds)
+        Ok(full_seeds.last().map(|b| b[0]).unwrap_or_default())
     }
 
     /// Create a new account
-    fn create_account_raw<T: Sized>(
-        program_id: &Pubkey,
+    fn create_account_raw(
+        _program_id: &Pubkey,
         accounts: &[AccountInfo],
         new_account: &Pubkey,
         payer: &Pubkey,
         owner: &Pubkey,
         seeds: &Vec<Vec<u8>>,
+        size: usize,
     ) -> Result<(), ProgramError> {
-        let size = size_of::<T>();
         let ix = create_account(
             payer,
             new_account,
@@ -1339,6 +2769,22 @@ impl Bridge {
         invoke_signed(&ix, accounts, &[s.as_slice()])
     }
 
+    /// Get the next account info from the iterator and check that it is the bridge of
+    /// `program_id`. The bridge signs for the accounts of the program, so it is verified against
+    /// its derived address rather than only its owner.
+    pub fn next_bridge_info<'a, 'b, I: Iterator<Item=&'a AccountInfo<'b>>>(
+        iter: &mut I,
+        program_id: &Pubkey,
+    ) -> Result<I::Item, ProgramError> {
+        let bridge_info = Self::next_account_info_with_owner(iter, program_id)?;
+        {
+            let bridge_data = bridge_info.try_borrow_data()?;
+            let bridge: &Bridge = Self::unpack_immutable(&bridge_data)?;
+            bridge.check_bridge_key(program_id, bridge_info.key)?;
+        }
+        Ok(bridge_info)
+    }
+
     /// Get the next account info from the iterator and check that it has the given owner
     pub fn next_account_info_with_owner<'a, 'b, I: Iterator<Item=&'a AccountInfo<'b>>>(
         iter: &mut I,
@@ -1351,3 +2797,1582 @@ impl Bridge {
         Ok(acc)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Once;
+
+    use solana_program::{
+        clock::Epoch, instruction::AccountMeta, message::Message, program_stubs,
+        secp256k1_program, sysvar::instructions,
+    };
+
+    use crate::{
+        instruction::{BatchedTransfer, ForeignAddress, CHAIN_ID_ETHEREUM, MAX_BATCHED_TRANSFERS},
+        vaa::{BodyFreezeAccount, BodyHaltMinting},
+    };
+
+    use super::*;
+
+    /// Account owned by a test and lent to the processor as an `AccountInfo`
+    struct TestAccount {
+        key: Pubkey,
+        owner: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+        is_signer: bool,
+    }
+
+    impl TestAccount {
+        fn new(key: Pubkey, owner: Pubkey, lamports: u64, data: Vec<u8>) -> Self {
+            TestAccount {
+                key,
+                owner,
+                lamports,
+                data,
+                is_signer: false,
+            }
+        }
+
+        fn with_state<T: IsInitialized>(
+            key: Pubkey,
+            owner: Pubkey,
+            lamports: u64,
+            state: T,
+        ) -> Self {
+            let mut data = vec![0; size_of::<T>()];
+            *Bridge::unpack_unchecked::<T>(&mut data).unwrap() = state;
+            Self::new(key, owner, lamports, data)
+        }
+
+        fn signer(mut self) -> Self {
+            self.is_signer = true;
+            self
+        }
+
+        fn info(&mut self) -> AccountInfo<'_> {
+            AccountInfo::new(
+                &self.key,
+                self.is_signer,
+                true,
+                &mut self.lamports,
+                &mut self.data,
+                &self.owner,
+                false,
+                Epoch::default(),
+            )
+        }
+
+        fn state<T: IsInitialized + Copy>(&self) -> T {
+            *Bridge::unpack_immutable::<T>(&self.data).unwrap()
+        }
+    }
+
+    fn clock_account(unix_timestamp: i64) -> TestAccount {
+        let mut account = TestAccount::new(
+            solana_program::sysvar::clock::id(),
+            solana_program::sysvar::id(),
+            0,
+            vec![0; Clock::size_of()],
+        );
+        Clock {
+            unix_timestamp,
+            ..Clock::default()
+        }
+        .to_account_info(&mut account.info())
+        .unwrap();
+        account
+    }
+
+    /// Instructions sysvar of a transaction executing the instruction at `current`
+    fn instructions_account(ixs: &[Instruction], current: u16) -> TestAccount {
+        let mut data = Message::new(ixs, None).serialize_instructions();
+        data.extend_from_slice(&current.to_le_bytes());
+        TestAccount::new(instructions::id(), solana_program::sysvar::id(), 0, data)
+    }
+
+    fn bridge_account(program_id: &Pubkey, lamports: u64, owed: u64) -> TestAccount {
+        let (key, seeds) = Bridge::derive_key(program_id, &Bridge::derive_bridge_seeds()).unwrap();
+        let bridge = Bridge {
+            guardian_set_index: 1,
+            config: BridgeConfig {
+                token_program: spl_token::id(),
+                ..BridgeConfig::default()
+            },
+            last_upgrade: UpgradeRecord::default(),
+            owed,
+            is_initialized: true,
+            bump: seeds.last().unwrap()[0],
+        };
+        TestAccount::with_state(key, *program_id, lamports, bridge)
+    }
+
+    fn guardian_set(index: u32) -> GuardianSet {
+        GuardianSet {
+            index,
+            len_keys: 1,
+            keys: [[7; 20]; MAX_LEN_GUARDIAN_KEYS],
+            creation_time: 0,
+            expiration_time: 0,
+            is_initialized: true,
+            bump: 0,
+        }
+    }
+
+    fn derived_account<T: IsInitialized>(
+        program_id: &Pubkey,
+        seeds: &Vec<Vec<u8>>,
+        state: impl FnOnce(u8) -> T,
+    ) -> TestAccount {
+        let (key, seeds) = Bridge::derive_key(program_id, seeds).unwrap();
+        let bump = seeds.last().unwrap()[0];
+        TestAccount::with_state(key, *program_id, 0, state(bump))
+    }
+
+    #[test]
+    fn treasury_withdrawal_keeps_owed_reimbursements() {
+        let program_id = Pubkey::new_unique();
+        let mut bridge_account =
+            bridge_account(&program_id, Bridge::MIN_BRIDGE_BALANCE + 1000, 400);
+        let mut recipient = TestAccount::new(
+            Pubkey::new_unique(),
+            solana_program::system_program::id(),
+            0,
+            vec![],
+        );
+        let bridge = bridge_account.state::<Bridge>();
+        let withdrawal = |amount| BodyTreasuryWithdrawal {
+            chain_id: CHAIN_ID_SOLANA,
+            recipient: recipient.key,
+            amount,
+        };
+        let (too_much, surplus, dust, other_recipient) = (
+            withdrawal(700),
+            withdrawal(600),
+            withdrawal(1),
+            BodyTreasuryWithdrawal {
+                recipient: Pubkey::new_unique(),
+                ..withdrawal(1)
+            },
+        );
+
+        let bridge_info = bridge_account.info();
+        let accounts = [recipient.info()];
+        let withdraw = |guardian_set_index, b: &BodyTreasuryWithdrawal| {
+            Bridge::process_vaa_treasury_withdrawal(
+                &mut accounts.iter(),
+                &bridge_info,
+                &bridge,
+                &guardian_set(guardian_set_index),
+                b,
+            )
+        };
+        assert_eq!(withdraw(0, &surplus), Err(Error::OldGuardianSet.into()));
+        assert_eq!(
+            withdraw(1, &other_recipient),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(withdraw(1, &too_much), Err(ProgramError::InsufficientFunds));
+        assert_eq!(withdraw(1, &surplus), Ok(()));
+        assert_eq!(bridge_info.lamports(), Bridge::MIN_BRIDGE_BALANCE + 400);
+        assert_eq!(accounts[0].lamports(), 600);
+
+        // Nothing is left above the owed reimbursements, or above the minimum balance at all
+        assert_eq!(withdraw(1, &dust), Err(ProgramError::InsufficientFunds));
+        **bridge_info.try_borrow_mut_lamports().unwrap() = Bridge::MIN_BRIDGE_BALANCE - 1;
+        assert_eq!(withdraw(1, &dust), Err(ProgramError::InsufficientFunds));
+    }
+
+    #[test]
+    fn claim_reimbursement_pays_out_surplus() {
+        let program_id = Pubkey::new_unique();
+        let mut bridge = bridge_account(&program_id, Bridge::MIN_BRIDGE_BALANCE + 300, 500);
+        let mut guardian = TestAccount::new(
+            Pubkey::new_unique(),
+            solana_program::system_program::id(),
+            0,
+            vec![],
+        )
+        .signer();
+        let guardian_key = guardian.key;
+        let mut ledger = derived_account(
+            &program_id,
+            &Bridge::derive_ledger_seeds(&bridge.key, &guardian.key),
+            |bump| GuardianLedger {
+                guardian: guardian_key,
+                owed: 500,
+                is_initialized: true,
+                bump,
+                ..GuardianLedger::default()
+            },
+        );
+
+        {
+            let accounts = [bridge.info(), ledger.info(), guardian.info()];
+            assert_eq!(
+                Bridge::process_claim_reimbursement(&program_id, &accounts),
+                Ok(())
+            );
+            // Nothing is left to pay out until the bridge is refilled
+            assert_eq!(
+                Bridge::process_claim_reimbursement(&program_id, &accounts),
+                Err(Error::NothingToClaim.into())
+            );
+        }
+        assert_eq!(guardian.lamports, 300);
+        assert_eq!(bridge.lamports, Bridge::MIN_BRIDGE_BALANCE);
+        assert_eq!(bridge.state::<Bridge>().owed, 200);
+        let l = ledger.state::<GuardianLedger>();
+        assert_eq!((l.owed, l.refunds_received), (200, 300));
+
+        guardian.is_signer = false;
+        let accounts = [bridge.info(), ledger.info(), guardian.info()];
+        assert_eq!(
+            Bridge::process_claim_reimbursement(&program_id, &accounts),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+
+    #[test]
+    fn halt_minting() {
+        let program_id = Pubkey::new_unique();
+        let mut bridge_account = bridge_account(&program_id, 0, 0);
+        let mint = Pubkey::new_unique();
+        let mut wrapped_meta = derived_account(
+            &program_id,
+            &Bridge::derive_wrapped_meta_seeds(&bridge_account.key, &mint),
+            |bump| WrappedAssetMeta {
+                is_initialized: true,
+                bump,
+                ..WrappedAssetMeta::default()
+            },
+        );
+        let bridge = bridge_account.state::<Bridge>();
+        let bridge_info = bridge_account.info();
+
+        {
+            let accounts = [wrapped_meta.info()];
+            let halt = |guardian_set_index, mint, halt| {
+                Bridge::process_vaa_halt_minting(
+                    &program_id,
+                    &mut accounts.iter(),
+                    &bridge_info,
+                    &bridge,
+                    &guardian_set(guardian_set_index),
+                    &BodyHaltMinting {
+                        chain_id: CHAIN_ID_SOLANA,
+                        mint,
+                        halt,
+                    },
+                )
+            };
+            assert_eq!(halt(0, mint, true), Err(Error::OldGuardianSet.into()));
+            assert_eq!(
+                halt(1, Pubkey::new_unique(), true),
+                Err(Error::InvalidDerivedAccount.into())
+            );
+            assert_eq!(halt(1, mint, true), Ok(()));
+        }
+        assert!(wrapped_meta.state::<WrappedAssetMeta>().minting_halted);
+    }
+
+    #[test]
+    fn freeze_account_checks_vaa() {
+        let program_id = Pubkey::new_unique();
+        let mut bridge_account = bridge_account(&program_id, 0, 0);
+        let bridge = bridge_account.state::<Bridge>();
+        let bridge_info = bridge_account.info();
+        let mut token_program = TestAccount::new(
+            spl_token::id(),
+            solana_program::system_program::id(),
+            0,
+            vec![],
+        );
+        let mut mint = TestAccount::new(Pubkey::new_unique(), spl_token::id(), 0, vec![]);
+        let mut token_account = TestAccount::new(Pubkey::new_unique(), spl_token::id(), 0, vec![]);
+        let b = BodyFreezeAccount {
+            chain_id: CHAIN_ID_SOLANA,
+            mint: mint.key,
+            token_account: token_account.key,
+            freeze: true,
+        };
+        let other_account = BodyFreezeAccount {
+            token_account: Pubkey::new_unique(),
+            ..b.clone()
+        };
+
+        let accounts = [token_program.info(), mint.info(), token_account.info()];
+        let freeze = |guardian_set_index, b: &BodyFreezeAccount| {
+            Bridge::process_vaa_freeze_account(
+                &program_id,
+                &accounts,
+                &mut accounts.iter(),
+                &bridge_info,
+                &bridge,
+                &guardian_set(guardian_set_index),
+                b,
+            )
+        };
+        assert_eq!(freeze(0, &b), Err(Error::OldGuardianSet.into()));
+        assert_eq!(
+            freeze(1, &other_account),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    fn transfer() -> BodyTransfer {
+        BodyTransfer {
+            nonce: 9,
+            source_chain: CHAIN_ID_SOLANA,
+            target_chain: 2,
+            source_address: Pubkey::new_unique().to_bytes(),
+            target_address: [4; 32],
+            asset: AssetMeta {
+                address: [5; 32],
+                chain: 2,
+                decimals: 8,
+            },
+            amount: U256::from(1000),
+        }
+    }
+
+    fn proposal_account(program_id: &Pubkey, bridge_key: &Pubkey, b: &BodyTransfer) -> TestAccount {
+        derived_account(
+            program_id,
+            &Bridge::derive_transfer_id_seeds(
+                bridge_key,
+                b.asset.chain,
+                b.asset.address,
+                b.target_chain,
+                b.target_address,
+                b.source_address,
+                b.nonce,
+            ),
+            |bump| TransferOutProposal {
+                amount: b.amount,
+                to_chain_id: b.target_chain,
+                source_address: b.source_address,
+                foreign_address: b.target_address,
+                asset: b.asset,
+                sequence: b.nonce,
+                vaa_account: Pubkey::default(),
+                vaa_time: 0,
+                lockup_time: 1000,
+                poke_counter: 0,
+                signature_account: Pubkey::default(),
+                reverted: false,
+                is_initialized: true,
+                bump,
+            },
+        )
+    }
+
+    fn set_proposal(account: &mut TestAccount, f: impl FnOnce(&mut TransferOutProposal)) {
+        f(Bridge::unpack(&mut account.data).unwrap());
+    }
+
+    #[test]
+    fn revert_transfer_requires_attested_timeout() {
+        let program_id = Pubkey::new_unique();
+        let mut bridge_account = bridge_account(&program_id, 0, 0);
+        let bridge = bridge_account.state::<Bridge>();
+        let b = transfer();
+        let mut proposal = proposal_account(&program_id, &bridge_account.key, &b);
+        let mut token_program = TestAccount::new(
+            spl_token::id(),
+            solana_program::system_program::id(),
+            0,
+            vec![],
+        );
+        let mut mint = TestAccount::new(Pubkey::new_unique(), spl_token::id(), 0, vec![]);
+        let mut source = TestAccount::new(Pubkey::new_unique(), spl_token::id(), 0, vec![]);
+        let timeout = 1000 + Bridge::TRANSFER_REVERT_TIMEOUT;
+
+        let mut revert = |proposal: &mut TestAccount, now: u32, target_check_time: u32| {
+            let bridge_info = bridge_account.info();
+            let accounts = [
+                proposal.info(),
+                token_program.info(),
+                mint.info(),
+                source.info(),
+            ];
+            Bridge::process_vaa_revert_transfer(
+                &program_id,
+                &accounts,
+                &mut accounts.iter(),
+                &Clock {
+                    unix_timestamp: now as i64,
+                    ..Clock::default()
+                },
+                &bridge_info,
+                &bridge,
+                &guardian_set(1),
+                &BodyRevertTransfer {
+                    transfer: b.clone(),
+                    target_check_time,
+                },
+            )
+        };
+
+        assert_eq!(
+            revert(&mut proposal, timeout, timeout),
+            Err(Error::TransferNotExpired.into())
+        );
+        // The target chain must have been checked after the timeout and before the revert
+        assert_eq!(
+            revert(&mut proposal, timeout + 100, timeout),
+            Err(Error::RevertNotAttested.into())
+        );
+        assert_eq!(
+            revert(&mut proposal, timeout + 100, timeout + 101),
+            Err(Error::RevertNotAttested.into())
+        );
+        // An attested revert proceeds to refund the source of the transfer
+        assert_eq!(
+            revert(&mut proposal, timeout + 100, timeout + 50),
+            Err(ProgramError::InvalidArgument)
+        );
+
+        set_proposal(&mut proposal, |p| p.vaa_time = 2000);
+        assert_eq!(
+            revert(&mut proposal, timeout + 100, timeout + 50),
+            Err(Error::VAAAlreadySubmitted.into())
+        );
+        set_proposal(&mut proposal, |p| {
+            p.vaa_time = 0;
+            p.reverted = true;
+        });
+        assert_eq!(
+            revert(&mut proposal, timeout + 100, timeout + 50),
+            Err(Error::TransferReverted.into())
+        );
+    }
+
+    #[test]
+    fn poke_rejects_settled_proposals() {
+        let program_id = Pubkey::new_unique();
+        let b = transfer();
+        let mut proposal = proposal_account(&program_id, &Pubkey::new_unique(), &b);
+
+        assert_eq!(
+            Bridge::process_poke(&program_id, &[proposal.info()]),
+            Ok(())
+        );
+        assert_eq!(proposal.state::<TransferOutProposal>().poke_counter, 1);
+
+        set_proposal(&mut proposal, |p| p.vaa_time = 2000);
+        assert_eq!(
+            Bridge::process_poke(&program_id, &[proposal.info()]),
+            Err(Error::VAAAlreadySubmitted.into())
+        );
+        set_proposal(&mut proposal, |p| {
+            p.vaa_time = 0;
+            p.reverted = true;
+        });
+        assert_eq!(
+            Bridge::process_poke(&program_id, &[proposal.info()]),
+            Err(Error::TransferReverted.into())
+        );
+    }
+
+    /// Secp instruction with a single signature whose offsets point into its own data
+    fn secp_instruction(sig_offset: u16, data_len: usize) -> Instruction {
+        let mut data = vec![0; data_len.max(1 + SECP_OFFSETS_SIZE)];
+        data[0] = 1;
+        data[1..3].copy_from_slice(&sig_offset.to_le_bytes());
+        data[4..6].copy_from_slice(&12u16.to_le_bytes());
+        data[7..9].copy_from_slice(&12u16.to_le_bytes());
+        data[9..11].copy_from_slice(&4u16.to_le_bytes());
+        data.truncate(data_len);
+        Instruction {
+            program_id: secp256k1_program::id(),
+            accounts: vec![],
+            data,
+        }
+    }
+
+    #[test]
+    fn verify_signatures_rejects_out_of_bounds_offsets() {
+        let program_id = Pubkey::new_unique();
+        let mut bridge = bridge_account(&program_id, 0, 0);
+        let mut bridge_program = TestAccount::new(program_id, Pubkey::default(), 0, vec![]);
+        let mut system = TestAccount::new(
+            solana_program::system_program::id(),
+            Pubkey::default(),
+            0,
+            vec![],
+        );
+        let mut sig_state = TestAccount::new(Pubkey::new_unique(), program_id, 0, vec![]);
+        let mut guardian_set =
+            TestAccount::with_state(Pubkey::new_unique(), program_id, 0, guardian_set(1));
+        let mut payer = TestAccount::new(Pubkey::new_unique(), Pubkey::default(), 0, vec![]);
+        let mut clock = clock_account(0);
+        let mut signers = [-1; MAX_LEN_GUARDIAN_KEYS];
+        signers[0] = 0;
+        let payload = VerifySigPayload {
+            hash: [0; 32],
+            signers,
+            initial_creation: true,
+            secp_instruction: 0,
+        };
+
+        let mut verify = |secp_ix: Instruction| {
+            let mut instructions = instructions_account(&[secp_ix], 1);
+            let accounts = [
+                bridge_program.info(),
+                system.info(),
+                instructions.info(),
+                bridge.info(),
+                sig_state.info(),
+                guardian_set.info(),
+                payer.info(),
+                clock.info(),
+            ];
+            Bridge::process_verify_signatures(&program_id, &accounts, &payload)
+        };
+
+        // The offsets of the signature are truncated
+        assert_eq!(
+            verify(secp_instruction(12, 1 + SECP_OFFSETS_SIZE - 1)),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        // The signature would extend beyond the instruction data
+        assert_eq!(
+            verify(secp_instruction(40, 100)),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(
+            verify(secp_instruction(u16::MAX, 100)),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn transfer_out_batch_requires_fee_per_transfer() {
+        let program_id = Pubkey::new_unique();
+        let mut bridge = bridge_account(&program_id, 0, 0);
+        let bridge_key = bridge.key;
+        let mut payer =
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), 0, vec![]).signer();
+        let mut other = [
+            TestAccount::new(program_id, Pubkey::default(), 0, vec![]),
+            TestAccount::new(
+                solana_program::system_program::id(),
+                Pubkey::default(),
+                0,
+                vec![],
+            ),
+            TestAccount::new(spl_token::id(), Pubkey::default(), 0, vec![]),
+            TestAccount::new(
+                solana_program::sysvar::rent::id(),
+                Pubkey::default(),
+                0,
+                vec![],
+            ),
+        ];
+        let mut clock = clock_account(0);
+        let payload = |len| TransferOutBatchPayload {
+            chain_id: 2,
+            target: [4; 32],
+            transfers: vec![
+                BatchedTransfer {
+                    amount: U256::from(1),
+                    asset: AssetMeta::default(),
+                    custody_bump: 0,
+                };
+                len
+            ],
+        };
+        let bridge_ix =
+            Instruction::new(program_id, &(), vec![AccountMeta::new(bridge_key, false)]);
+
+        let mut transfer_out = |fee_recipient: &Pubkey, fee: u64, len: usize| {
+            let mut instructions = instructions_account(
+                &[
+                    system_instruction::transfer(&payer.key, fee_recipient, fee),
+                    bridge_ix.clone(),
+                ],
+                1,
+            );
+            let [bridge_program, system, token, rent] = &mut other;
+            let accounts = [
+                bridge_program.info(),
+                system.info(),
+                token.info(),
+                rent.info(),
+                clock.info(),
+                instructions.info(),
+                bridge.info(),
+                payer.info(),
+            ];
+            Bridge::process_transfer_out_batch(&program_id, &accounts, &payload(len))
+        };
+
+        let fee = Bridge::transfer_fee();
+        assert_eq!(
+            transfer_out(&bridge_key, fee, MAX_BATCHED_TRANSFERS),
+            Err(Error::InsufficientFees.into())
+        );
+        assert_eq!(
+            transfer_out(
+                &Pubkey::new_unique(),
+                fee * MAX_BATCHED_TRANSFERS as u64,
+                MAX_BATCHED_TRANSFERS
+            ),
+            Err(ProgramError::InvalidArgument)
+        );
+        // With the fees paid, the batch proceeds to the transfers, whose accounts are missing
+        assert_eq!(
+            transfer_out(
+                &bridge_key,
+                fee * MAX_BATCHED_TRANSFERS as u64,
+                MAX_BATCHED_TRANSFERS
+            ),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+    }
+
+    #[test]
+    fn close_guardian_set_requires_expiration() {
+        let program_id = Pubkey::new_unique();
+        let mut bridge = bridge_account(&program_id, 1000, 0);
+        let seeds = Bridge::derive_guardian_set_seeds(&bridge.key, 0);
+        let mut active = derived_account(&program_id, &seeds, |bump| GuardianSet {
+            index: 0,
+            bump,
+            ..guardian_set(0)
+        });
+        let mut expired = derived_account(&program_id, &seeds, |bump| GuardianSet {
+            index: 0,
+            expiration_time: 2000,
+            bump,
+            ..guardian_set(0)
+        });
+        expired.lamports = 500;
+        let closable = 2000 + Bridge::GUARDIAN_SET_CLOSE_DELAY as i64 + 1;
+
+        let mut close = |guardian_set: &mut TestAccount, now| {
+            let mut clock = clock_account(now);
+            let accounts = [clock.info(), bridge.info(), guardian_set.info()];
+            Bridge::process_close_guardian_set(&program_id, &accounts)
+        };
+        assert_eq!(
+            close(&mut active, closable),
+            Err(Error::AccountNotExpired.into())
+        );
+        assert_eq!(
+            close(&mut expired, closable - 1),
+            Err(Error::AccountNotExpired.into())
+        );
+        assert_eq!(close(&mut expired, closable), Ok(()));
+
+        assert_eq!(bridge.lamports, 1500);
+        assert_eq!(expired.lamports, 0);
+        assert!(expired.data.iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn evict_signatures_requires_eviction_time() {
+        let program_id = Pubkey::new_unique();
+        let mut bridge = bridge_account(&program_id, 1000, 0);
+        let signature_state = |creation_time| {
+            let mut account = derived_account(
+                &program_id,
+                &Bridge::derive_signature_seeds(&bridge.key, &[3; 32], 1),
+                |bump| SignatureState {
+                    signatures: [[0; 65]; MAX_LEN_GUARDIAN_KEYS],
+                    hash: [3; 32],
+                    guardian_set_index: 1,
+                    creation_time,
+                    is_initialized: true,
+                    bump,
+                },
+            );
+            account.lamports = 500;
+            account
+        };
+        // Signature states migrated from the previous version have no creation time
+        let mut unknown_age = signature_state(0);
+        let mut stale = signature_state(2000);
+        let evictable = 2000 + Bridge::SIGNATURE_EVICTION_TIME as i64 + 1;
+
+        let mut evict = |sig_state: &mut TestAccount, now| {
+            let mut clock = clock_account(now);
+            let accounts = [clock.info(), bridge.info(), sig_state.info()];
+            Bridge::process_evict_signatures(&program_id, &accounts)
+        };
+        assert_eq!(
+            evict(&mut unknown_age, evictable),
+            Err(Error::AccountNotExpired.into())
+        );
+        assert_eq!(
+            evict(&mut stale, evictable - 1),
+            Err(Error::AccountNotExpired.into())
+        );
+        assert_eq!(evict(&mut stale, evictable), Ok(()));
+
+        assert_eq!(bridge.lamports, 1500);
+        assert_eq!(stale.lamports, 0);
+        assert!(stale.data.iter().all(|b| *b == 0));
+    }
+
+    fn upgrade_vaa(buffer: Pubkey) -> VAA {
+        VAA {
+            version: 1,
+            guardian_set_index: 1,
+            signatures: vec![],
+            timestamp: 100,
+            payload: Some(VAABody::UpgradeContract(BodyContractUpgrade {
+                chain_id: CHAIN_ID_SOLANA,
+                buffer,
+            })),
+        }
+    }
+
+    #[test]
+    fn migrate_requires_upgrade_buffer_key() {
+        let program_id = Pubkey::new_unique();
+        let buffer = Pubkey::new_unique();
+        let vaa = upgrade_vaa(buffer);
+        let vaa_hash = vaa.body_hash().unwrap();
+
+        let mut bridge = bridge_account(&program_id, 0, 0);
+        let bridge_key = bridge.key;
+        set_last_upgrade(&mut bridge, vaa_hash);
+        let mut claim = derived_account(
+            &program_id,
+            &Bridge::derive_claim_seeds(&bridge_key, vaa.signature_body().unwrap()),
+            |bump| ClaimedVAA {
+                hash: vaa_hash,
+                vaa_time: 200,
+                is_initialized: true,
+                bump,
+            },
+        );
+        let mut system = TestAccount::new(
+            solana_program::system_program::id(),
+            Pubkey::default(),
+            0,
+            vec![],
+        );
+        let mut clock = clock_account(300);
+        let mut authority = TestAccount::new(buffer, Pubkey::default(), 0, vec![]).signer();
+        let mut impostor =
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), 0, vec![]).signer();
+        let mut bridge_stage = bridge_stage_account(&program_id, &bridge_key);
+        let bridge_bump = bridge.state::<Bridge>().bump;
+        let claim_bump = claim.state::<ClaimedVAA>().bump;
+
+        let mut migrate = |bridge: &mut TestAccount, authority: &mut TestAccount, finish| {
+            let accounts = [
+                system.info(),
+                clock.info(),
+                bridge.info(),
+                authority.info(),
+                claim.info(),
+                bridge_stage.info(),
+            ];
+            let payload = MigratePayload {
+                finish,
+                bridge_bump,
+                claim_bump,
+                bumps: vec![],
+                vaa: vaa.serialize().unwrap(),
+            };
+            Bridge::process_migrate(&program_id, &accounts, &payload)
+        };
+
+        assert_eq!(
+            migrate(&mut bridge, &mut impostor, true),
+            Err(Error::MigrationNotApproved.into())
+        );
+        authority.is_signer = false;
+        assert_eq!(
+            migrate(&mut bridge, &mut authority, true),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        authority.is_signer = true;
+
+        assert_eq!(migrate(&mut bridge, &mut authority, false), Ok(()));
+        assert_eq!(migrate(&mut bridge, &mut authority, true), Ok(()));
+        assert!(bridge.state::<Bridge>().last_upgrade.migrated);
+        assert_eq!(
+            migrate(&mut bridge, &mut authority, false),
+            Err(Error::NoPendingMigration.into())
+        );
+
+        // A bridge that recorded another upgrade can't be migrated with this VAA
+        set_last_upgrade(&mut bridge, [1; 32]);
+        assert_eq!(
+            migrate(&mut bridge, &mut authority, false),
+            Err(Error::NoPendingMigration.into())
+        );
+    }
+
+    #[test]
+    fn migrate_verifies_bump_seeds_and_stores_legacy_claim_bump() {
+        let program_id = Pubkey::new_unique();
+        let buffer = Pubkey::new_unique();
+        let vaa = upgrade_vaa(buffer);
+        let vaa_hash = vaa.body_hash().unwrap();
+
+        let mut bridge = bridge_account(&program_id, 0, 0);
+        let bridge_key = bridge.key;
+        let bridge_bump = bridge.state::<Bridge>().bump;
+        set_last_upgrade(&mut bridge, vaa_hash);
+
+        // Claims of the previous version have no bump seed
+        let claim_seeds = Bridge::derive_claim_seeds(&bridge_key, vaa.signature_body().unwrap());
+        let (_, claim_full_seeds) = Bridge::derive_key(&program_id, &claim_seeds).unwrap();
+        let claim_bump = claim_full_seeds.last().unwrap()[0];
+        let mut claim = derived_account(&program_id, &claim_seeds, |_| ClaimedVAA {
+            hash: vaa_hash,
+            vaa_time: 200,
+            is_initialized: true,
+            bump: 0,
+        });
+
+        // A guardian set of the previous layout and its stage
+        let (set_key, set_seeds) =
+            Bridge::derive_key(&program_id, &Bridge::derive_guardian_set_seeds(&bridge_key, 0))
+                .unwrap();
+        let set_bump = set_seeds.last().unwrap()[0];
+        let mut legacy_set = TestAccount::with_state(
+            set_key,
+            program_id,
+            1000,
+            LegacyGuardianSet {
+                index: 0,
+                len_keys: 1,
+                keys: [[7; 20]; MAX_LEN_GUARDIAN_KEYS],
+                creation_time: 0,
+                expiration_time: 0,
+                is_initialized: true,
+            },
+        );
+        let stage_key =
+            Bridge::derive_migration_stage_id(&program_id, &bridge_key, &set_key).unwrap();
+        let mut stage = TestAccount::new(
+            stage_key,
+            program_id,
+            0,
+            vec![0; MigrationStage::size(size_of::<GuardianSet>(), &set_seeds)],
+        );
+
+        let mut system = TestAccount::new(
+            solana_program::system_program::id(),
+            Pubkey::default(),
+            0,
+            vec![],
+        );
+        let mut clock = clock_account(300);
+        let mut authority = TestAccount::new(buffer, Pubkey::default(), 0, vec![]).signer();
+        let mut bridge_stage = bridge_stage_account(&program_id, &bridge_key);
+
+        let mut migrate = |bridge_bump: u8, claim_bump: u8, bumps: Vec<u8>| {
+            let accounts = [
+                system.info(),
+                clock.info(),
+                bridge.info(),
+                authority.info(),
+                claim.info(),
+                bridge_stage.info(),
+                legacy_set.info(),
+                stage.info(),
+            ];
+            let payload = MigratePayload {
+                finish: false,
+                bridge_bump,
+                claim_bump,
+                bumps,
+                vaa: vaa.serialize().unwrap(),
+            };
+            Bridge::process_migrate(&program_id, &accounts, &payload)
+        };
+
+        assert_eq!(
+            migrate(bridge_bump.wrapping_sub(1), claim_bump, vec![set_bump]),
+            Err(Error::InvalidDerivedAccount.into())
+        );
+        assert_eq!(
+            migrate(bridge_bump, claim_bump.wrapping_sub(1), vec![set_bump]),
+            Err(Error::InvalidDerivedAccount.into())
+        );
+        assert_eq!(
+            migrate(bridge_bump, claim_bump, vec![]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(
+            migrate(bridge_bump, claim_bump, vec![set_bump.wrapping_sub(1)]),
+            Err(Error::InvalidDerivedAccount.into())
+        );
+        assert_eq!(migrate(bridge_bump, claim_bump, vec![set_bump]), Ok(()));
+
+        assert_eq!(claim.state::<ClaimedVAA>().bump, claim_bump);
+        assert_eq!(legacy_set.lamports, 0);
+        let (header, data, seeds) = Bridge::unpack_migration_stage(&stage.data).unwrap();
+        assert_eq!(header.account, set_key);
+        assert_eq!(Bridge::unpack_immutable::<GuardianSet>(data).unwrap().bump, set_bump);
+        assert_eq!(MigrationStage::read_seeds(seeds).unwrap(), set_seeds);
+    }
+
+    #[test]
+    fn migrate_of_legacy_bridge_requires_latest_upgrade() {
+        let program_id = Pubkey::new_unique();
+        let buffer = Pubkey::new_unique();
+        let old_vaa = VAA {
+            timestamp: 50,
+            ..upgrade_vaa(buffer)
+        };
+        let new_vaa = upgrade_vaa(buffer);
+        let old_hash = old_vaa.body_hash().unwrap();
+        let new_hash = new_vaa.body_hash().unwrap();
+
+        let (bridge_key, bridge_seeds) =
+            Bridge::derive_key(&program_id, &Bridge::derive_bridge_seeds()).unwrap();
+        let bridge_bump = bridge_seeds.last().unwrap()[0];
+        let claim = |vaa: &VAA, vaa_time| {
+            derived_account(
+                &program_id,
+                &Bridge::derive_claim_seeds(&bridge_key, vaa.signature_body().unwrap()),
+                |bump| ClaimedVAA {
+                    hash: vaa.body_hash().unwrap(),
+                    vaa_time,
+                    is_initialized: true,
+                    bump,
+                },
+            )
+        };
+        let mut old_claim = claim(&old_vaa, 100);
+        let mut new_claim = claim(&new_vaa, 200);
+
+        let mut system = TestAccount::new(
+            solana_program::system_program::id(),
+            Pubkey::default(),
+            0,
+            vec![],
+        );
+        let mut clock = clock_account(300);
+        let mut authority = TestAccount::new(buffer, Pubkey::default(), 0, vec![]).signer();
+
+        // `convert` passes the bridge and its stage as accounts to migrate
+        let mut migrate = |vaa: &VAA,
+                           claim: &mut TestAccount,
+                           bridge: &mut TestAccount,
+                           stage: &mut TestAccount,
+                           finish: bool,
+                           convert: bool| {
+            let claim_bump = claim.state::<ClaimedVAA>().bump;
+            let bridge_info = bridge.info();
+            let stage_info = stage.info();
+            let mut accounts = vec![
+                system.info(),
+                clock.info(),
+                bridge_info.clone(),
+                authority.info(),
+                claim.info(),
+                stage_info.clone(),
+            ];
+            let mut bumps = vec![];
+            if convert {
+                accounts.push(bridge_info);
+                accounts.push(stage_info);
+                bumps.push(bridge_bump);
+            }
+            let payload = MigratePayload {
+                finish,
+                bridge_bump,
+                claim_bump,
+                bumps,
+                vaa: vaa.serialize().unwrap(),
+            };
+            Bridge::process_migrate(&program_id, &accounts, &payload)
+        };
+        let recorded = |stage: &TestAccount| {
+            let (_, data, _) = Bridge::unpack_migration_stage(&stage.data).unwrap();
+            Bridge::unpack_immutable::<Bridge>(data).unwrap().last_upgrade
+        };
+
+        // Converting the bridge of the previous version records the upgrade VAA, even an older one
+        let mut legacy_bridge = TestAccount::with_state(
+            bridge_key,
+            program_id,
+            1000,
+            LegacyBridge {
+                guardian_set_index: 1,
+                config: BridgeConfig::default(),
+                is_initialized: true,
+            },
+        );
+        let mut stage = TestAccount::new(
+            Bridge::derive_migration_stage_id(&program_id, &bridge_key, &bridge_key).unwrap(),
+            program_id,
+            0,
+            vec![0; MigrationStage::size(size_of::<Bridge>(), &bridge_seeds)],
+        );
+        assert_eq!(
+            migrate(&old_vaa, &mut old_claim, &mut legacy_bridge, &mut stage, false, true),
+            Ok(())
+        );
+        assert_eq!(recorded(&stage).vaa_hash, old_hash);
+        assert_eq!(legacy_bridge.lamports, 0);
+
+        // While the bridge is staged, the upgrade executed later replaces the record
+        let mut staged =
+            TestAccount::new(bridge_key, solana_program::system_program::id(), 0, vec![]);
+        assert_eq!(
+            migrate(&new_vaa, &mut new_claim, &mut staged, &mut stage, false, false),
+            Ok(())
+        );
+        assert_eq!(
+            recorded(&stage),
+            UpgradeRecord {
+                vaa_hash: new_hash,
+                upgrade_time: 200,
+                ..UpgradeRecord::default()
+            }
+        );
+        assert_eq!(
+            migrate(&old_vaa, &mut old_claim, &mut staged, &mut stage, false, false),
+            Err(Error::NoPendingMigration.into())
+        );
+        assert_eq!(
+            migrate(&new_vaa, &mut new_claim, &mut staged, &mut stage, true, false),
+            Err(Error::NoPendingMigration.into())
+        );
+
+        // The record is only read from the stage of the bridge
+        let mut copied_stage =
+            TestAccount::new(Pubkey::new_unique(), program_id, 0, stage.data.clone());
+        assert_eq!(
+            migrate(&new_vaa, &mut new_claim, &mut staged, &mut copied_stage, false, false),
+            Err(Error::InvalidDerivedAccount.into())
+        );
+
+        // A recreated bridge that finished the migration of the older upgrade
+        let mut bridge = bridge_account(&program_id, 0, 0);
+        Bridge::unpack::<Bridge>(&mut bridge.data).unwrap().last_upgrade = UpgradeRecord {
+            vaa_hash: old_hash,
+            upgrade_time: 100,
+            migrated: true,
+            ..UpgradeRecord::default()
+        };
+        assert_eq!(
+            migrate(&old_vaa, &mut old_claim, &mut bridge, &mut stage, false, false),
+            Err(Error::NoPendingMigration.into())
+        );
+        assert_eq!(
+            migrate(&new_vaa, &mut new_claim, &mut bridge, &mut stage, true, false),
+            Ok(())
+        );
+        let last_upgrade = bridge.state::<Bridge>().last_upgrade;
+        assert_eq!(last_upgrade.vaa_hash, new_hash);
+        assert!(last_upgrade.migrated);
+    }
+
+    /// Empty stage account of the bridge, only read while the bridge is staged
+    fn bridge_stage_account(program_id: &Pubkey, bridge_key: &Pubkey) -> TestAccount {
+        TestAccount::new(
+            Bridge::derive_migration_stage_id(program_id, bridge_key, bridge_key).unwrap(),
+            *program_id,
+            0,
+            vec![],
+        )
+    }
+
+    fn set_last_upgrade(bridge: &mut TestAccount, vaa_hash: [u8; 32]) {
+        let b: &mut Bridge = Bridge::unpack(&mut bridge.data).unwrap();
+        b.last_upgrade = UpgradeRecord {
+            vaa_hash,
+            upgrade_time: 200,
+            ..UpgradeRecord::default()
+        };
+    }
+
+    fn token_account(key: Pubkey, mint: &Pubkey, owner: &Pubkey, amount: u64) -> TestAccount {
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..spl_token::state::Account::default()
+        }
+        .pack_into_slice(&mut data);
+        TestAccount::new(key, spl_token::id(), 0, data)
+    }
+
+    fn mint_account(key: Pubkey) -> TestAccount {
+        let mut data = vec![0; Mint::LEN];
+        Mint {
+            decimals: 9,
+            is_initialized: true,
+            ..Mint::default()
+        }
+        .pack_into_slice(&mut data);
+        TestAccount::new(key, spl_token::id(), 0, data)
+    }
+
+    fn ethereum_address() -> ForeignAddress {
+        let mut address = [0; 32];
+        address[12..].copy_from_slice(&[4; 20]);
+        address
+    }
+
+    /// Accounts of a `TransferOut` of a native token, in the order of `instruction::transfer_out`.
+    /// The proposal is allocated as if the system program had created it.
+    fn native_transfer_out_accounts(
+        program_id: &Pubkey,
+        bridge: TestAccount,
+        sender: &Pubkey,
+        mint: &Pubkey,
+        sequence: u32,
+        t: &mut TransferOutPayload,
+    ) -> Vec<TestAccount> {
+        let bridge_ix =
+            Instruction::new(*program_id, &(), vec![AccountMeta::new(bridge.key, false)]);
+        let fee_ix = system_instruction::transfer(
+            &Pubkey::new_unique(),
+            &bridge.key,
+            Bridge::transfer_fee(),
+        );
+        let (proposal_key, _) = Bridge::derive_key(
+            program_id,
+            &Bridge::derive_transfer_id_seeds(
+                &bridge.key,
+                t.asset.chain,
+                t.asset.address,
+                t.chain_id,
+                t.target,
+                sender.to_bytes(),
+                sequence,
+            ),
+        )
+        .unwrap();
+        let sequence_account = derived_account(
+            program_id,
+            &Bridge::derive_sequence_seeds(&bridge.key, sender),
+            |bump| TransferSequence {
+                sequence,
+                is_initialized: true,
+                bump,
+            },
+        );
+        let (custody_key, custody_seeds) =
+            Bridge::derive_key(program_id, &Bridge::derive_custody_seeds(&bridge.key, mint))
+                .unwrap();
+        t.custody_bump = custody_seeds.last().unwrap()[0];
+        let custody = token_account(custody_key, mint, &bridge.key, 0);
+
+        vec![
+            TestAccount::new(*program_id, Pubkey::default(), 0, vec![]),
+            TestAccount::new(
+                solana_program::system_program::id(),
+                Pubkey::default(),
+                0,
+                vec![],
+            ),
+            TestAccount::new(spl_token::id(), Pubkey::default(), 0, vec![]),
+            TestAccount::new(
+                solana_program::sysvar::rent::id(),
+                Pubkey::default(),
+                0,
+                vec![],
+            ),
+            clock_account(1000),
+            instructions_account(&[fee_ix, bridge_ix], 1),
+            token_account(*sender, mint, &Pubkey::new_unique(), 100),
+            bridge,
+            TestAccount::new(
+                proposal_key,
+                *program_id,
+                0,
+                vec![0; size_of::<TransferOutProposal>()],
+            ),
+            mint_account(*mint),
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), 0, vec![]).signer(),
+            sequence_account,
+            custody,
+        ]
+    }
+
+    #[test]
+    fn transfer_out_and_post_vaa_use_separate_accounts() {
+        let program_id = Pubkey::new_unique();
+        let bridge = bridge_account(&program_id, Bridge::MIN_BRIDGE_BALANCE, 0);
+        let bridge_key = bridge.key;
+        let sender = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut t = TransferOutPayload {
+            amount: U256::from(100),
+            chain_id: 2,
+            asset: AssetMeta {
+                address: mint.to_bytes(),
+                chain: CHAIN_ID_SOLANA,
+                decimals: 9,
+            },
+            target: ethereum_address(),
+            custody_bump: 0,
+        };
+        let mut accounts =
+            native_transfer_out_accounts(&program_id, bridge, &sender, &mint, 5, &mut t);
+        {
+            let infos: Vec<_> = accounts.iter_mut().map(|a| a.info()).collect();
+            assert_eq!(
+                Bridge::process_transfer_native_out(&program_id, &infos, &t, false),
+                Ok(())
+            );
+        }
+
+        // The proposal only links to the account that the VAA is posted to
+        let proposal = accounts[8].state::<TransferOutProposal>();
+        assert_eq!(proposal.sequence, 5);
+        assert_eq!(proposal.vaa_account, Pubkey::default());
+        assert_eq!(proposal.vaa_time, 0);
+        let b = BodyTransfer {
+            nonce: proposal.sequence,
+            source_chain: CHAIN_ID_SOLANA,
+            target_chain: proposal.to_chain_id,
+            source_address: sender.to_bytes(),
+            target_address: proposal.foreign_address,
+            asset: proposal.asset,
+            amount: proposal.amount,
+        };
+        let vaa = VAA {
+            version: 1,
+            guardian_set_index: 1,
+            signatures: vec![],
+            timestamp: 2000,
+            payload: Some(VAABody::Transfer(b.clone())),
+        };
+        let vaa_data = vaa.serialize().unwrap();
+
+        let (posted_key, _) = Bridge::derive_key(
+            &program_id,
+            &Bridge::derive_posted_vaa_seeds(&bridge_key, &accounts[8].key),
+        )
+        .unwrap();
+        let mut posted = TestAccount::new(
+            posted_key,
+            program_id,
+            0,
+            vec![0; PostedVAA::size(vaa_data.len())],
+        );
+        let mut other = TestAccount::new(
+            Pubkey::new_unique(),
+            program_id,
+            0,
+            vec![0; PostedVAA::size(vaa_data.len())],
+        );
+        let sig_key = Pubkey::new_unique();
+        let post = |accounts: &mut Vec<TestAccount>, posted: &mut TestAccount, data: &VAAData| {
+            let mut infos: Vec<_> = accounts.iter_mut().map(|a| a.info()).collect();
+            infos.push(posted.info());
+            let proposal_and_posted = [infos[8].clone(), infos[13].clone()];
+            Bridge::process_vaa_transfer_post(
+                &program_id,
+                &infos,
+                &mut proposal_and_posted.iter(),
+                &infos[7],
+                &infos[10],
+                &vaa,
+                &b,
+                data.clone(),
+                &sig_key,
+            )
+        };
+
+        assert_eq!(
+            post(&mut accounts, &mut other, &vaa_data),
+            Err(Error::InvalidDerivedAccount.into())
+        );
+        let mut too_long = vaa_data.clone();
+        too_long.resize(MAX_VAA_SIZE + 1, 0);
+        assert_eq!(
+            post(&mut accounts, &mut posted, &too_long),
+            Err(Error::VAATooLong.into())
+        );
+        assert_eq!(post(&mut accounts, &mut posted, &vaa_data), Ok(()));
+        assert_eq!(
+            post(&mut accounts, &mut posted, &vaa_data),
+            Err(Error::VAAAlreadySubmitted.into())
+        );
+
+        let proposal = accounts[8].state::<TransferOutProposal>();
+        assert_eq!(proposal.vaa_account, posted_key);
+        assert_eq!(proposal.vaa_time, 2000);
+        assert_eq!(proposal.signature_account, sig_key);
+        let (header, body) = Bridge::unpack_posted_vaa(&posted.data).unwrap();
+        assert!(header.is_initialized);
+        assert_eq!(header.proposal, accounts[8].key);
+        assert_eq!(header.len as usize, vaa_data.len());
+        assert_eq!(body, vaa_data.as_slice());
+    }
+
+    #[test]
+    fn transfer_sequences_increment_per_sender() {
+        let program_id = Pubkey::new_unique();
+        let mut bridge = bridge_account(&program_id, 0, 0);
+        let mut payer =
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), 0, vec![]).signer();
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let sequence_account = |sender: &Pubkey, sequence| {
+            derived_account(
+                &program_id,
+                &Bridge::derive_sequence_seeds(&bridge.key, sender),
+                |bump| TransferSequence {
+                    sequence,
+                    is_initialized: true,
+                    bump,
+                },
+            )
+        };
+        let mut alice_sequence = sequence_account(&alice, 0);
+        let mut bob_sequence = sequence_account(&bob, 7);
+
+        let bridge_info = bridge.info();
+        let payer_info = payer.info();
+        let alice_info = alice_sequence.info();
+        let bob_info = bob_sequence.info();
+        let take = |sequence_info: &AccountInfo, sender: &Pubkey| {
+            Bridge::take_transfer_sequence(
+                &program_id,
+                &[],
+                &bridge_info,
+                sequence_info,
+                sender,
+                &payer_info,
+            )
+        };
+        assert_eq!(take(&alice_info, &alice), Ok(0));
+        assert_eq!(take(&alice_info, &alice), Ok(1));
+        assert_eq!(take(&bob_info, &bob), Ok(7));
+        assert_eq!(take(&alice_info, &alice), Ok(2));
+        // A sender can't take the sequence numbers of another sender
+        assert_eq!(
+            take(&bob_info, &alice),
+            Err(Error::InvalidDerivedAccount.into())
+        );
+        assert_eq!(take(&bob_info, &bob), Ok(8));
+    }
+
+    thread_local! {
+        /// Instructions invoked via CPI on the current thread
+        static INVOKED: RefCell<Vec<Instruction>> = RefCell::new(vec![]);
+    }
+
+    /// Syscall stubs that record the instructions invoked via CPI instead of dropping them
+    struct RecordingStubs;
+
+    impl program_stubs::SyscallStubs for RecordingStubs {
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            _signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            INVOKED.with(|i| i.borrow_mut().push(instruction.clone()));
+            if instruction.program_id == associated_token::id() {
+                create_associated_token_account(instruction, account_infos);
+            }
+            Ok(())
+        }
+    }
+
+    /// Does what the associated token account program does for its `Create` instruction: the
+    /// payer funds a new token account of the wallet
+    fn create_associated_token_account(instruction: &Instruction, account_infos: &[AccountInfo]) {
+        let account = |i: usize| {
+            account_infos
+                .iter()
+                .find(|a| *a.key == instruction.accounts[i].pubkey)
+                .unwrap()
+        };
+        let (payer, token_account, wallet, mint) = (account(0), account(1), account(2), account(3));
+
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account {
+            mint: *mint.key,
+            owner: *wallet.key,
+            state: spl_token::state::AccountState::Initialized,
+            ..spl_token::state::Account::default()
+        }
+        .pack_into_slice(&mut data);
+        *RefCell::borrow_mut(&token_account.data) = Box::leak(data.into_boxed_slice());
+        let rent = Rent::default().minimum_balance(spl_token::state::Account::LEN);
+        Bridge::transfer_sol(payer, token_account, rent).unwrap();
+    }
+
+    /// Returns the instructions invoked via CPI while running `f`
+    fn invoked_instructions(f: impl FnOnce()) -> Vec<Instruction> {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(RecordingStubs));
+        });
+        INVOKED.with(|i| i.borrow_mut().clear());
+        f();
+        INVOKED.with(|i| std::mem::replace(&mut *i.borrow_mut(), vec![]))
+    }
+
+    #[test]
+    fn transfer_in_creates_associated_token_account_of_wallet() {
+        let program_id = Pubkey::new_unique();
+        let mut bridge = bridge_account(&program_id, Bridge::MIN_BRIDGE_BALANCE, 0);
+        let bridge_key = bridge.key;
+        let bridge_state = bridge.state::<Bridge>();
+        let wallet = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let ata = associated_token::get_associated_token_address(&wallet, &mint);
+        let custody = Bridge::derive_custody_id(&program_id, &bridge_key, &mint).unwrap();
+        let rent = Rent::default().minimum_balance(spl_token::state::Account::LEN);
+        let transfer = |target: &Pubkey| BodyTransfer {
+            nonce: 1,
+            source_chain: CHAIN_ID_ETHEREUM,
+            target_chain: CHAIN_ID_SOLANA,
+            source_address: ethereum_address(),
+            target_address: target.to_bytes(),
+            asset: AssetMeta {
+                address: mint.to_bytes(),
+                chain: CHAIN_ID_SOLANA,
+                decimals: 9,
+            },
+            amount: U256::from(100),
+        };
+        let (to_wallet, to_other_wallet) = (transfer(&wallet), transfer(&Pubkey::new_unique()));
+
+        let mut token_program = TestAccount::new(spl_token::id(), Pubkey::default(), 0, vec![]);
+        let mut mint_account = mint_account(mint);
+        // The wallet has no associated token account yet
+        let mut destination =
+            TestAccount::new(ata, solana_program::system_program::id(), 0, vec![]);
+        let mut custody_account = token_account(custody, &mint, &bridge_key, 100);
+        let mut wallet_account =
+            TestAccount::new(wallet, solana_program::system_program::id(), 0, vec![]);
+        let mut associated_token_program =
+            TestAccount::new(associated_token::id(), Pubkey::default(), 0, vec![]);
+        let mut payer = TestAccount::new(
+            Pubkey::new_unique(),
+            solana_program::system_program::id(),
+            rent,
+            vec![],
+        )
+        .signer();
+        let payer_key = payer.key;
+
+        let bridge_info = bridge.info();
+        let accounts = [
+            token_program.info(),
+            mint_account.info(),
+            destination.info(),
+            custody_account.info(),
+            wallet_account.info(),
+            associated_token_program.info(),
+            payer.info(),
+        ];
+        let transfer_in = |b: &BodyTransfer| {
+            let mut result = None;
+            let invoked = invoked_instructions(|| {
+                result = Some(Bridge::process_vaa_transfer(
+                    &program_id,
+                    &accounts,
+                    &mut accounts[..6].iter(),
+                    &bridge_info,
+                    &accounts[6],
+                    &bridge_state,
+                    b,
+                ))
+            });
+            (result.unwrap(), invoked)
+        };
+        let custody_transfer = spl_token::instruction::transfer(
+            &spl_token::id(),
+            &custody,
+            &ata,
+            &bridge_key,
+            &[],
+            100,
+        )
+        .unwrap();
+
+        let (result, invoked) = transfer_in(&to_other_wallet);
+        assert_eq!(result, Err(Error::InvalidDestination.into()));
+        assert!(invoked.is_empty());
+
+        // The first redemption creates the account, the payer advances its rent
+        let (result, invoked) = transfer_in(&to_wallet);
+        assert_eq!(result, Ok(rent));
+        assert_eq!(
+            invoked,
+            vec![
+                associated_token::create_associated_token_account(&payer_key, &wallet, &mint),
+                custody_transfer.clone(),
+            ]
+        );
+        assert_eq!(accounts[6].lamports(), 0);
+        let created = Bridge::token_account_deserialize(&accounts[2]).unwrap();
+        assert_eq!((created.mint, created.owner), (mint, wallet));
+
+        // Later redemptions use the existing account
+        let (result, invoked) = transfer_in(&to_wallet);
+        assert_eq!(result, Ok(0));
+        assert_eq!(invoked, vec![custody_transfer]);
+    }
+
+    #[test]
+    fn transfer_out_cpi_requires_signing_authority() {
+        let program_id = Pubkey::new_unique();
+        let bridge = bridge_account(&program_id, Bridge::MIN_BRIDGE_BALANCE, 0);
+        let bridge_key = bridge.key;
+        let sender = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut t = TransferOutPayload {
+            amount: U256::from(100),
+            chain_id: 2,
+            asset: AssetMeta {
+                address: mint.to_bytes(),
+                chain: CHAIN_ID_SOLANA,
+                decimals: 9,
+            },
+            target: ethereum_address(),
+            custody_bump: 0,
+        };
+        let mut accounts =
+            native_transfer_out_accounts(&program_id, bridge, &sender, &mint, 0, &mut t);
+        // The sender is owned by a PDA of the invoking program
+        let (authority, _) = Pubkey::find_program_address(&[b"authority"], &Pubkey::new_unique());
+        accounts.push(TestAccount::new(authority, Pubkey::default(), 0, vec![]));
+        let (payer, custody) = (accounts[10].key, accounts[12].key);
+
+        let transfer = |accounts: &mut Vec<TestAccount>| {
+            let infos: Vec<_> = accounts.iter_mut().map(|a| a.info()).collect();
+            Bridge::process_transfer_native_out(&program_id, &infos, &t, true)
+        };
+        assert_eq!(
+            transfer(&mut accounts),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+
+        // The invoking program signs for its PDA with `invoke_signed`
+        accounts[13].is_signer = true;
+        let invoked = invoked_instructions(|| assert_eq!(transfer(&mut accounts), Ok(())));
+        assert_eq!(
+            invoked[0],
+            system_instruction::transfer(&payer, &bridge_key, Bridge::transfer_fee())
+        );
+        let lock = invoked
+            .iter()
+            .find(|ix| ix.program_id == spl_token::id())
+            .unwrap();
+        assert_eq!(
+            spl_token::instruction::TokenInstruction::unpack(&lock.data),
+            Ok(spl_token::instruction::TokenInstruction::Transfer { amount: 100 })
+        );
+        assert_eq!(lock.accounts[0].pubkey, sender);
+        assert_eq!(lock.accounts[1].pubkey, custody);
+        assert_eq!(lock.accounts[2], AccountMeta::new_readonly(authority, true));
+
+        let proposal = accounts[8].state::<TransferOutProposal>();
+        assert!(proposal.is_initialized);
+        assert_eq!(proposal.source_address, sender.to_bytes());
+    }
+}
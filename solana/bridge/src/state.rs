@@ -8,7 +8,7 @@ use zerocopy::AsBytes;
 
 use crate::{
     error::Error,
-    instruction::{ForeignAddress, MAX_LEN_GUARDIAN_KEYS, MAX_VAA_SIZE},
+    instruction::{ForeignAddress, MAX_LEN_GUARDIAN_KEYS},
     vaa::BodyTransfer,
 };
 use solana_program::program_pack::Pack;
@@ -41,6 +41,9 @@ pub struct GuardianSet {
 
     /// Is `true` if this structure has been initialized.
     pub is_initialized: bool,
+
+    /// bump seed of the derived address of this account
+    pub bump: u8,
 }
 
 impl IsInitialized for GuardianSet {
@@ -51,6 +54,7 @@ impl IsInitialized for GuardianSet {
 
 /// proposal to transfer tokens to a foreign chain
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub struct TransferOutProposal {
     /// amount to transfer
     pub amount: U256,
@@ -62,11 +66,10 @@ pub struct TransferOutProposal {
     pub foreign_address: ForeignAddress,
     /// asset that is being transferred
     pub asset: AssetMeta,
-    /// nonce of the transfer
-    pub nonce: u32,
-    /// vaa to unlock the tokens on the foreign chain
-    /// it is +1 byte long to make space for the termination byte
-    pub vaa: [u8; MAX_VAA_SIZE + 1],
+    /// sequence number of the transfer, used as the nonce of the VAA
+    pub sequence: u32,
+    /// account holding the vaa to unlock the tokens on the foreign chain
+    pub vaa_account: Pubkey,
     /// time the vaa was submitted
     pub vaa_time: u32,
     /// time the lockup was created
@@ -75,9 +78,14 @@ pub struct TransferOutProposal {
     pub poke_counter: u8,
     /// Account where signatures are stored
     pub signature_account: Pubkey,
+    /// the transfer was reverted by the guardians and refunded to the source address
+    pub reverted: bool,
 
     /// Is `true` if this structure has been initialized.
     pub is_initialized: bool,
+
+    /// bump seed of the derived address of this account
+    pub bump: u8,
 }
 
 impl IsInitialized for TransferOutProposal {
@@ -89,12 +97,134 @@ impl IsInitialized for TransferOutProposal {
 impl TransferOutProposal {
     pub fn matches_vaa(&self, b: &BodyTransfer) -> bool {
         return b.amount == self.amount
+            && b.nonce == self.sequence
             && b.target_address == self.foreign_address
             && b.target_chain == self.to_chain_id
             && b.asset == self.asset;
     }
 }
 
+/// sequence counter for transfers out of a sender account
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TransferSequence {
+    /// sequence number assigned to the next transfer
+    pub sequence: u32,
+
+    /// Is `true` if this structure has been initialized.
+    pub is_initialized: bool,
+
+    /// bump seed of the derived address of this account
+    pub bump: u8,
+}
+
+impl IsInitialized for TransferSequence {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+/// VAA posted for data availability.
+/// The header is followed by `len` bytes of VAA data in the same account.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PostedVAA {
+    /// length of the VAA data following the header
+    pub len: u32,
+    /// proposal the VAA was posted for
+    pub proposal: Pubkey,
+
+    /// Is `true` if this structure has been initialized.
+    pub is_initialized: bool,
+
+    /// bump seed of the derived address of this account
+    pub bump: u8,
+}
+
+impl IsInitialized for PostedVAA {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl PostedVAA {
+    /// Size of an account holding a VAA of `vaa_len` bytes
+    pub fn size(vaa_len: usize) -> usize {
+        size_of::<PostedVAA>() + vaa_len
+    }
+}
+
+/// Account staging the converted data of a program account while `Migrate` recreates the account
+/// with the layout of this version.
+/// The header is followed by `len` bytes of account data and `seeds_len` bytes of the seeds of the
+/// account, each prefixed by its length.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MigrationStage {
+    /// lamports the account held when it was staged
+    pub lamports: u64,
+    /// account the data is staged for
+    pub account: Pubkey,
+    /// length of the account data following the header
+    pub len: u32,
+    /// length of the seeds following the account data
+    pub seeds_len: u32,
+
+    /// Is `true` if this structure has been initialized.
+    pub is_initialized: bool,
+
+    /// bump seed of the derived address of this account
+    pub bump: u8,
+}
+
+impl IsInitialized for MigrationStage {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl MigrationStage {
+    /// Size of an account staging `len` bytes of account data derived from `seeds`
+    pub fn size(len: usize, seeds: &[Vec<u8>]) -> usize {
+        size_of::<MigrationStage>() + len + Self::seeds_len(seeds)
+    }
+
+    /// Length of `seeds` when stored in a stage
+    pub fn seeds_len(seeds: &[Vec<u8>]) -> usize {
+        seeds.iter().map(|s| 1 + s.len()).sum()
+    }
+
+    /// Stores `seeds` in `output`, each prefixed by its length
+    pub fn write_seeds(seeds: &[Vec<u8>], output: &mut [u8]) -> Result<(), ProgramError> {
+        let mut offset = 0;
+        for seed in seeds {
+            let end = offset + 1 + seed.len();
+            if seed.len() > u8::MAX as usize || output.len() < end {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            output[offset] = seed.len() as u8;
+            output[offset + 1..end].copy_from_slice(seed);
+            offset = end;
+        }
+        Ok(())
+    }
+
+    /// Reads seeds stored by `write_seeds`
+    pub fn read_seeds(input: &[u8]) -> Result<Vec<Vec<u8>>, ProgramError> {
+        let mut seeds = Vec::new();
+        let mut offset = 0;
+        while offset < input.len() {
+            let end = offset + 1 + input[offset] as usize;
+            if input.len() < end {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            seeds.push(input[offset + 1..end].to_vec());
+            offset = end;
+        }
+        Ok(seeds)
+    }
+}
+
 /// record of a claimed VAA
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
@@ -106,6 +236,9 @@ pub struct ClaimedVAA {
 
     /// Is `true` if this structure has been initialized.
     pub is_initialized: bool,
+
+    /// bump seed of the derived address of this account
+    pub bump: u8,
 }
 
 impl IsInitialized for ClaimedVAA {
@@ -114,6 +247,39 @@ impl IsInitialized for ClaimedVAA {
     }
 }
 
+/// ledger of the costs a guardian advanced by submitting VAAs and of its reimbursements.
+///
+/// Submitting VAAs is permissionless and guardian keys can't sign Solana transactions, so the
+/// ledger is kept per fee payer. Any submitter is reimbursed the costs it advanced, which carries
+/// no profit for front-runners.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GuardianLedger {
+    /// fee payer the guardian submits VAAs with
+    pub guardian: Pubkey,
+    /// number of VAAs submitted
+    pub submissions: u64,
+    /// rent paid for bridge accounts created by the submissions that was not subsidized by the
+    /// bridge
+    pub rent_advanced: u64,
+    /// reimbursements paid out to the guardian
+    pub refunds_received: u64,
+    /// reimbursements that could not be paid out yet because the bridge lacked funds
+    pub owed: u64,
+
+    /// Is `true` if this structure has been initialized.
+    pub is_initialized: bool,
+
+    /// bump seed of the derived address of this account
+    pub bump: u8,
+}
+
+impl IsInitialized for GuardianLedger {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
 /// metadata tracking for wrapped assets
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
@@ -122,9 +288,16 @@ pub struct WrappedAssetMeta {
     pub chain: u8,
     /// address of the asset on the native chain
     pub address: ForeignAddress,
+    /// minting of the wrapped asset was halted by the guardians
+    pub minting_halted: bool,
 
     /// Is `true` if this structure has been initialized.
     pub is_initialized: bool,
+
+    /// bump seed of the derived address of this account
+    pub bump: u8,
+    /// bump seed of the derived address of the wrapped mint
+    pub mint_bump: u8,
 }
 
 impl IsInitialized for WrappedAssetMeta {
@@ -160,6 +333,22 @@ pub struct BridgeConfig {
     pub token_program: Pubkey,
 }
 
+/// Record of the last contract upgrade
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UpgradeRecord {
+    /// hash of the program that was replaced, zero if it was not deployed by an upgrade
+    pub old_buffer_hash: [u8; 32],
+    /// hash of the program that was deployed
+    pub new_buffer_hash: [u8; 32],
+    /// body hash of the VAA that approved the upgrade
+    pub vaa_hash: [u8; 32],
+    /// time of the upgrade, zero if no upgrade happened yet
+    pub upgrade_time: u32,
+    /// Is `true` once the upgraded program has migrated the program accounts
+    pub migrated: bool,
+}
+
 /// Bridge state.
 #[repr(C)]
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -170,8 +359,17 @@ pub struct Bridge {
     /// read-only config parameters for a bridge instance.
     pub config: BridgeConfig,
 
+    /// the last upgrade of the program
+    pub last_upgrade: UpgradeRecord,
+
+    /// reimbursements owed to guardians that have not been paid out yet
+    pub owed: u64,
+
     /// Is `true` if this structure has been initialized.
     pub is_initialized: bool,
+
+    /// bump seed of the derived address of this account
+    pub bump: u8,
 }
 
 impl IsInitialized for Bridge {
@@ -193,8 +391,14 @@ pub struct SignatureState {
     /// index of the guardian set
     pub guardian_set_index: u32,
 
+    /// time the first signature was verified
+    pub creation_time: u32,
+
     /// Is `true` if this structure has been initialized.
     pub is_initialized: bool,
+
+    /// bump seed of the derived address of this account
+    pub bump: u8,
 }
 
 impl IsInitialized for SignatureState {
@@ -254,6 +458,65 @@ impl Bridge {
         #[allow(clippy::cast_ptr_alignment)]
             Ok(unsafe { &*(&input[0] as *const u8 as *const T) })
     }
+
+    /// Unpacks a posted VAA header and the data following it without checking that the state is
+    /// initialized.
+    pub fn unpack_posted_vaa_unchecked(
+        input: &mut [u8],
+    ) -> Result<(&mut PostedVAA, &mut [u8]), ProgramError> {
+        if input.len() < size_of::<PostedVAA>() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (header, data) = input.split_at_mut(size_of::<PostedVAA>());
+        Ok((Self::unpack_unchecked(header)?, data))
+    }
+
+    /// Unpacks a posted VAA header and its VAA data while assuring that the state is initialized.
+    pub fn unpack_posted_vaa(input: &[u8]) -> Result<(&PostedVAA, &[u8]), ProgramError> {
+        if input.len() < size_of::<PostedVAA>() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (header, data) = input.split_at(size_of::<PostedVAA>());
+        let header: &PostedVAA = Self::unpack_immutable(header)?;
+        if data.len() < header.len as usize {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok((header, &data[..header.len as usize]))
+    }
+
+    /// Unpacks a migration stage header, the staged account data and the staged seeds without
+    /// checking that the state is initialized.
+    pub fn unpack_migration_stage_unchecked(
+        input: &mut [u8],
+    ) -> Result<(&mut MigrationStage, &mut [u8], &mut [u8]), ProgramError> {
+        if input.len() < size_of::<MigrationStage>() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (header, data) = input.split_at_mut(size_of::<MigrationStage>());
+        let header: &mut MigrationStage = Self::unpack_unchecked(header)?;
+        if data.len() != header.len as usize + header.seeds_len as usize {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (data, seeds) = data.split_at_mut(header.len as usize);
+        Ok((header, data, seeds))
+    }
+
+    /// Unpacks a migration stage header, the staged account data and the staged seeds while
+    /// assuring that the state is initialized.
+    pub fn unpack_migration_stage(
+        input: &[u8],
+    ) -> Result<(&MigrationStage, &[u8], &[u8]), ProgramError> {
+        if input.len() < size_of::<MigrationStage>() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (header, data) = input.split_at(size_of::<MigrationStage>());
+        let header: &MigrationStage = Self::unpack_immutable(header)?;
+        if data.len() != header.len as usize + header.seeds_len as usize {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (data, seeds) = data.split_at(header.len as usize);
+        Ok((header, data, seeds))
+    }
 }
 
 /// Implementation of derivations
@@ -291,7 +554,7 @@ impl Bridge {
         target_chain: u8,
         target_address: ForeignAddress,
         sender: ForeignAddress,
-        nonce: u32,
+        sequence: u32,
     ) -> Vec<Vec<u8>> {
         vec![
             "transfer".as_bytes().to_vec(),
@@ -301,7 +564,43 @@ impl Bridge {
             target_chain.as_bytes().to_vec(),
             target_address.as_bytes().to_vec(),
             sender.as_bytes().to_vec(),
-            nonce.as_bytes().to_vec(),
+            sequence.as_bytes().to_vec(),
+        ]
+    }
+
+    /// Calculates derived seeds for the transfer sequence of a sender
+    pub fn derive_sequence_seeds(bridge_key: &Pubkey, sender: &Pubkey) -> Vec<Vec<u8>> {
+        vec![
+            "sequence".as_bytes().to_vec(),
+            bridge_key.to_bytes().to_vec(),
+            sender.to_bytes().to_vec(),
+        ]
+    }
+
+    /// Calculates derived seeds for a posted VAA
+    pub fn derive_posted_vaa_seeds(bridge_key: &Pubkey, proposal: &Pubkey) -> Vec<Vec<u8>> {
+        vec![
+            "vaa".as_bytes().to_vec(),
+            bridge_key.to_bytes().to_vec(),
+            proposal.to_bytes().to_vec(),
+        ]
+    }
+
+    /// Calculates derived seeds for the stage of an account migrated by `Migrate`
+    pub fn derive_migration_stage_seeds(bridge_key: &Pubkey, account: &Pubkey) -> Vec<Vec<u8>> {
+        vec![
+            "migration".as_bytes().to_vec(),
+            bridge_key.to_bytes().to_vec(),
+            account.to_bytes().to_vec(),
+        ]
+    }
+
+    /// Calculates derived seeds for the ledger of a guardian
+    pub fn derive_ledger_seeds(bridge_key: &Pubkey, guardian: &Pubkey) -> Vec<Vec<u8>> {
+        vec![
+            "ledger".as_bytes().to_vec(),
+            bridge_key.to_bytes().to_vec(),
+            guardian.to_bytes().to_vec(),
         ]
     }
 
@@ -351,11 +650,58 @@ impl Bridge {
         ]
     }
 
+    /// Calculates the address of the given seeds and bump seed using a single derivation
+    pub fn derive_key_with_bump(
+        program_id: &Pubkey,
+        seeds: &Vec<Vec<u8>>,
+        bump: u8,
+    ) -> Result<Pubkey, Error> {
+        let bump = [bump];
+        let mut s: Vec<&[u8]> = seeds.iter().map(|item| item.as_slice()).collect();
+        s.push(&bump);
+        Pubkey::create_program_address(&s, program_id).map_err(|_| Error::InvalidDerivedAccount)
+    }
+
+    /// Checks that `key` was derived from the given seeds and bump seed
+    pub fn check_derived_key(
+        program_id: &Pubkey,
+        key: &Pubkey,
+        seeds: &Vec<Vec<u8>>,
+        bump: u8,
+    ) -> Result<(), Error> {
+        if Self::derive_key_with_bump(program_id, seeds, bump)? != *key {
+            return Err(Error::InvalidDerivedAccount);
+        }
+        Ok(())
+    }
+
+    /// Checks that `key` is the bridge account of `program_id`, i.e. the account this state was
+    /// loaded from is not another account of the program with the same layout
+    pub fn check_bridge_key(&self, program_id: &Pubkey, key: &Pubkey) -> Result<(), Error> {
+        Self::check_derived_key(program_id, key, &Self::derive_bridge_seeds(), self.bump)
+    }
+
+    /// Seeds to sign for the bridge account including its bump seed
+    pub fn signer_seeds(&self) -> Vec<Vec<u8>> {
+        let mut seeds = Self::derive_bridge_seeds();
+        seeds.push(vec![self.bump]);
+        seeds
+    }
+
     /// Calculates a derived address for this program
     pub fn derive_bridge_id(program_id: &Pubkey) -> Result<Pubkey, Error> {
         Ok(Self::derive_key(program_id, &Self::derive_bridge_seeds())?.0)
     }
 
+    /// Calculates a derived address for the stage of an account migrated by `Migrate`
+    pub fn derive_migration_stage_id(
+        program_id: &Pubkey,
+        bridge_key: &Pubkey,
+        account: &Pubkey,
+    ) -> Result<Pubkey, Error> {
+        Ok(Self::derive_key(program_id, &Self::derive_migration_stage_seeds(bridge_key, account))?.0)
+    }
+
     /// Calculates a derived address for a custody account
     pub fn derive_custody_id(
         program_id: &Pubkey,
@@ -365,6 +711,17 @@ impl Bridge {
         Ok(Self::derive_key(program_id, &Self::derive_custody_seeds(bridge, mint))?.0)
     }
 
+    /// Calculates a derived address for a custody account and returns it with its bump seed.
+    /// The bump seed is passed to the instructions using the custody account.
+    pub fn derive_custody_id_and_bump(
+        program_id: &Pubkey,
+        bridge: &Pubkey,
+        mint: &Pubkey,
+    ) -> Result<(Pubkey, u8), Error> {
+        let (key, seeds) = Self::derive_key(program_id, &Self::derive_custody_seeds(bridge, mint))?;
+        Ok((key, seeds.last().map(|b| b[0]).unwrap_or_default()))
+    }
+
     /// Calculates a derived address for a claim account
     pub fn derive_claim_id(
         program_id: &Pubkey,
@@ -420,7 +777,7 @@ impl Bridge {
         target_chain: u8,
         target_address: ForeignAddress,
         user: ForeignAddress,
-        slot: u32,
+        sequence: u32,
     ) -> Result<Pubkey, Error> {
         Ok(Self::derive_key(
             program_id,
@@ -431,12 +788,39 @@ impl Bridge {
                 target_chain,
                 target_address,
                 user,
-                slot,
+                sequence,
             ),
         )?
             .0)
     }
 
+    /// Calculates a derived address for the transfer sequence of a sender
+    pub fn derive_sequence_id(
+        program_id: &Pubkey,
+        bridge_key: &Pubkey,
+        sender: &Pubkey,
+    ) -> Result<Pubkey, Error> {
+        Ok(Self::derive_key(program_id, &Self::derive_sequence_seeds(bridge_key, sender))?.0)
+    }
+
+    /// Calculates a derived address for a posted VAA
+    pub fn derive_posted_vaa_id(
+        program_id: &Pubkey,
+        bridge_key: &Pubkey,
+        proposal: &Pubkey,
+    ) -> Result<Pubkey, Error> {
+        Ok(Self::derive_key(program_id, &Self::derive_posted_vaa_seeds(bridge_key, proposal))?.0)
+    }
+
+    /// Calculates a derived address for the ledger of a guardian
+    pub fn derive_ledger_id(
+        program_id: &Pubkey,
+        bridge_key: &Pubkey,
+        guardian: &Pubkey,
+    ) -> Result<Pubkey, Error> {
+        Ok(Self::derive_key(program_id, &Self::derive_ledger_seeds(bridge_key, guardian))?.0)
+    }
+
     /// Calculates derived address for a signature account
     pub fn derive_signature_id<'a>(
         program_id: &Pubkey,
@@ -458,24 +842,22 @@ impl Bridge {
         Ok(Self::find_program_address(seeds, program_id))
     }
 
+    /// Finds the canonical derived address of `seeds`, i.e. the one with the highest valid bump
+    /// seed, and returns it with the seeds including the bump seed
     pub fn find_program_address(
         seeds: &Vec<Vec<u8>>,
         program_id: &Pubkey,
     ) -> (Pubkey, Vec<Vec<u8>>) {
-        let mut nonce = [255u8];
-        for _ in 0..std::u8::MAX {
-            {
+        let bumps: Vec<u8> = (1..=u8::MAX).rev().collect();
+        let mut s: Vec<&[u8]> = seeds.iter().map(|item| item.as_slice()).collect();
+        for i in 0..bumps.len() {
+            s.push(&bumps[i..i + 1]);
+            if let Ok(address) = Pubkey::create_program_address(&s, program_id) {
                 let mut seeds_with_nonce = seeds.to_vec();
-                seeds_with_nonce.push(nonce.to_vec());
-                let s: Vec<_> = seeds_with_nonce
-                    .iter()
-                    .map(|item| item.as_slice())
-                    .collect();
-                if let Ok(address) = Pubkey::create_program_address(&s, program_id) {
-                    return (address, seeds_with_nonce);
-                }
+                seeds_with_nonce.push(vec![bumps[i]]);
+                return (address, seeds_with_nonce);
             }
-            nonce[0] -= 1;
+            s.pop();
         }
         panic!("Unable to find a viable program address nonce");
     }
@@ -483,6 +865,16 @@ impl Bridge {
     /// Tx fee of Signature checks and PostVAA (see docs for calculation)
     pub const VAA_TX_FEE: u64 = 18 * 10000;
 
+    /// Period after the expiration of a guardian set after which its account can be closed
+    pub const GUARDIAN_SET_CLOSE_DELAY: u32 = 24 * 60 * 60;
+
+    /// Period after a lockup after which the guardians can revert it if no VAA was posted
+    pub const TRANSFER_REVERT_TIMEOUT: u32 = 7 * 24 * 60 * 60;
+
+    /// Period after the creation of a signature state after which its account can be evicted.
+    /// Signatures of outbound transfers are read by users to redeem the VAA on the foreign chain.
+    pub const SIGNATURE_EVICTION_TIME: u32 = 30 * 24 * 60 * 60;
+
     pub fn transfer_fee() -> u64 {
         // Pay for 2 signature state and Claimed VAA rents + 2 * guardian tx fees
         // This will pay for this transfer and ~10 inbound ones
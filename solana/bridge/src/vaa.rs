@@ -131,6 +131,10 @@ pub enum VAABody {
     UpdateGuardianSet(BodyUpdateGuardianSet),
     Transfer(BodyTransfer),
     UpgradeContract(BodyContractUpgrade),
+    TreasuryWithdrawal(BodyTreasuryWithdrawal),
+    FreezeAccount(BodyFreezeAccount),
+    HaltMinting(BodyHaltMinting),
+    RevertTransfer(BodyRevertTransfer),
 }
 
 impl VAABody {
@@ -138,6 +142,10 @@ impl VAABody {
         match self {
             VAABody::UpdateGuardianSet(_) => 0x01,
             VAABody::UpgradeContract(_) => 0x02,
+            VAABody::TreasuryWithdrawal(_) => 0x03,
+            VAABody::FreezeAccount(_) => 0x04,
+            VAABody::HaltMinting(_) => 0x05,
+            VAABody::RevertTransfer(_) => 0x06,
             VAABody::Transfer(_) => 0x10,
         }
     }
@@ -151,6 +159,12 @@ impl VAABody {
                 VAABody::UpdateGuardianSet(BodyUpdateGuardianSet::deserialize(&mut payload_data)?)
             }
             0x02 => VAABody::UpgradeContract(BodyContractUpgrade::deserialize(&mut payload_data)?),
+            0x03 => VAABody::TreasuryWithdrawal(BodyTreasuryWithdrawal::deserialize(
+                &mut payload_data,
+            )?),
+            0x04 => VAABody::FreezeAccount(BodyFreezeAccount::deserialize(&mut payload_data)?),
+            0x05 => VAABody::HaltMinting(BodyHaltMinting::deserialize(&mut payload_data)?),
+            0x06 => VAABody::RevertTransfer(BodyRevertTransfer::deserialize(&mut payload_data)?),
             0x10 => VAABody::Transfer(BodyTransfer::deserialize(&mut payload_data)?),
             _ => {
                 return Err(Error::InvalidVAAAction);
@@ -165,6 +179,10 @@ impl VAABody {
             VAABody::Transfer(b) => b.serialize(),
             VAABody::UpdateGuardianSet(b) => b.serialize(),
             VAABody::UpgradeContract(b) => b.serialize(),
+            VAABody::TreasuryWithdrawal(b) => b.serialize(),
+            VAABody::FreezeAccount(b) => b.serialize(),
+            VAABody::HaltMinting(b) => b.serialize(),
+            VAABody::RevertTransfer(b) => b.serialize(),
         }
     }
 }
@@ -192,6 +210,36 @@ pub struct BodyContractUpgrade {
     pub buffer: Pubkey,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct BodyTreasuryWithdrawal {
+    pub chain_id: u8,
+    pub recipient: Pubkey,
+    pub amount: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BodyFreezeAccount {
+    pub chain_id: u8,
+    pub mint: Pubkey,
+    pub token_account: Pubkey,
+    pub freeze: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BodyHaltMinting {
+    pub chain_id: u8,
+    pub mint: Pubkey,
+    pub halt: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BodyRevertTransfer {
+    pub transfer: BodyTransfer,
+    /// unix time at which the guardians verified that the transfer was not redeemed on its
+    /// target chain
+    pub target_check_time: u32,
+}
+
 impl BodyContractUpgrade {
     fn deserialize(data: &mut Cursor<&Vec<u8>>) -> Result<BodyContractUpgrade, Error> {
         let chain_id = data.read_u8()?;
@@ -213,6 +261,81 @@ impl BodyContractUpgrade {
     }
 }
 
+impl BodyTreasuryWithdrawal {
+    fn deserialize(data: &mut Cursor<&Vec<u8>>) -> Result<BodyTreasuryWithdrawal, Error> {
+        let chain_id = data.read_u8()?;
+        let mut key: [u8; 32] = [0; 32];
+        data.read_exact(&mut key[..])?;
+        let amount = data.read_u64::<BigEndian>()?;
+
+        Ok(BodyTreasuryWithdrawal {
+            chain_id,
+            recipient: Pubkey::new(&key[..]),
+            amount,
+        })
+    }
+
+    fn serialize(&self) -> Result<Vec<u8>, Error> {
+        let mut v: Cursor<Vec<u8>> = Cursor::new(Vec::new());
+        v.write_u8(self.chain_id)?;
+        v.write(&self.recipient.to_bytes())?;
+        v.write_u64::<BigEndian>(self.amount)?;
+
+        Ok(v.into_inner())
+    }
+}
+
+impl BodyFreezeAccount {
+    fn deserialize(data: &mut Cursor<&Vec<u8>>) -> Result<BodyFreezeAccount, Error> {
+        let chain_id = data.read_u8()?;
+        let mut mint: [u8; 32] = [0; 32];
+        data.read_exact(&mut mint[..])?;
+        let mut token_account: [u8; 32] = [0; 32];
+        data.read_exact(&mut token_account[..])?;
+        let freeze = data.read_u8()? != 0;
+
+        Ok(BodyFreezeAccount {
+            chain_id,
+            mint: Pubkey::new(&mint[..]),
+            token_account: Pubkey::new(&token_account[..]),
+            freeze,
+        })
+    }
+
+    fn serialize(&self) -> Result<Vec<u8>, Error> {
+        let mut v: Cursor<Vec<u8>> = Cursor::new(Vec::new());
+        v.write_u8(self.chain_id)?;
+        v.write(&self.mint.to_bytes())?;
+        v.write(&self.token_account.to_bytes())?;
+        v.write_u8(self.freeze as u8)?;
+
+        Ok(v.into_inner())
+    }
+}
+
+impl BodyHaltMinting {
+    fn deserialize(data: &mut Cursor<&Vec<u8>>) -> Result<BodyHaltMinting, Error> {
+        let chain_id = data.read_u8()?;
+        let mut key: [u8; 32] = [0; 32];
+        data.read_exact(&mut key[..])?;
+        let halt = data.read_u8()? != 0;
+
+        Ok(BodyHaltMinting {
+            chain_id,
+            mint: Pubkey::new(&key[..]),
+            halt,
+        })
+    }
+
+    fn serialize(&self) -> Result<Vec<u8>, Error> {
+        let mut v: Cursor<Vec<u8>> = Cursor::new(Vec::new());
+        v.write_u8(self.chain_id)?;
+        v.write(&self.mint.to_bytes())?;
+        v.write_u8(self.halt as u8)?;
+
+        Ok(v.into_inner())
+    }
+}
 
 impl BodyUpdateGuardianSet {
     fn deserialize(data: &mut Cursor<&Vec<u8>>) -> Result<BodyUpdateGuardianSet, Error> {
@@ -297,6 +420,25 @@ impl BodyTransfer {
     }
 }
 
+impl BodyRevertTransfer {
+    fn deserialize(data: &mut Cursor<&Vec<u8>>) -> Result<BodyRevertTransfer, Error> {
+        let transfer = BodyTransfer::deserialize(data)?;
+        let target_check_time = data.read_u32::<BigEndian>()?;
+
+        Ok(BodyRevertTransfer {
+            transfer,
+            target_check_time,
+        })
+    }
+
+    fn serialize(&self) -> Result<Vec<u8>, Error> {
+        let mut v = self.transfer.serialize()?;
+        v.write_u32::<BigEndian>(self.target_check_time)?;
+
+        Ok(v)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use hex;
@@ -306,7 +448,10 @@ mod tests {
         state::AssetMeta,
         vaa::{BodyTransfer, BodyUpdateGuardianSet, Signature, VAABody, VAA},
     };
-    use crate::vaa::BodyContractUpgrade;
+    use crate::vaa::{
+        BodyContractUpgrade, BodyFreezeAccount, BodyHaltMinting, BodyRevertTransfer,
+        BodyTreasuryWithdrawal,
+    };
     use solana_program::pubkey::Pubkey;
 
     #[test]
@@ -387,6 +532,114 @@ mod tests {
         assert_eq!(vaa, parsed_vaa)
     }
 
+    #[test]
+    fn serialize_deserialize_vaa_treasury_withdrawal() {
+        let vaa = VAA {
+            version: 8,
+            guardian_set_index: 3,
+            signatures: vec![Signature {
+                index: 1,
+                r: [2; 32],
+                s: [2; 32],
+                v: 7,
+            }],
+            timestamp: 83,
+            payload: Some(VAABody::TreasuryWithdrawal(BodyTreasuryWithdrawal {
+                chain_id: 1,
+                recipient: Pubkey::new_unique(),
+                amount: 1_000_000,
+            })),
+        };
+
+        let data = vaa.serialize().unwrap();
+        let parsed_vaa = VAA::deserialize(data.as_slice()).unwrap();
+        assert_eq!(vaa, parsed_vaa)
+    }
+
+    #[test]
+    fn serialize_deserialize_vaa_freeze_account() {
+        let vaa = VAA {
+            version: 8,
+            guardian_set_index: 3,
+            signatures: vec![Signature {
+                index: 1,
+                r: [2; 32],
+                s: [2; 32],
+                v: 7,
+            }],
+            timestamp: 83,
+            payload: Some(VAABody::FreezeAccount(BodyFreezeAccount {
+                chain_id: 1,
+                mint: Pubkey::new_unique(),
+                token_account: Pubkey::new_unique(),
+                freeze: true,
+            })),
+        };
+
+        let data = vaa.serialize().unwrap();
+        let parsed_vaa = VAA::deserialize(data.as_slice()).unwrap();
+        assert_eq!(vaa, parsed_vaa)
+    }
+
+    #[test]
+    fn serialize_deserialize_vaa_halt_minting() {
+        let vaa = VAA {
+            version: 8,
+            guardian_set_index: 3,
+            signatures: vec![Signature {
+                index: 1,
+                r: [2; 32],
+                s: [2; 32],
+                v: 7,
+            }],
+            timestamp: 83,
+            payload: Some(VAABody::HaltMinting(BodyHaltMinting {
+                chain_id: 1,
+                mint: Pubkey::new_unique(),
+                halt: true,
+            })),
+        };
+
+        let data = vaa.serialize().unwrap();
+        let parsed_vaa = VAA::deserialize(data.as_slice()).unwrap();
+        assert_eq!(vaa, parsed_vaa)
+    }
+
+    #[test]
+    fn serialize_deserialize_vaa_revert_transfer() {
+        let vaa = VAA {
+            version: 8,
+            guardian_set_index: 3,
+            signatures: vec![Signature {
+                index: 1,
+                r: [2; 32],
+                s: [2; 32],
+                v: 7,
+            }],
+            timestamp: 83,
+            payload: Some(VAABody::RevertTransfer(BodyRevertTransfer {
+                transfer: BodyTransfer {
+                    nonce: 28,
+                    source_chain: 1,
+                    target_chain: 2,
+                    source_address: [1; 32],
+                    target_address: [1; 32],
+                    asset: AssetMeta {
+                        address: [2; 32],
+                        chain: 8,
+                        decimals: 9,
+                    },
+                    amount: U256::from(3),
+                },
+                target_check_time: 94,
+            })),
+        };
+
+        let data = vaa.serialize().unwrap();
+        let parsed_vaa = VAA::deserialize(data.as_slice()).unwrap();
+        assert_eq!(vaa, parsed_vaa)
+    }
+
     #[test]
     fn parse_given_guardian_set_update() {
         let vaa = VAA {
@@ -34,7 +34,11 @@ use spl_token::{
     state::{Account, Mint},
 };
 
-use spl_bridge::{instruction::*, state::*};
+use spl_bridge::{instruction::*, state::*, vaa::VAA};
+use wormhole_solana_client::{
+    ops::{lock, redeem, simulate, verify_vaa, Lockup},
+    Pda,
+};
 
 use crate::faucet::request_and_confirm_airdrop;
 use solana_sdk::program_pack::Pack;
@@ -138,6 +142,58 @@ fn command_poke_proposal(config: &Config, bridge: &Pubkey, proposal: &Pubkey) ->
     Ok(Some(transaction))
 }
 
+fn command_claim_reimbursement(config: &Config, bridge: &Pubkey) -> CommmandResult {
+    println!("Claiming reimbursements");
+
+    let ix = claim_reimbursement(bridge, &config.fee_payer.pubkey())?;
+    let mut transaction = Transaction::new_with_payer(&[ix], Some(&config.fee_payer.pubkey()));
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        fee_calculator.calculate_fee(&transaction.message()),
+    )?;
+    transaction.sign(&[&config.fee_payer], recent_blockhash);
+    Ok(Some(transaction))
+}
+
+fn command_post_vaa(config: &Config, bridge: &Pubkey, vaa: &VAA) -> CommmandResult {
+    println!("Posting VAA");
+
+    let pda = Pda::new(bridge)?;
+    let payer = config.fee_payer.pubkey();
+    let verify_transactions = verify_vaa(&config.rpc_client, &pda, &payer, vaa)?;
+    let mut transaction = redeem(&config.rpc_client, &pda, &payer, vaa)?;
+
+    // Rent of the created accounts is not known upfront, only the fees are checked
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        verify_transactions
+            .iter()
+            .chain(Some(&transaction))
+            .map(|tx| fee_calculator.calculate_fee(&tx.message()))
+            .sum(),
+    )?;
+
+    // The signatures are verified before the VAA can be posted
+    for mut verify_transaction in verify_transactions {
+        println!("Verifying signatures");
+        verify_transaction.sign(&[&config.fee_payer], recent_blockhash);
+        simulate(&config.rpc_client, bridge, &verify_transaction)?;
+        let signature = config
+            .rpc_client
+            .send_and_confirm_transaction_with_spinner_and_commitment(
+                &verify_transaction,
+                config.commitment_config,
+            )?;
+        println!("Signature: {}", signature);
+    }
+
+    transaction.sign(&[&config.fee_payer], recent_blockhash);
+    Ok(Some(transaction))
+}
+
 fn command_lock_tokens(
     config: &Config,
     bridge: &Pubkey,
@@ -146,7 +202,6 @@ fn command_lock_tokens(
     amount: u64,
     to_chain: u8,
     target: ForeignAddress,
-    nonce: u32,
 ) -> CommmandResult {
     println!("Initiating transfer to foreign chain");
 
@@ -154,65 +209,24 @@ fn command_lock_tokens(
         .rpc_client
         .get_minimum_balance_for_rent_exemption(size_of::<Mint>())?;
 
-    let bridge_key = Bridge::derive_bridge_id(bridge)?;
-
-    // Fetch token balance to get decimals.
-    let balance = config
-        .rpc_client
-        .get_token_account_balance_with_commitment(&account, config.commitment_config)?
-        .value;
-
-    // Check whether we can find wrapped asset meta for the given token
-    let wrapped_key = Bridge::derive_wrapped_meta_id(bridge, &bridge_key, &token)?;
-    let asset_meta = match config.rpc_client.get_account(&wrapped_key) {
-        Ok(v) => {
-            let wrapped_meta: &WrappedAssetMeta =
-                Bridge::unpack_unchecked_immutable(v.data.as_slice())?;
-            AssetMeta {
-                address: wrapped_meta.address,
-                chain: wrapped_meta.chain,
-                decimals: balance.decimals,
-            }
-        }
-        Err(_e) => AssetMeta {
-            address: token.to_bytes(),
-            chain: CHAIN_ID_SOLANA,
-            decimals: 0,
-        },
-    };
-
-    let instructions = vec![
-        approve(
-            &spl_token::id(),
-            &account,
-            &bridge_key,
-            &config.owner.pubkey(),
-            &[],
-            amount,
-        )?,
-        system_instruction::transfer(&config.owner.pubkey(), &bridge_key, Bridge::transfer_fee()),
-        transfer_out(
-            bridge,
-            &config.owner.pubkey(),
-            &account,
-            &token,
-            &TransferOutPayload {
-                amount: U256::from(amount),
-                chain_id: to_chain,
-                asset: asset_meta,
-                target,
-                nonce,
-            },
-        )?,
-    ];
-
-    println!(
-        "custody: {}, ",
-        instructions[2].accounts[8].pubkey.to_string()
-    );
-
-    let mut transaction =
-        Transaction::new_with_payer(&instructions.as_slice(), Some(&config.fee_payer.pubkey()));
+    let pda = Pda::new(bridge)?;
+    let Lockup {
+        mut transaction,
+        proposal,
+        sequence,
+    } = lock(
+        &config.rpc_client,
+        &pda,
+        &config.fee_payer.pubkey(),
+        &config.owner.pubkey(),
+        &account,
+        &token,
+        amount,
+        to_chain,
+        target,
+    )?;
+    println!("Sequence: {}", sequence);
+    println!("proposal: {}, ", proposal.to_string());
 
     let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
     check_fee_payer_balance(
@@ -998,21 +1012,12 @@ fn main() {
                         .required(true)
                         .help("Chain to transfer to"),
                 )
-                .arg(
-                    Arg::with_name("nonce")
-                        .validator(is_u32)
-                        .value_name("NONCE")
-                        .takes_value(true)
-                        .index(6)
-                        .required(true)
-                        .help("Nonce of the transfer"),
-                )
                 .arg(
                     Arg::with_name("recipient")
                         .validator(is_hex)
                         .value_name("RECIPIENT_ADDRESS")
                         .takes_value(true)
-                        .index(7)
+                        .index(6)
                         .required(true)
                         .help("Address of the recipient (hex)"),
                 )
@@ -1070,6 +1075,22 @@ fn main() {
                         ),
                 )
         )
+        .subcommand(
+            SubCommand::with_name("claim-reimbursement")
+                .about("Claim the reimbursements the bridge owes to the fee payer for submitting VAAs")
+                .arg(
+                    Arg::with_name("bridge")
+                        .long("bridge")
+                        .value_name("BRIDGE_KEY")
+                        .validator(is_pubkey_or_keypair)
+                        .takes_value(true)
+                        .index(1)
+                        .required(true)
+                        .help(
+                            "Specify the bridge program address"
+                        ),
+                )
+        )
         .subcommand(
             SubCommand::with_name("wrapped-address")
                 .about("Derive wrapped asset address")
@@ -1187,6 +1208,12 @@ fn main() {
 
     solana_logger::setup_with_default("solana=info");
 
+    // Errors of the bridge are decoded when simulating transactions of the bridge commands
+    let bridge = matches
+        .subcommand()
+        .1
+        .and_then(|arg_matches| pubkey_of(arg_matches, "bridge"));
+
     let _ = match matches.subcommand() {
         ("create-token", Some(arg_matches)) => {
             let decimals = value_t_or_exit!(arg_matches, "decimals", u8);
@@ -1262,7 +1289,6 @@ fn main() {
             let bridge = pubkey_of(arg_matches, "bridge").unwrap();
             let account = pubkey_of(arg_matches, "sender").unwrap();
             let amount = value_t_or_exit!(arg_matches, "amount", u64);
-            let nonce = value_t_or_exit!(arg_matches, "nonce", u32);
             let chain = value_t_or_exit!(arg_matches, "chain", u8);
             let token = pubkey_of(arg_matches, "token").unwrap();
             let recipient_string: String = value_of(arg_matches, "recipient").unwrap();
@@ -1275,7 +1301,7 @@ fn main() {
             let mut recipient = [0u8; 32];
             recipient.copy_from_slice(&recipient_data);
             command_lock_tokens(
-                &config, &bridge, account, token, amount, chain, recipient, nonce,
+                &config, &bridge, account, token, amount, chain, recipient,
             )
         }
         ("poke", Some(arg_matches)) => {
@@ -1283,6 +1309,18 @@ fn main() {
             let proposal = pubkey_of(arg_matches, "proposal").unwrap();
             command_poke_proposal(&config, &bridge, &proposal)
         }
+        ("claim-reimbursement", Some(arg_matches)) => {
+            let bridge = pubkey_of(arg_matches, "bridge").unwrap();
+            command_claim_reimbursement(&config, &bridge)
+        }
+        ("postvaa", Some(arg_matches)) => {
+            let bridge = pubkey_of(arg_matches, "bridge").unwrap();
+            let vaa_string: String = value_of(arg_matches, "vaa").unwrap();
+            let vaa_data = hex::decode(vaa_string).unwrap();
+            let vaa = VAA::deserialize(&vaa_data).unwrap();
+
+            command_post_vaa(&config, &bridge, &vaa)
+        }
         ("create-wrapped", Some(arg_matches)) => {
             let bridge = pubkey_of(arg_matches, "bridge").unwrap();
             let chain = value_t_or_exit!(arg_matches, "chain", u8);
@@ -1305,10 +1343,15 @@ fn main() {
             let mut token_addr = [0u8; 32];
             token_addr.copy_from_slice(addr_data.as_slice());
 
-            let bridge_key = Bridge::derive_bridge_id(&bridge).unwrap();
-            let wrapped_key =
-                Bridge::derive_wrapped_asset_id(&bridge, &bridge_key, chain, decimals, token_addr)
-                    .unwrap();
+            let wrapped_key = Pda::new(&bridge)
+                .and_then(|pda| {
+                    pda.wrapped_mint(&AssetMeta {
+                        address: token_addr,
+                        chain,
+                        decimals,
+                    })
+                })
+                .unwrap();
             println!("Wrapped address: {}", wrapped_key);
             return;
         }
@@ -1316,6 +1359,10 @@ fn main() {
     }
         .and_then(|transaction| {
             if let Some(transaction) = transaction {
+                if let Some(bridge) = bridge {
+                    simulate(&config.rpc_client, &bridge, &transaction)?;
+                }
+
                 // TODO: Upgrade to solana-client 1.3 and
                 // `send_and_confirm_transaction_with_spinner_and_commitment()` with single
                 // confirmation by default for better UX
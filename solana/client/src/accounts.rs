@@ -0,0 +1,445 @@
+//! Typed account bundles of the bridge instructions
+//!
+//! Each bundle names the accounts of one instruction and knows their order and access flags, see
+//! `docs/solana_program.md` for the layouts.
+
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use spl_bridge::{
+    associated_token,
+    instruction::{BridgeInstruction, TransferOutPayload, VAAData, VerifySigPayload, CHAIN_ID_SOLANA},
+    vaa::{BodyRevertTransfer, VAABody, VAA},
+};
+
+use crate::{error::Error, pda::Pda};
+
+/// Accounts of a `TransferOut` instruction
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransferOutAccounts {
+    pub program_id: Pubkey,
+    pub token_account: Pubkey,
+    pub bridge: Pubkey,
+    pub proposal: Pubkey,
+    pub mint: Pubkey,
+    pub payer: Pubkey,
+    pub sequence: Pubkey,
+    pub asset: TransferOutAsset,
+}
+
+/// Account depending on whether the transferred asset is native to Solana
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransferOutAsset {
+    /// custody token account the native tokens are locked in and the bump seed of its address
+    Custody(Pubkey, u8),
+    /// meta account of the wrapped mint the tokens are burned from
+    WrappedMeta(Pubkey),
+}
+
+impl TransferOutAccounts {
+    pub fn new(
+        pda: &Pda,
+        payer: &Pubkey,
+        token_account: &Pubkey,
+        mint: &Pubkey,
+        t: &TransferOutPayload,
+        sequence: u32,
+    ) -> Result<Self, Error> {
+        let asset = if t.asset.chain == CHAIN_ID_SOLANA {
+            let (custody, bump) = pda.custody(mint)?;
+            TransferOutAsset::Custody(custody, bump)
+        } else {
+            TransferOutAsset::WrappedMeta(pda.wrapped_meta(mint)?)
+        };
+
+        Ok(TransferOutAccounts {
+            program_id: pda.program_id,
+            token_account: *token_account,
+            bridge: pda.bridge,
+            proposal: pda.transfer_out_proposal(&t.asset, t.chain_id, t.target, token_account, sequence)?,
+            mint: *mint,
+            payer: *payer,
+            sequence: pda.transfer_sequence(token_account)?,
+            asset,
+        })
+    }
+
+    pub fn to_account_metas(&self) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.program_id, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            AccountMeta::new(self.token_account, false),
+            AccountMeta::new_readonly(self.bridge, false),
+            AccountMeta::new(self.proposal, false),
+            AccountMeta::new(self.mint, false),
+            AccountMeta::new(self.payer, true),
+            AccountMeta::new(self.sequence, false),
+        ];
+        accounts.push(match self.asset {
+            TransferOutAsset::Custody(k, _) => AccountMeta::new(k, false),
+            TransferOutAsset::WrappedMeta(k) => AccountMeta::new_readonly(k, false),
+        });
+        accounts
+    }
+
+    /// Creates the `TransferOut` instruction
+    pub fn instruction(&self, t: &TransferOutPayload) -> Result<Instruction, Error> {
+        let mut t = *t;
+        if let TransferOutAsset::Custody(_, bump) = self.asset {
+            t.custody_bump = bump;
+        }
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts: self.to_account_metas(),
+            data: BridgeInstruction::TransferOut(t).serialize()?,
+        })
+    }
+}
+
+/// Accounts of a `VerifySignatures` instruction
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerifySignaturesAccounts {
+    pub program_id: Pubkey,
+    pub bridge: Pubkey,
+    pub signature_state: Pubkey,
+    pub guardian_set: Pubkey,
+    pub payer: Pubkey,
+}
+
+impl VerifySignaturesAccounts {
+    pub fn new(pda: &Pda, payer: &Pubkey, vaa: &VAA) -> Result<Self, Error> {
+        Ok(VerifySignaturesAccounts {
+            program_id: pda.program_id,
+            bridge: pda.bridge,
+            signature_state: pda.signature_state(&vaa.body_hash()?, vaa.guardian_set_index)?,
+            guardian_set: pda.guardian_set(vaa.guardian_set_index)?,
+            payer: *payer,
+        })
+    }
+
+    pub fn to_account_metas(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(self.program_id, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            AccountMeta::new(self.bridge, false),
+            AccountMeta::new(self.signature_state, false),
+            AccountMeta::new_readonly(self.guardian_set, false),
+            AccountMeta::new(self.payer, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ]
+    }
+
+    /// Creates the `VerifySignatures` instruction
+    pub fn instruction(&self, p: &VerifySigPayload) -> Result<Instruction, Error> {
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts: self.to_account_metas(),
+            data: BridgeInstruction::VerifySignatures(*p).serialize()?,
+        })
+    }
+}
+
+/// Accounts of a `PostVAA` instruction
+#[derive(Clone, Debug, PartialEq)]
+pub struct PostVAAAccounts {
+    pub program_id: Pubkey,
+    pub bridge: Pubkey,
+    pub guardian_set: Pubkey,
+    pub claim: Pubkey,
+    pub signature_state: Pubkey,
+    pub payer: Pubkey,
+    pub ledger: Pubkey,
+    pub action: PostVAAAction,
+}
+
+/// Accounts specific to the action of a VAA
+#[derive(Clone, Debug, PartialEq)]
+pub enum PostVAAAction {
+    UpdateGuardianSet {
+        new_guardian_set: Pubkey,
+    },
+    UpgradeContract {
+        buffer: Pubkey,
+        program_data: Pubkey,
+    },
+    TreasuryWithdrawal {
+        recipient: Pubkey,
+    },
+    FreezeAccount {
+        mint: Pubkey,
+        token_account: Pubkey,
+    },
+    HaltMinting {
+        wrapped_meta: Pubkey,
+    },
+    /// Refund of a Solana (any) -> foreign (any) transfer that never received a VAA
+    RevertTransfer {
+        proposal: Pubkey,
+        mint: Pubkey,
+        source: Pubkey,
+        asset: TransferOutAsset,
+    },
+    /// Solana (any) -> foreign (any)
+    TransferOut {
+        proposal: Pubkey,
+        posted_vaa: Pubkey,
+    },
+    /// Foreign (wrapped) -> Solana (native)
+    TransferInNative {
+        mint: Pubkey,
+        destination: Pubkey,
+        custody: Pubkey,
+        /// wallet owning `destination` when it is the associated token account of the target
+        wallet: Option<Pubkey>,
+    },
+    /// Foreign (native) -> Solana (wrapped)
+    TransferInWrapped {
+        mint: Pubkey,
+        destination: Pubkey,
+        wrapped_meta: Pubkey,
+        /// wallet owning `destination` when it is the associated token account of the target
+        wallet: Option<Pubkey>,
+    },
+}
+
+impl PostVAAAccounts {
+    pub fn new(pda: &Pda, payer: &Pubkey, vaa: &VAA) -> Result<Self, Error> {
+        let action = match vaa.payload.as_ref().ok_or(spl_bridge::error::Error::InvalidVAAAction)? {
+            VAABody::UpdateGuardianSet(u) => PostVAAAction::UpdateGuardianSet {
+                new_guardian_set: pda.guardian_set(u.new_index)?,
+            },
+            VAABody::UpgradeContract(u) => {
+                let (program_data, _) = Pubkey::find_program_address(
+                    &[pda.program_id.as_ref()],
+                    &solana_sdk::bpf_loader_upgradeable::id(),
+                );
+                PostVAAAction::UpgradeContract {
+                    buffer: u.buffer,
+                    program_data,
+                }
+            }
+            VAABody::TreasuryWithdrawal(w) => PostVAAAction::TreasuryWithdrawal {
+                recipient: w.recipient,
+            },
+            VAABody::FreezeAccount(f) => PostVAAAction::FreezeAccount {
+                mint: f.mint,
+                token_account: f.token_account,
+            },
+            VAABody::HaltMinting(h) => PostVAAAction::HaltMinting {
+                wrapped_meta: pda.wrapped_meta(&h.mint)?,
+            },
+            VAABody::RevertTransfer(BodyRevertTransfer { transfer: t, .. }) => {
+                let source = Pubkey::new(&t.source_address);
+                let (mint, asset) = if t.asset.chain == CHAIN_ID_SOLANA {
+                    let mint = Pubkey::new(&t.asset.address);
+                    let (custody, bump) = pda.custody(&mint)?;
+                    (mint, TransferOutAsset::Custody(custody, bump))
+                } else {
+                    let mint = pda.wrapped_mint(&t.asset)?;
+                    (mint, TransferOutAsset::WrappedMeta(pda.wrapped_meta(&mint)?))
+                };
+                PostVAAAction::RevertTransfer {
+                    proposal: pda.transfer_out_proposal(
+                        &t.asset,
+                        t.target_chain,
+                        t.target_address,
+                        &source,
+                        t.nonce,
+                    )?,
+                    mint,
+                    source,
+                    asset,
+                }
+            }
+            VAABody::Transfer(t) => {
+                if t.source_chain == CHAIN_ID_SOLANA {
+                    let proposal = pda.transfer_out_proposal(
+                        &t.asset,
+                        t.target_chain,
+                        t.target_address,
+                        &Pubkey::new(&t.source_address),
+                        t.nonce,
+                    )?;
+                    PostVAAAction::TransferOut {
+                        proposal,
+                        posted_vaa: pda.posted_vaa(&proposal)?,
+                    }
+                } else if t.asset.chain == CHAIN_ID_SOLANA {
+                    let mint = Pubkey::new(&t.asset.address);
+                    PostVAAAction::TransferInNative {
+                        mint,
+                        destination: Pubkey::new(&t.target_address),
+                        custody: pda.custody(&mint)?.0,
+                        wallet: None,
+                    }
+                } else {
+                    let mint = pda.wrapped_mint(&t.asset)?;
+                    PostVAAAction::TransferInWrapped {
+                        mint,
+                        destination: Pubkey::new(&t.target_address),
+                        wrapped_meta: pda.wrapped_meta(&mint)?,
+                        wallet: None,
+                    }
+                }
+            }
+        };
+
+        Ok(PostVAAAccounts {
+            program_id: pda.program_id,
+            bridge: pda.bridge,
+            guardian_set: pda.guardian_set(vaa.guardian_set_index)?,
+            claim: pda.claim(vaa.signature_body()?)?,
+            signature_state: pda.signature_state(&vaa.body_hash()?, vaa.guardian_set_index)?,
+            payer: *payer,
+            ledger: pda.ledger(payer)?,
+            action,
+        })
+    }
+
+    /// Redeems an inbound transfer to the associated token account of its target instead of the
+    /// target itself, which is then the wallet owning it. The bridge creates the associated token
+    /// account if it does not exist yet.
+    pub fn redeem_to_wallet(mut self) -> Self {
+        match self.action {
+            PostVAAAction::TransferInNative {
+                mint,
+                ref mut destination,
+                ref mut wallet,
+                ..
+            }
+            | PostVAAAction::TransferInWrapped {
+                mint,
+                ref mut destination,
+                ref mut wallet,
+                ..
+            } => {
+                if wallet.is_none() {
+                    *wallet = Some(*destination);
+                    *destination =
+                        associated_token::get_associated_token_address(destination, &mint);
+                }
+            }
+            _ => {}
+        }
+        self
+    }
+
+    pub fn to_account_metas(&self) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.program_id, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new(self.bridge, false),
+            AccountMeta::new(self.guardian_set, false),
+            AccountMeta::new(self.claim, false),
+            AccountMeta::new(self.signature_state, false),
+            AccountMeta::new(self.payer, true),
+            AccountMeta::new(self.ledger, false),
+        ];
+
+        match self.action {
+            PostVAAAction::UpdateGuardianSet { new_guardian_set } => {
+                accounts.push(AccountMeta::new(new_guardian_set, false));
+            }
+            PostVAAAction::UpgradeContract {
+                buffer,
+                program_data,
+            } => {
+                // The program account is modified by the upgrade
+                accounts[0] = AccountMeta::new(self.program_id, false);
+                accounts.push(AccountMeta::new(buffer, false));
+                accounts.push(AccountMeta::new(program_data, false));
+                accounts.push(AccountMeta::new_readonly(
+                    solana_sdk::bpf_loader_upgradeable::id(),
+                    false,
+                ));
+            }
+            PostVAAAction::TreasuryWithdrawal { recipient } => {
+                accounts.push(AccountMeta::new(recipient, false));
+            }
+            PostVAAAction::FreezeAccount {
+                mint,
+                token_account,
+            } => {
+                accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+                accounts.push(AccountMeta::new_readonly(mint, false));
+                accounts.push(AccountMeta::new(token_account, false));
+            }
+            PostVAAAction::HaltMinting { wrapped_meta } => {
+                accounts.push(AccountMeta::new(wrapped_meta, false));
+            }
+            PostVAAAction::RevertTransfer {
+                proposal,
+                mint,
+                source,
+                ref asset,
+            } => {
+                accounts.push(AccountMeta::new(proposal, false));
+                accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+                accounts.push(AccountMeta::new(mint, false));
+                accounts.push(AccountMeta::new(source, false));
+                accounts.push(match *asset {
+                    TransferOutAsset::Custody(k, _) => AccountMeta::new(k, false),
+                    TransferOutAsset::WrappedMeta(k) => AccountMeta::new_readonly(k, false),
+                });
+            }
+            PostVAAAction::TransferOut {
+                proposal,
+                posted_vaa,
+            } => {
+                accounts.push(AccountMeta::new(proposal, false));
+                accounts.push(AccountMeta::new(posted_vaa, false));
+            }
+            PostVAAAction::TransferInNative {
+                mint,
+                destination,
+                custody,
+                wallet,
+            } => {
+                accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+                accounts.push(AccountMeta::new(mint, false));
+                accounts.push(AccountMeta::new(destination, false));
+                accounts.push(AccountMeta::new(custody, false));
+                if let Some(wallet) = wallet {
+                    accounts.push(AccountMeta::new_readonly(wallet, false));
+                    accounts.push(AccountMeta::new_readonly(associated_token::id(), false));
+                }
+            }
+            PostVAAAction::TransferInWrapped {
+                mint,
+                destination,
+                wrapped_meta,
+                wallet,
+            } => {
+                accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+                accounts.push(AccountMeta::new(mint, false));
+                accounts.push(AccountMeta::new(destination, false));
+                accounts.push(AccountMeta::new(wrapped_meta, false));
+                if let Some(wallet) = wallet {
+                    accounts.push(AccountMeta::new_readonly(wallet, false));
+                    accounts.push(AccountMeta::new_readonly(associated_token::id(), false));
+                }
+            }
+        }
+
+        accounts
+    }
+
+    /// Creates the `PostVAA` instruction for the serialized VAA `v` (without signatures)
+    pub fn instruction(&self, v: VAAData) -> Result<Instruction, Error> {
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts: self.to_account_metas(),
+            data: BridgeInstruction::PostVAA(v).serialize()?,
+        })
+    }
+}
@@ -0,0 +1,132 @@
+//! Error types
+
+use num_traits::FromPrimitive;
+use solana_client::client_error::ClientError;
+use solana_sdk::{
+    instruction::InstructionError, program_error::ProgramError, pubkey::Pubkey,
+    transaction::{Transaction, TransactionError},
+};
+use thiserror::Error;
+
+/// Errors that may be returned by the client.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// A request to the RPC node failed
+    #[error("rpc request failed: {0}")]
+    Rpc(#[from] ClientError),
+    /// An instruction could not be created
+    #[error("program error: {0}")]
+    Program(#[from] ProgramError),
+    /// An address could not be derived or an account could not be parsed
+    #[error("bridge error: {0}")]
+    Bridge(#[from] spl_bridge::error::Error),
+    /// A required account does not exist
+    #[error("account {0} does not exist")]
+    AccountNotFound(Pubkey),
+    /// The VAA refers to a guardian that is not part of its guardian set
+    #[error("unknown guardian index {0}")]
+    UnknownGuardian(u8),
+    /// An instruction of a simulated transaction was rejected by the bridge program
+    #[error("instruction {instruction} rejected by the bridge: {error}")]
+    Rejected {
+        instruction: u8,
+        error: spl_bridge::error::Error,
+        logs: Vec<String>,
+    },
+    /// A simulated transaction failed for another reason
+    #[error("transaction failed: {error}")]
+    TransactionFailed {
+        error: TransactionError,
+        logs: Vec<String>,
+    },
+}
+
+impl Error {
+    /// Decodes the `error` `tx` failed with. Custom errors of instructions of the bridge
+    /// `program_id` are mapped back to the bridge errors.
+    pub fn from_transaction_error(
+        program_id: &Pubkey,
+        tx: &Transaction,
+        error: TransactionError,
+        logs: Vec<String>,
+    ) -> Self {
+        if let TransactionError::InstructionError(index, InstructionError::Custom(code)) = error {
+            let program = tx
+                .message
+                .instructions
+                .get(index as usize)
+                .map(|i| i.program_id(&tx.message.account_keys));
+            if program == Some(program_id) {
+                if let Some(error) = spl_bridge::error::Error::from_u32(code) {
+                    return Error::Rejected {
+                        instruction: index,
+                        error,
+                        logs,
+                    };
+                }
+            }
+        }
+        Error::TransactionFailed { error, logs }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::instruction::Instruction;
+
+    use super::*;
+
+    fn transaction(programs: &[Pubkey]) -> Transaction {
+        let instructions: Vec<Instruction> = programs
+            .iter()
+            .map(|program_id| Instruction {
+                program_id: *program_id,
+                accounts: vec![],
+                data: vec![],
+            })
+            .collect();
+        Transaction::new_with_payer(&instructions, Some(&Pubkey::new_unique()))
+    }
+
+    #[test]
+    fn custom_errors_of_the_bridge_are_decoded() {
+        let bridge = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let tx = transaction(&[other, bridge]);
+        let failure = |index, code| {
+            Error::from_transaction_error(
+                &bridge,
+                &tx,
+                TransactionError::InstructionError(index, InstructionError::Custom(code)),
+                vec!["log".to_string()],
+            )
+        };
+
+        for error in &[
+            spl_bridge::error::Error::ExpectedToken,
+            spl_bridge::error::Error::VAAClaimed,
+            spl_bridge::error::Error::AmountOverflow,
+        ] {
+            match failure(1, error.clone() as u32) {
+                Error::Rejected {
+                    instruction,
+                    error: decoded,
+                    logs,
+                } => {
+                    assert_eq!(instruction, 1);
+                    assert_eq!(&decoded, error);
+                    assert_eq!(logs, vec!["log".to_string()]);
+                }
+                e => panic!("unexpected error {:?}", e),
+            }
+        }
+
+        // Errors of other programs and unknown codes are not mapped to bridge errors
+        let code = spl_bridge::error::Error::VAAClaimed as u32;
+        assert!(matches!(failure(0, code), Error::TransactionFailed { .. }));
+        assert!(matches!(
+            failure(1, u32::MAX),
+            Error::TransactionFailed { .. }
+        ));
+    }
+}
@@ -0,0 +1,14 @@
+//! Client library for the Wormhole Solana bridge program.
+//!
+//! Wraps the program's instructions in typed account bundles so callers don't need to know the
+//! positional account layout of each instruction, provides helpers to derive the program's
+//! addresses and offers high-level operations that return transactions ready to be signed.
+
+pub mod accounts;
+pub mod error;
+pub mod ops;
+pub mod pda;
+
+pub use spl_bridge;
+
+pub use crate::{error::Error, pda::Pda};
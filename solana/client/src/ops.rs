@@ -0,0 +1,491 @@
+//! High-level operations on the bridge
+//!
+//! The returned transactions are not signed yet. They are paid by `payer` which needs to sign
+//! them together with the other signers named by each operation.
+
+use std::io::Write;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use primitive_types::U256;
+use solana_client::{rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
+use solana_sdk::{
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    instruction::Instruction, pubkey::Pubkey, secp256k1_program, system_instruction,
+    transaction::Transaction,
+};
+use spl_bridge::{
+    instruction::{
+        create_wrapped, BatchedTransfer, ForeignAddress, TransferOutBatchPayload,
+        TransferOutPayload, VerifySigPayload, CHAIN_ID_SOLANA,
+    },
+    state::{
+        AssetMeta, Bridge, ClaimedVAA, GuardianLedger, GuardianSet, IsInitialized,
+        SignatureState, TransferOutProposal, TransferSequence, WrappedAssetMeta,
+    },
+    vaa::{BodyTransfer, VAABody, VAA},
+};
+
+use crate::{
+    accounts::{PostVAAAccounts, PostVAAAction, TransferOutAccounts, VerifySignaturesAccounts},
+    error::Error,
+    pda::Pda,
+};
+
+/// Maximum number of signatures that are verified in a single transaction
+pub const SIGNATURES_PER_TX: usize = 6;
+
+/// An outbound transfer ready to be signed
+pub struct Lockup {
+    /// transaction to be signed by the payer and the owner of the token account
+    pub transaction: Transaction,
+    /// the proposal tracking the transfer
+    pub proposal: Pubkey,
+    /// the sequence number assigned to the transfer
+    pub sequence: u32,
+}
+
+/// Several outbound transfers locked by a single transaction
+pub struct BatchLockup {
+    /// transaction to be signed by the payer and the owner of the token accounts
+    pub transaction: Transaction,
+    /// the proposal tracking each transfer and the sequence number assigned to it, in order
+    pub proposals: Vec<(Pubkey, u32)>,
+}
+
+/// Fetches the guardian set with the given index
+pub fn fetch_guardian_set(rpc: &RpcClient, pda: &Pda, index: u32) -> Result<GuardianSet, Error> {
+    let key = pda.guardian_set(index)?;
+    let account = rpc
+        .get_account_with_commitment(&key, commitment())?
+        .value
+        .ok_or(Error::AccountNotFound(key))?;
+    let guardian_set: &GuardianSet = Bridge::unpack_immutable(account.data.as_slice())?;
+    Ok(*guardian_set)
+}
+
+/// Fetches the sequence number that will be assigned to the next transfer of `token_account`
+pub fn fetch_next_sequence(rpc: &RpcClient, pda: &Pda, token_account: &Pubkey) -> Result<u32, Error> {
+    let key = pda.transfer_sequence(token_account)?;
+    match rpc.get_account_with_commitment(&key, commitment())?.value {
+        Some(account) => {
+            let sequence: &TransferSequence = Bridge::unpack_immutable(account.data.as_slice())?;
+            Ok(sequence.sequence)
+        }
+        None => Ok(0),
+    }
+}
+
+/// Fetches the asset a mint represents. Mints without a wrapped meta are native Solana assets.
+pub fn fetch_asset_meta(rpc: &RpcClient, pda: &Pda, mint: &Pubkey, decimals: u8) -> Result<AssetMeta, Error> {
+    let key = pda.wrapped_meta(mint)?;
+    match rpc.get_account_with_commitment(&key, commitment())?.value {
+        Some(account) => {
+            let meta: &WrappedAssetMeta = Bridge::unpack_immutable(account.data.as_slice())?;
+            Ok(AssetMeta {
+                address: meta.address,
+                chain: meta.chain,
+                decimals,
+            })
+        }
+        None => Ok(AssetMeta {
+            address: mint.to_bytes(),
+            chain: CHAIN_ID_SOLANA,
+            decimals: 0,
+        }),
+    }
+}
+
+/// Locks `amount` tokens of `token_account` for a transfer to `target` on `target_chain`.
+///
+/// The transaction approves the bridge to move the tokens and pays the transfer fee, both on
+/// behalf of `owner`, so it needs to be signed by `owner` and `payer`.
+pub fn lock(
+    rpc: &RpcClient,
+    pda: &Pda,
+    payer: &Pubkey,
+    owner: &Pubkey,
+    token_account: &Pubkey,
+    mint: &Pubkey,
+    amount: u64,
+    target_chain: u8,
+    target: ForeignAddress,
+) -> Result<Lockup, Error> {
+    let balance = rpc
+        .get_token_account_balance_with_commitment(token_account, commitment())?
+        .value;
+    let asset = fetch_asset_meta(rpc, pda, mint, balance.decimals)?;
+    let sequence = fetch_next_sequence(rpc, pda, token_account)?;
+
+    let payload = TransferOutPayload {
+        amount: U256::from(amount),
+        chain_id: target_chain,
+        asset,
+        target,
+        custody_bump: 0,
+    };
+    let accounts = TransferOutAccounts::new(pda, owner, token_account, mint, &payload, sequence)?;
+
+    let instructions = vec![
+        spl_token::instruction::approve(
+            &spl_token::id(),
+            token_account,
+            &pda.bridge,
+            owner,
+            &[],
+            amount,
+        )?,
+        system_instruction::transfer(owner, &pda.bridge, Bridge::transfer_fee()),
+        accounts.instruction(&payload)?,
+    ];
+
+    Ok(Lockup {
+        transaction: Transaction::new_with_payer(&instructions, Some(payer)),
+        proposal: accounts.proposal,
+        sequence,
+    })
+}
+
+/// Locks several assets of `owner` for transfers to `target` on `target_chain` in a single
+/// `TransferOutBatch`.
+///
+/// `assets` lists the token account, its mint and the amount to transfer for each asset. Like
+/// `lock`, the transaction needs to be signed by `owner` and `payer`.
+pub fn lock_batch(
+    rpc: &RpcClient,
+    pda: &Pda,
+    payer: &Pubkey,
+    owner: &Pubkey,
+    assets: &[(Pubkey, Pubkey, u64)],
+    target_chain: u8,
+    target: ForeignAddress,
+) -> Result<BatchLockup, Error> {
+    let mut instructions = Vec::new();
+    let mut sources: Vec<(Pubkey, Pubkey, u32)> = Vec::new();
+    let mut transfers = Vec::new();
+    for (token_account, mint, amount) in assets {
+        let balance = rpc
+            .get_token_account_balance_with_commitment(token_account, commitment())?
+            .value;
+        let asset = fetch_asset_meta(rpc, pda, mint, balance.decimals)?;
+
+        // Transfers from the same token account take consecutive sequence numbers
+        let earlier = sources.iter().filter(|(k, _, _)| k == token_account).count() as u32;
+        let sequence = fetch_next_sequence(rpc, pda, token_account)? + earlier;
+        let approved: u64 = assets
+            .iter()
+            .filter(|(k, _, _)| k == token_account)
+            .map(|(_, _, a)| *a)
+            .sum();
+        if earlier == 0 {
+            instructions.push(spl_token::instruction::approve(
+                &spl_token::id(),
+                token_account,
+                &pda.bridge,
+                owner,
+                &[],
+                approved,
+            )?);
+        }
+
+        sources.push((*token_account, *mint, sequence));
+        transfers.push(BatchedTransfer {
+            amount: U256::from(*amount),
+            asset,
+            custody_bump: 0,
+        });
+    }
+
+    let payload = TransferOutBatchPayload {
+        chain_id: target_chain,
+        target,
+        transfers,
+    };
+    instructions.push(system_instruction::transfer(
+        owner,
+        &pda.bridge,
+        Bridge::transfer_fee() * assets.len() as u64,
+    ));
+    instructions.push(spl_bridge::instruction::transfer_out_batch(
+        &pda.program_id,
+        owner,
+        &sources,
+        &payload,
+    )?);
+
+    let mut proposals = Vec::with_capacity(sources.len());
+    for ((token_account, _, sequence), t) in sources.iter().zip(payload.transfers.iter()) {
+        proposals.push((
+            pda.transfer_out_proposal(&t.asset, target_chain, target, token_account, *sequence)?,
+            *sequence,
+        ));
+    }
+
+    Ok(BatchLockup {
+        transaction: Transaction::new_with_payer(&instructions, Some(payer)),
+        proposals,
+    })
+}
+
+/// Returns whether `vaa` has already been executed on chain
+pub fn fetch_claimed(rpc: &RpcClient, pda: &Pda, vaa: &VAA) -> Result<bool, Error> {
+    let key = pda.claim(vaa.signature_body()?)?;
+    let account = rpc.get_account_with_commitment(&key, commitment())?.value;
+    Ok(account.map_or(false, |a| !a.data.is_empty()))
+}
+
+/// Fetches the claim of `vaa` if it has already been executed on chain
+pub fn fetch_claim(rpc: &RpcClient, pda: &Pda, vaa: &VAA) -> Result<Option<ClaimedVAA>, Error> {
+    let key = pda.claim(vaa.signature_body()?)?;
+    fetch_state(rpc, &key)
+}
+
+/// Fetches the signatures of `vaa` that have already been verified on chain, if any
+pub fn fetch_signature_state(
+    rpc: &RpcClient,
+    pda: &Pda,
+    vaa: &VAA,
+) -> Result<Option<SignatureState>, Error> {
+    fetch_signature_state_by_hash(rpc, pda, &vaa.body_hash()?, vaa.guardian_set_index)
+}
+
+/// Like `fetch_signature_state` but for the VAA with the given body hash, signed by the guardian
+/// set with the given index
+pub fn fetch_signature_state_by_hash(
+    rpc: &RpcClient,
+    pda: &Pda,
+    hash: &[u8; 32],
+    guardian_set_index: u32,
+) -> Result<Option<SignatureState>, Error> {
+    let key = pda.signature_state(hash, guardian_set_index)?;
+    fetch_state(rpc, &key)
+}
+
+/// Fetches the proposal of the outbound transfer `t`, if it exists
+pub fn fetch_transfer_out_proposal(
+    rpc: &RpcClient,
+    pda: &Pda,
+    t: &BodyTransfer,
+) -> Result<Option<TransferOutProposal>, Error> {
+    let key = pda.transfer_out_proposal(
+        &t.asset,
+        t.target_chain,
+        t.target_address,
+        &Pubkey::new(&t.source_address),
+        t.nonce,
+    )?;
+    fetch_state(rpc, &key)
+}
+
+/// Fetches and unpacks the bridge account `key`, returning `None` if it has not been created
+fn fetch_state<T: IsInitialized + Copy>(rpc: &RpcClient, key: &Pubkey) -> Result<Option<T>, Error> {
+    match rpc.get_account_with_commitment(key, commitment())?.value {
+        Some(account) if !account.data.is_empty() => {
+            let state: &T = Bridge::unpack_immutable(account.data.as_slice())?;
+            Ok(Some(*state))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Creates the transactions verifying the signatures of `vaa` on chain.
+///
+/// Signatures are verified in chunks of `SIGNATURES_PER_TX`. The transactions need to be
+/// executed in order before the VAA can be redeemed. Signatures that have already been verified
+/// on chain are skipped, so an interrupted verification can be resumed.
+pub fn verify_vaa(rpc: &RpcClient, pda: &Pda, payer: &Pubkey, vaa: &VAA) -> Result<Vec<Transaction>, Error> {
+    let guardian_set = fetch_guardian_set(rpc, pda, vaa.guardian_set_index)?;
+    let signature_state = fetch_signature_state(rpc, pda, vaa)?;
+    verify_vaa_remaining(pda, payer, vaa, &guardian_set, signature_state.as_ref())
+}
+
+/// Like `verify_vaa` but using an already fetched `guardian_set`
+pub fn verify_vaa_with_guardian_set(
+    pda: &Pda,
+    payer: &Pubkey,
+    vaa: &VAA,
+    guardian_set: &GuardianSet,
+) -> Result<Vec<Transaction>, Error> {
+    verify_vaa_remaining(pda, payer, vaa, guardian_set, None)
+}
+
+/// Like `verify_vaa_with_guardian_set` but skipping the signatures already stored in
+/// `signature_state`
+pub fn verify_vaa_remaining(
+    pda: &Pda,
+    payer: &Pubkey,
+    vaa: &VAA,
+    guardian_set: &GuardianSet,
+    signature_state: Option<&SignatureState>,
+) -> Result<Vec<Transaction>, Error> {
+    // Map signatures to guardian set
+    let mut signature_items: Vec<SignatureItem> = Vec::new();
+    for s in vaa.signatures.iter() {
+        if s.index >= guardian_set.len_keys {
+            return Err(Error::UnknownGuardian(s.index));
+        }
+        let mut item = SignatureItem {
+            signature: [0; 64 + 1],
+            key: guardian_set.keys[s.index as usize],
+            index: s.index,
+        };
+        item.signature[0..32].copy_from_slice(&s.r);
+        item.signature[32..64].copy_from_slice(&s.s);
+        item.signature[64] = s.v;
+
+        if let Some(state) = signature_state {
+            if state.signatures[s.index as usize][..] == item.signature[..] {
+                continue;
+            }
+        }
+        signature_items.push(item);
+    }
+
+    let vaa_hash = vaa.body_hash()?;
+    let vaa_body = vaa.signature_body()?;
+    let accounts = VerifySignaturesAccounts::new(pda, payer, vaa)?;
+
+    let mut verify_txs: Vec<Transaction> = Vec::new();
+    for (tx_index, chunk) in signature_items.chunks(SIGNATURES_PER_TX).enumerate() {
+        let mut secp_payload = Vec::new();
+        let mut signature_status = [-1i8; 20];
+
+        let data_offset = 1 + chunk.len() * 11;
+        let message_offset = data_offset + chunk.len() * 85;
+
+        // 1 number of signatures
+        secp_payload.write_u8(chunk.len() as u8).unwrap();
+
+        // Secp signature info description (11 bytes * n)
+        for (i, s) in chunk.iter().enumerate() {
+            secp_payload.write_u16::<LittleEndian>((data_offset + 85 * i) as u16).unwrap();
+            secp_payload.write_u8(0).unwrap();
+            secp_payload.write_u16::<LittleEndian>((data_offset + 85 * i + 65) as u16).unwrap();
+            secp_payload.write_u8(0).unwrap();
+            secp_payload.write_u16::<LittleEndian>(message_offset as u16).unwrap();
+            secp_payload.write_u16::<LittleEndian>(vaa_body.len() as u16).unwrap();
+            secp_payload.write_u8(0).unwrap();
+            signature_status[s.index as usize] = i as i8;
+        }
+
+        // Write signatures and addresses
+        for s in chunk.iter() {
+            secp_payload.write_all(&s.signature).unwrap();
+            secp_payload.write_all(&s.key).unwrap();
+        }
+
+        // Write body
+        secp_payload.write_all(&vaa_body).unwrap();
+
+        let secp_ix = Instruction {
+            program_id: secp256k1_program::id(),
+            data: secp_payload,
+            accounts: vec![],
+        };
+
+        let verify_ix = accounts.instruction(&VerifySigPayload {
+            signers: signature_status,
+            hash: vaa_hash,
+            initial_creation: signature_state.is_none() && tx_index == 0,
+            secp_instruction: 0,
+        })?;
+
+        verify_txs.push(Transaction::new_with_payer(&[secp_ix, verify_ix], Some(payer)))
+    }
+
+    Ok(verify_txs)
+}
+
+/// Creates the transaction executing `vaa` once its signatures have been verified
+///
+/// Inbound transfers whose target is not a token account are redeemed to the associated token
+/// account of the target wallet, which the bridge creates if it does not exist yet. The wrapped
+/// mint of a foreign asset is created with its first inbound transfer. `payer` pays the rent of
+/// the mint and its meta, which is not reimbursed since creating a wrapped asset is not part of
+/// executing the VAA.
+pub fn redeem(rpc: &RpcClient, pda: &Pda, payer: &Pubkey, vaa: &VAA) -> Result<Transaction, Error> {
+    let mut instructions = Vec::new();
+    let mut accounts = PostVAAAccounts::new(pda, payer, vaa)?;
+    if let PostVAAAction::TransferInNative { destination, .. }
+    | PostVAAAction::TransferInWrapped { destination, .. } = accounts.action
+    {
+        let target = rpc.get_account_with_commitment(&destination, commitment())?.value;
+        if target.map_or(true, |a| a.owner != spl_token::id()) {
+            accounts = accounts.redeem_to_wallet();
+        }
+    }
+    if let (PostVAAAction::TransferInWrapped { mint, .. }, Some(VAABody::Transfer(t))) =
+        (&accounts.action, &vaa.payload)
+    {
+        let mint = rpc.get_account_with_commitment(mint, commitment())?.value;
+        if mint.map_or(true, |a| a.data.is_empty()) {
+            instructions.push(create_wrapped(&pda.program_id, payer, t.asset)?);
+        }
+    }
+
+    // Signatures are checked by the signature state, so they are not submitted
+    let mut vaa = vaa.clone();
+    vaa.signatures = Vec::new();
+    instructions.push(accounts.instruction(vaa.serialize()?)?);
+
+    Ok(Transaction::new_with_payer(&instructions, Some(payer)))
+}
+
+/// Fetches the reimbursement ledger of the guardian submitting VAAs with `payer`
+pub fn fetch_ledger(rpc: &RpcClient, pda: &Pda, payer: &Pubkey) -> Result<GuardianLedger, Error> {
+    let key = pda.ledger(payer)?;
+    let account = rpc
+        .get_account_with_commitment(&key, commitment())?
+        .value
+        .ok_or(Error::AccountNotFound(key))?;
+    let ledger: &GuardianLedger = Bridge::unpack_immutable(account.data.as_slice())?;
+    Ok(*ledger)
+}
+
+/// Creates the transaction paying out the reimbursements owed to the guardian `payer`
+pub fn claim_reimbursement(pda: &Pda, payer: &Pubkey) -> Result<Transaction, Error> {
+    let ix = spl_bridge::instruction::claim_reimbursement(&pda.program_id, payer)?;
+
+    Ok(Transaction::new_with_payer(&[ix], Some(payer)))
+}
+
+/// Simulates the signed transaction `tx`, decoding the errors of the bridge `program_id`.
+///
+/// Simulating before sending surfaces the error a transaction would fail with instead of the
+/// opaque errors of a failed preflight.
+pub fn simulate(rpc: &RpcClient, program_id: &Pubkey, tx: &Transaction) -> Result<(), Error> {
+    let result = rpc
+        .simulate_transaction_with_config(
+            tx,
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                commitment: Some(CommitmentConfig {
+                    commitment: CommitmentLevel::SingleGossip,
+                }),
+                encoding: None,
+            },
+        )?
+        .value;
+    match result.err {
+        Some(e) => Err(Error::from_transaction_error(
+            program_id,
+            tx,
+            e,
+            result.logs.unwrap_or_default(),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Commitment used to fetch bridge state
+fn commitment() -> CommitmentConfig {
+    CommitmentConfig {
+        commitment: CommitmentLevel::Single,
+    }
+}
+
+/// A guardian signature together with the key it is checked against
+struct SignatureItem {
+    signature: [u8; 64 + 1],
+    key: [u8; 20],
+    index: u8,
+}
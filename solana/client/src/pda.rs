@@ -0,0 +1,193 @@
+//! Derived addresses of the bridge program
+
+use solana_sdk::pubkey::Pubkey;
+use spl_bridge::{
+    instruction::ForeignAddress,
+    state::{AssetMeta, Bridge},
+};
+
+use crate::error::Error;
+
+/// Derives the addresses of the accounts of a bridge instance.
+///
+/// The bridge address is derived once on creation and reused for all other derivations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pda {
+    /// the bridge program
+    pub program_id: Pubkey,
+    /// the bridge config account
+    pub bridge: Pubkey,
+}
+
+impl Pda {
+    pub fn new(program_id: &Pubkey) -> Result<Self, Error> {
+        Ok(Pda {
+            program_id: *program_id,
+            bridge: Bridge::derive_bridge_id(program_id)?,
+        })
+    }
+
+    /// Address of the guardian set with the given index
+    pub fn guardian_set(&self, index: u32) -> Result<Pubkey, Error> {
+        Ok(Bridge::derive_guardian_set_id(&self.program_id, &self.bridge, index)?)
+    }
+
+    /// Address of the account tracking the signatures of a VAA with the given body hash
+    pub fn signature_state(&self, hash: &[u8; 32], guardian_set_index: u32) -> Result<Pubkey, Error> {
+        Ok(Bridge::derive_signature_id(
+            &self.program_id,
+            &self.bridge,
+            hash,
+            guardian_set_index,
+        )?)
+    }
+
+    /// Address of the account marking a VAA with the given signature body as claimed
+    pub fn claim(&self, signature_body: Vec<u8>) -> Result<Pubkey, Error> {
+        Ok(Bridge::derive_claim_id(&self.program_id, &self.bridge, signature_body)?)
+    }
+
+    /// Address of the proposal of an outbound transfer
+    pub fn transfer_out_proposal(
+        &self,
+        asset: &AssetMeta,
+        target_chain: u8,
+        target: ForeignAddress,
+        sender: &Pubkey,
+        sequence: u32,
+    ) -> Result<Pubkey, Error> {
+        Ok(Bridge::derive_transfer_id(
+            &self.program_id,
+            &self.bridge,
+            asset.chain,
+            asset.address,
+            target_chain,
+            target,
+            sender.to_bytes(),
+            sequence,
+        )?)
+    }
+
+    /// Address of the transfer sequence of a sender token account
+    pub fn transfer_sequence(&self, sender: &Pubkey) -> Result<Pubkey, Error> {
+        Ok(Bridge::derive_sequence_id(&self.program_id, &self.bridge, sender)?)
+    }
+
+    /// Address of the VAA posted for a proposal
+    pub fn posted_vaa(&self, proposal: &Pubkey) -> Result<Pubkey, Error> {
+        Ok(Bridge::derive_posted_vaa_id(&self.program_id, &self.bridge, proposal)?)
+    }
+
+    /// Address of the mint of a wrapped asset
+    pub fn wrapped_mint(&self, asset: &AssetMeta) -> Result<Pubkey, Error> {
+        Ok(Bridge::derive_wrapped_asset_id(
+            &self.program_id,
+            &self.bridge,
+            asset.chain,
+            asset.decimals,
+            asset.address,
+        )?)
+    }
+
+    /// Address of the meta account of a wrapped mint
+    pub fn wrapped_meta(&self, mint: &Pubkey) -> Result<Pubkey, Error> {
+        Ok(Bridge::derive_wrapped_meta_id(&self.program_id, &self.bridge, mint)?)
+    }
+
+    /// Address of the reimbursement ledger of a guardian submitting VAAs with `payer`
+    pub fn ledger(&self, payer: &Pubkey) -> Result<Pubkey, Error> {
+        Ok(Bridge::derive_ledger_id(&self.program_id, &self.bridge, payer)?)
+    }
+
+    /// Address of the custody token account of a native mint and the bump seed of the address
+    pub fn custody(&self, mint: &Pubkey) -> Result<(Pubkey, u8), Error> {
+        Ok(Bridge::derive_custody_id_and_bump(&self.program_id, &self.bridge, mint)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spl_bridge::instruction::{CHAIN_ID_ETHEREUM, CHAIN_ID_SOLANA};
+
+    use super::*;
+
+    #[test]
+    fn addresses_match_bridge_derivations() {
+        let program_id = Pubkey::new_unique();
+        let pda = Pda::new(&program_id).unwrap();
+        let bridge = Bridge::derive_bridge_id(&program_id).unwrap();
+        assert_eq!(pda.bridge, bridge);
+
+        let sender = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let asset = AssetMeta {
+            address: [7u8; 32],
+            chain: CHAIN_ID_ETHEREUM,
+            decimals: 8,
+        };
+
+        assert_eq!(
+            pda.guardian_set(3).unwrap(),
+            Bridge::derive_guardian_set_id(&program_id, &bridge, 3).unwrap()
+        );
+        assert_eq!(
+            pda.signature_state(&[1u8; 32], 3).unwrap(),
+            Bridge::derive_signature_id(&program_id, &bridge, &[1u8; 32], 3).unwrap()
+        );
+        assert_eq!(
+            pda.claim(vec![2u8; 40]).unwrap(),
+            Bridge::derive_claim_id(&program_id, &bridge, vec![2u8; 40]).unwrap()
+        );
+        assert_eq!(
+            pda.transfer_out_proposal(&asset, CHAIN_ID_SOLANA, [3u8; 32], &sender, 9)
+                .unwrap(),
+            Bridge::derive_transfer_id(
+                &program_id,
+                &bridge,
+                CHAIN_ID_ETHEREUM,
+                [7u8; 32],
+                CHAIN_ID_SOLANA,
+                [3u8; 32],
+                sender.to_bytes(),
+                9,
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            pda.transfer_sequence(&sender).unwrap(),
+            Bridge::derive_sequence_id(&program_id, &bridge, &sender).unwrap()
+        );
+        assert_eq!(
+            pda.posted_vaa(&sender).unwrap(),
+            Bridge::derive_posted_vaa_id(&program_id, &bridge, &sender).unwrap()
+        );
+        assert_eq!(
+            pda.wrapped_mint(&asset).unwrap(),
+            Bridge::derive_wrapped_asset_id(&program_id, &bridge, CHAIN_ID_ETHEREUM, 8, [7u8; 32])
+                .unwrap()
+        );
+        assert_eq!(
+            pda.wrapped_meta(&mint).unwrap(),
+            Bridge::derive_wrapped_meta_id(&program_id, &bridge, &mint).unwrap()
+        );
+        assert_eq!(
+            pda.ledger(&sender).unwrap(),
+            Bridge::derive_ledger_id(&program_id, &bridge, &sender).unwrap()
+        );
+
+        // The bridge checks custody accounts against their canonical address
+        let (custody, bump) = pda.custody(&mint).unwrap();
+        assert_eq!(
+            custody,
+            Bridge::derive_custody_id(&program_id, &bridge, &mint).unwrap()
+        );
+        assert_eq!(
+            custody,
+            Pubkey::create_program_address(
+                &[b"custody", bridge.as_ref(), mint.as_ref(), &[bump]],
+                &program_id
+            )
+            .unwrap()
+        );
+    }
+}
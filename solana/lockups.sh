@@ -11,6 +11,6 @@ account=3C3m4tjTy4nSMkkYdqCDSiCWEgpDa6whvprvABdFGBiW
 token=6qRhs8oAuZYLd4zzaNnQHqdRyknrQQWDWQhALEN8UA7M
 
 while : ; do
-  cli lock "$bridge_address" "$account" "$token" 1000000000 "$chain_id_ethereum" "$RANDOM" "$recipient_address"
+  cli lock "$bridge_address" "$account" "$token" 1000000000 "$chain_id_ethereum" "$recipient_address"
   sleep 5
 done